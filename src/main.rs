@@ -7,43 +7,61 @@ extern crate sdl2_image;
 extern crate sdl2_mixer;
 extern crate sdl2_ttf;
 
-use sdl2::pixels::Color;
-
 use sdl2_image::{INIT_PNG};
 use sdl2_mixer::{AUDIO_S16LSB, DEFAULT_FREQUENCY, Music}; 
-use sdl2_ttf::{Font}; 
 
+use std::env;
 use std::f32;
-use std::path::Path;
-use std::rc::Rc;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
 
 use pongo::ball::Ball;
+use pongo::frame_pacer::FramePacing;
 use pongo::game::Game;
+use pongo::history::{self, ExportFormat, ExportTable};
+use pongo::match_rules::MatchRules;
 use pongo::net::Net;
 use pongo::paddle::Paddle;
-use pongo::score_card::ScoreCard;
+use pongo::replay::Replay;
+use pongo::scoreboard::Scoreboard;
+use pongo::settings::Settings;
 use pongo::ui::Ui;
 
 /// Assemble the game components and wire them together using dependency injection. 
 fn build() -> Game {
 
-    // Screen dimensions and background color.
+    // Screen dimensions. Everything is laid out in this logical space, which is scaled to fit
+    // however large the window is made.
     let screen_width = 800.;
     let screen_height = 600.;
-    let screen_background_color = Color::RGB(0x25, 0x25, 0x25); 
     
     // Initialize SDL and capture the window renderer for later use. 
     let sdl_ctx = sdl2::init().unwrap();
     let video_subsystem = sdl_ctx.video().unwrap();
     let window = video_subsystem.window("pongo", screen_width as u32, screen_height as u32)
         .position_centered()
+        .resizable()
         .build()
         .unwrap();
-    let renderer = window.renderer().build().unwrap();
+    // VSync has to be asked for as the renderer is created, so it only follows the settings
+    // when the game starts.
+    let settings = Settings::load_or_default();
+    let mut renderer_builder = window.renderer();
+    if settings.frame_pacing == FramePacing::VSync {
+        renderer_builder = renderer_builder.present_vsync();
+    }
+    let renderer = renderer_builder.build().unwrap();
     
     //sdl_ctx.mouse().set_relative_mouse_mode(true);
     sdl_ctx.mouse().show_cursor(false);
 
+    // Typed text is only wanted while the player is entering a name. SDL sends text input
+    // events from the start, which would turn the key that opens a name entry into its first
+    // letter.
+    video_subsystem.text_input().stop();
+
     // Initialize sdl_image for PNG image rendering. 
     sdl2_image::init(INIT_PNG);
     
@@ -60,23 +78,27 @@ fn build() -> Game {
     let pong_sound = sdl2_mixer::Music::from_file(pong_sound_path).unwrap();
 
     // Package the media we will use later on in the UI type. 
-    let ui = Ui::new(sdl_ctx, renderer, ttf_ctx, sdl_audio, ping_sound, pong_sound);
+    // The window is moved, sized or made fullscreen as the settings say, and everything is
+    // drawn in the colors and fonts of the theme they name.
+    let ui = Ui::new(screen_width, screen_height, sdl_ctx, renderer, ttf_ctx, sdl_audio, 
+                     ping_sound, pong_sound, &settings);
+    let theme = ui.theme.clone();
 
     // The net will run vertically across the center of the screen.
-    let net = Net::new(Color::RGB(0xff, 0xff, 0xff),
+    let net = Net::new(theme.net,
                        screen_width / 2. - 10. / 2.,
                        10.,
                        screen_height / (2 * 20 - 1) as f32,
                        20);
 
     // Our ball will launch from the center of the screen.
-    let ball = Ball::new(Color::RGB(0xff, 0xcc, 0x00), 
-                         screen_width / 2., 
-                         screen_height / 2., 
-                         11., 
-                         500.,
-                         f32::consts::PI * 50. / 180.,
-                         f32::consts::PI * 45. / 180.); 
+    let mut ball = Ball::new(theme.ball, 
+                             screen_width / 2., 
+                             screen_height / 2., 
+                             11., 
+                             500.,
+                             f32::consts::PI * 50. / 180.,
+                             f32::consts::PI * 45. / 180.); 
     
     // Common ball properties.
     let paddle_x_offset = 4.;
@@ -86,7 +108,7 @@ fn build() -> Game {
     
     // The left paddle starts in the left center of the screen and is controlled by the human
     // player.
-    let left_paddle = Paddle::new(Color::RGB(0x03, 0x91, 0xcf), 
+    let left_paddle = Paddle::new(theme.left_paddle, 
                                   paddle_x_offset, 
                                   paddle_initial_y,
                                   paddle_width,
@@ -96,38 +118,28 @@ fn build() -> Game {
 
     // The right paddle start in the right center of the screen and is controlled by the computer
     // palyer.
-    let right_paddle = Paddle::new(Color::RGB(0xeb, 0x4e, 0x3d), 
+    let mut right_paddle = Paddle::new(theme.right_paddle, 
                                   screen_width - (paddle_x_offset + paddle_width), 
                                   paddle_initial_y,
                                   paddle_width,
                                   paddle_height,
                                   300.);
+    // With shape cues on, the right paddle is striped and the left one solid.
+    right_paddle.striped = true;
+
+    // However large the ball is drawn, it is not drawn across the faces of the paddles.
+    ball.paddle_faces = (left_paddle.x + left_paddle.width, right_paddle.x);
   
-    let font_path = Path::new("assets/fonts/pixel.ttf");
-    let font = Rc::new(sdl2_ttf::Font::from_file(font_path, 128).unwrap());
-    let score_board_width = screen_width / 2. - 100.;
-    let score_board_x = screen_width / 2. - score_board_width / 2.;
-    let score_board_y = 5.;
-    let score_card_width = 80.;
-    let score_card_height = 60.;
-
-    let lscore_card = ScoreCard::new(Color::RGB(0x03, 0x91, 0xcf),
-                                     score_board_x + 5.,
-                                     score_board_y + 5.,
-                                     score_card_width,
-                                     score_card_height,
-                                     font.clone());
-
-    let rscore_card = ScoreCard::new(Color::RGB(0xeb, 0x4e, 0x3d),
-                                     score_board_x + score_board_width - 5. - score_card_width,
-                                     score_board_y + 5.,
-                                     score_card_width,
-                                     score_card_height,
-                                     font.clone());
+    // The scoreboard sits at the top of the court, either side of the net.
+    let scoreboard = Scoreboard::new(theme.left_paddle,
+                                     theme.right_paddle,
+                                     screen_width / 2.,
+                                     10.,
+                                     48,
+                                     14);
 
     // Assemble and return the game. We're ready to play!
     return Game::new(ui,
-                     screen_background_color,
                      screen_width,
                      screen_height,
                      40,
@@ -135,12 +147,154 @@ fn build() -> Game {
                      ball,
                      left_paddle,
                      right_paddle,
-                     lscore_card,
-                     rscore_card);
+                     scoreboard,
+                     settings);
 
 }
     
+/// Write the match history and aggregated statistics to standard output.
+fn export_stats(args: &[String]) {
+    let usage = "Usage: pongo stats export [--format json|csv] [--table matches|totals]";
+    if args.first().map_or(true, |arg| arg != "export") {
+        let _ = writeln!(&mut io::stderr(), "{}", usage);
+        process::exit(2);
+    }
+    let format = option_from_args(args, "--format", ExportFormat::Json, usage);
+    let table = option_from_args(args, "--table", ExportTable::Matches, usage);
+
+    let path = history::history_path();
+    let records = match history::load(&path) {
+        Ok(records) => records,
+        Err(e) => {
+            let _ = writeln!(&mut io::stderr(), "Unable to read {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    };
+    let stdout = io::stdout();
+    if let Err(e) = history::export(&mut stdout.lock(), &records, format, table) {
+        let _ = writeln!(&mut io::stderr(), "Unable to export statistics: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Read the value given after a flag on the command line, or `default` if the flag is left
+/// out. Exits with the usage if the value is missing or cannot be read.
+fn option_from_args<T>(args: &[String], flag: &str, default: T, usage: &str) -> T
+    where T: FromStr<Err = String> {
+    return match args.iter().position(|arg| arg == flag) {
+        Some(i) => match args.get(i + 1).map(|arg| arg.parse::<T>()) {
+            Some(Ok(value)) => value,
+            Some(Err(e)) => {
+                let _ = writeln!(&mut io::stderr(), "{}\n{}", e, usage);
+                process::exit(2);
+            },
+            None => {
+                let _ = writeln!(&mut io::stderr(), "{}", usage);
+                process::exit(2);
+            }
+        },
+        None => default
+    };
+}
+
+/// Read the match rules from the command line, if any are given. Rules left out keep their
+/// classic value of a single game to 5 points.
+fn rules_from_args(args: &[String]) -> Option<MatchRules> {
+    let flags = ["--points", "--win-by-two", "--games", "--sets", "--time-limit"];
+    if !args.iter().any(|arg| flags.contains(&&arg[..])) {
+        return None;
+    }
+    let usage = "Usage: pongo [--points N] [--win-by-two] [--games N] [--sets N] \
+                 [--time-limit SECS]";
+    let value_of = |flag: &str| -> Option<u32> {
+        let i = match args.iter().position(|arg| arg == flag) {
+            Some(i) => i,
+            None => return None
+        };
+        match args.get(i + 1).and_then(|arg| arg.parse::<u32>().ok()) {
+            Some(value) if value > 0 => Some(value),
+            _ => {
+                let _ = writeln!(&mut io::stderr(), "{} needs a number above 0\n{}", flag, usage);
+                process::exit(2);
+            }
+        }
+    };
+
+    let mut rules = MatchRules::first_to(value_of("--points").unwrap_or(5));
+    rules.win_by_two = args.iter().any(|arg| arg == "--win-by-two");
+    rules.games_per_set = value_of("--games").unwrap_or(1);
+    rules.sets = value_of("--sets").unwrap_or(1);
+    rules.time_limit_secs = value_of("--time-limit");
+    return Some(rules);
+}
+
 fn main() {
-    build().launch_then_block_until_exit();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // `pongo stats export --format json|csv` writes the match history to standard output.
+    if args.first().map_or(false, |arg| arg == "stats") {
+        export_stats(&args[1..]);
+        return;
+    }
+
+    // `pongo replay <file>` watches a recorded match instead of playing one.
+    if args.first().map_or(false, |arg| arg == "replay") {
+        let path = match args.get(1) {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let _ = writeln!(&mut io::stderr(), "Usage: pongo replay <file>");
+                process::exit(2);
+            }
+        };
+        let replay = match Replay::load(&path) {
+            Ok(replay) => replay,
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(), "Unable to load {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        };
+        build().play_replay(&replay);
+        return;
+    }
+
+    let mut game = build();
+
+    // Pass --points, --win-by-two, --games, --sets and --time-limit to change how a match is
+    // won for this run. Otherwise the rules in the settings apply. Joining someone else's
+    // match plays by their rules instead.
+    if let Some(rules) = rules_from_args(&args) {
+        game.set_rules(rules);
+    }
+
+    // Pass --record to keep a replay of every match played, optionally followed by the file to
+    // write the first one to. Later matches go to match-2.pgr, match-3.pgr and so on beside it.
+    if let Some(i) = args.iter().position(|arg| arg == "--record") {
+        let path = args.get(i + 1)
+            .and_then(|arg| if arg.starts_with("--") {None} else {Some(PathBuf::from(arg))});
+        game.record_replays(path);
+    }
+
+    // Pass --frame-log FILE to log the time each frame takes as CSV.
+    if let Some(i) = args.iter().position(|arg| arg == "--frame-log") {
+        let path = match args.get(i + 1) {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let _ = writeln!(&mut io::stderr(), "Usage: pongo --frame-log <file>");
+                process::exit(2);
+            }
+        };
+        if let Err(e) = game.log_frame_times(&path) {
+            let _ = writeln!(&mut io::stderr(), "Unable to log frame times to {}: {}", 
+                             path.display(), e);
+            process::exit(1);
+        }
+    }
+
+    // Pass --no-instant-replay to carry on straight away after each point.
+    if args.iter().any(|arg| arg == "--no-instant-replay") {
+        game.set_instant_replays(false);
+    }
+
+    game.launch_then_block_until_exit();
 }
 
@@ -0,0 +1,47 @@
+use std::str::FromStr;
+
+/// How well the computer plays. Higher levels move the computer's paddle faster, so it reaches
+/// more balls.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AiLevel {
+    Easy = 0,
+    Normal = 1,
+    Hard = 2
+}
+
+/// Every level, from easiest to hardest. A level's value indexes this array.
+pub const AI_LEVELS: [AiLevel; 3] = [AiLevel::Easy, AiLevel::Normal, AiLevel::Hard];
+
+impl AiLevel {
+
+    /// Speed of the computer's paddle in pixels per second, before any speedup.
+    pub fn paddle_speed(&self) -> f32 {
+        return match *self {
+            AiLevel::Easy => 220.,
+            AiLevel::Normal => 300.,
+            AiLevel::Hard => 400.
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            AiLevel::Easy => "easy",
+            AiLevel::Normal => "normal",
+            AiLevel::Hard => "hard"
+        };
+    }
+
+}
+
+impl FromStr for AiLevel {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AiLevel, String> {
+        return match AI_LEVELS.iter().find(|level| level.name() == s) {
+            Some(level) => Ok(*level),
+            None => Err(format!("unknown AI level '{}', expected easy, normal or hard", s))
+        };
+    }
+
+}
@@ -1,12 +1,16 @@
 extern crate rand;
 
+use pongo::checksum::{Checksummable, StateHasher};
 use pongo::ui::{Drawable,Ui};
+use rand::{Rng, SeedableRng, XorShiftRng};
 use rand::distributions::{IndependentSample, Range};
 use sdl2::pixels::Color;
 use sdl2_gfx::primitives::DrawRenderer;
+use std::f32;
 use super::Resettable;
 
 // The ball is rendered as a circle, but treated as a square to simplify game mechanics. 
+#[derive(Clone)]
 pub struct Ball {
     pub color: Color,                   
     pub initial_x: f32,         // The initial x location. Stored so that we can reset the ball.
@@ -14,14 +18,19 @@ pub struct Ball {
     pub x: f32,                 // x pixel coordinate of top left corner.
     pub y: f32,                 // y pixel coordinate of top left corner.
     pub diameter: f32,           
-    pub speed: f32,             // Speed in pixels per second. Never changes.
+    pub speed: f32,             // Speed in pixels per second. Only the developer
+                                // console changes it, for the match being played.
+    pub initial_speed: f32,     // Stored so that the next match starts at it again.
     pub speed_multiplier: f32,  // Used to adjust the speed.
     pub vx: f32,                // Horizontal velocity in pixels per second.
     pub vy: f32,                // Vertical velocity in pixels per second.
     pub max_launch_angle: f32,  // Maximum angle at which the ball will launch. 
-    pub max_bounce_angle: f32   // Maximum angle at which ball will bounce when hitting paddle.
+    pub max_bounce_angle: f32,  // Maximum angle at which ball will bounce when hitting paddle.
                                 // The angle is taken as up or down from an imaginary line
                                 // running perpendicular to the paddle (i.o.w. running horizontal)
+    pub paddle_faces: (f32, f32),   // x of the faces of the left and right paddles, which
+                                    // the ball is never drawn across.
+    rng: XorShiftRng            // Picks launch angles. Seeded so that a match can be replayed.
 }
 
 impl Ball {
@@ -42,16 +51,52 @@ impl Ball {
             y: y, 
             diameter: diameter, 
             speed: speed, 
+            initial_speed: speed,
             speed_multiplier: 1.0, 
             vx: 0., 
             vy: 0., 
             max_launch_angle: max_launch_angle, 
-            max_bounce_angle: max_bounce_angle 
+            max_bounce_angle: max_bounce_angle,
+            paddle_faces: (f32::NEG_INFINITY, f32::INFINITY),
+            rng: rand::thread_rng().gen()
         };
         
         ball.reset();
         return ball
     }
+
+    /// Seed the generator that picks launch angles. Launches that follow will be the same
+    /// every time the same seed is used.
+    pub fn seed(&mut self, seed: [u32; 4]) {
+        self.rng = XorShiftRng::from_seed(seed);
+    }
+
+    /// The generator that picks launch angles, as it stands now. Putting it back with
+    /// `restore_launch_rng` makes the launches that follow the same as they would be from here.
+    pub fn launch_rng(&self) -> XorShiftRng {
+        return self.rng.clone();
+    }
+
+    pub fn restore_launch_rng(&mut self, rng: XorShiftRng) {
+        self.rng = rng;
+    }
+
+    /// The radius the ball is drawn at. It grows around its center as large as the settings
+    /// ask, but no further than the walls and the faces of the paddles in front of it, so it
+    /// never looks like it touches one before it does. Near them it is drawn at its real size.
+    fn drawn_radius(&self, ui: &Ui) -> f32 {
+        let radius = self.diameter / 2.;
+        let (center_x, center_y) = (self.x + radius, self.y + radius);
+        let (left_face, right_face) = self.paddle_faces;
+        let mut room = center_y.min(ui.height - center_y);
+        if self.x >= left_face {
+            room = room.min(center_x - left_face);
+        }
+        if self.x + self.diameter <= right_face {
+            room = room.min(right_face - center_x);
+        }
+        return (radius * ui.object_scale as f32 / 100.).min(room).max(radius);
+    }
 }
 
 impl Resettable for Ball {
@@ -67,17 +112,17 @@ impl Resettable for Ball {
 
         // Calculate a new launch angle. The launch angle is always random, but never greater
         // than the configured maximum launch angle.
-        let mut rng = rand::thread_rng();
-        let launch_angle = Range::new(0., self.max_launch_angle).ind_sample(&mut rng);
+        let rng = &mut self.rng;
+        let launch_angle = Range::new(0., self.max_launch_angle).ind_sample(rng);
         
         // Posible direction can be either up (-1) or down (+1).
         let dir = [-1., 1.];
 
         // Use the sine of the angle to determine the vertical speed. Then, 
         // choose a direction (up or down) to select a vertical velocity.
-        let up_or_down = rand::sample(&mut rng, dir.into_iter(),1)[0]; 
+        let up_or_down = rand::sample(rng, dir.into_iter(),1)[0]; 
         let vy = launch_angle.sin() * self.speed * up_or_down; 
-        let left_or_right = rand::sample(&mut rng, dir.into_iter(),1)[0]; 
+        let left_or_right = rand::sample(rng, dir.into_iter(),1)[0]; 
         
         // Use Pythagoras to determine the horizontal speed. Then, choose a
         // direction (left or right) to select a horizontal velocity.
@@ -91,11 +136,37 @@ impl Resettable for Ball {
 
 impl Drawable for Ball {
 
+    /// The ball can be drawn larger than it is, around the same center, to make it easier to
+    /// see, while it has room. With shape cues on, a ring in the theme's shade sets it off from
+    /// the paddles and the net whatever their colors.
     fn draw(&self, ui: &mut Ui) {
         let x = self.x + self.diameter / 2.;
         let y = self.y + self.diameter / 2.;
-        let radius = self.diameter / 2.;
+        let radius = self.drawn_radius(ui);
         let _ = ui.renderer.filled_circle(x as i16, y as i16, radius as i16, self.color);
+        if ui.shape_cues {
+            let shade = ui.theme.shade;
+            let _ = ui.renderer.circle(x as i16, y as i16, radius as i16, shade);
+            let _ = ui.renderer.circle(x as i16, y as i16, radius as i16 - 1, shade);
+        }
     }
     
 }
+
+impl Checksummable for Ball {
+
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_f32(self.x);
+        hasher.write_f32(self.y);
+        hasher.write_f32(self.vx);
+        hasher.write_f32(self.vy);
+        hasher.write_f32(self.speed);
+        hasher.write_f32(self.speed_multiplier);
+    }
+
+    fn dump_state(&self) -> String {
+        return format!("ball x={} y={} vx={} vy={} speed={} speed_multiplier={}",
+                       self.x, self.y, self.vx, self.vy, self.speed, self.speed_multiplier);
+    }
+
+}
@@ -0,0 +1,310 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Incrementally builds a 64 bit FNV-1a checksum over simulation state. Floats are hashed by
+/// their bit patterns, so two peers only agree when their state is identical bit for bit.
+pub struct StateHasher {
+    hash: u64
+}
+
+impl StateHasher {
+
+    pub fn new() -> StateHasher {
+        return StateHasher { hash: 0xcbf29ce484222325 };
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.hash ^= value as u64;
+        self.hash = self.hash.wrapping_mul(0x100000001b3);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        for i in 0..4 {
+            self.write_u8((value >> (i * 8)) as u8);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        for i in 0..8 {
+            self.write_u8((value >> (i * 8)) as u8);
+        }
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.write_u32(value as u32);
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        // Treat both zeroes as the same value. Everything else is hashed exactly.
+        let value = if value == 0. {0.} else {value};
+        self.write_u32(unsafe { ::std::mem::transmute::<f32, u32>(value) });
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(if value {1} else {0});
+    }
+
+    pub fn finish(&self) -> u64 {
+        return self.hash;
+    }
+
+}
+
+/// Trait for types whose state takes part in the per tick simulation checksum.
+pub trait Checksummable {
+
+    /// Feed every field that influences the simulation into the hasher.
+    fn hash_state(&self, hasher: &mut StateHasher);
+
+    /// Human readable form of the same fields, used when reporting a desync.
+    fn dump_state(&self) -> String;
+
+}
+
+/// Checksum of the full simulation state at the end of a tick, along with a copy of the state
+/// that produced it. The copy is only turned into a readable dump when a desync is reported.
+#[derive(Clone)]
+pub struct TickChecksum<S> {
+    pub tick: u64,
+    pub checksum: u64,
+    pub state: S
+}
+
+impl<S: Checksummable> TickChecksum<S> {
+
+    /// Checksum the given state as the state at the end of a tick.
+    pub fn new(tick: u64, state: S) -> TickChecksum<S> {
+        let mut hasher = StateHasher::new();
+        state.hash_state(&mut hasher);
+        return TickChecksum { tick: tick, checksum: hasher.finish(), state: state };
+    }
+
+}
+
+/// The first tick at which the local simulation and a peer (or a replay) disagreed.
+pub struct Desync<S> {
+    pub tick: u64,
+    pub local: TickChecksum<S>,
+    pub remote_checksum: u64,
+    pub remote_dump: Option<String>    // Only available if the other side sent its state along.
+}
+
+impl<S: Checksummable> fmt::Display for Desync<S> {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Desync detected at tick {}", self.tick));
+        try!(writeln!(f, "Local checksum:  {:016x}", self.local.checksum));
+        try!(writeln!(f, "Remote checksum: {:016x}", self.remote_checksum));
+        try!(writeln!(f, "Local state:"));
+        try!(writeln!(f, "{}", self.local.state.dump_state()));
+        try!(writeln!(f, "Remote state:"));
+        match self.remote_dump {
+            Some(ref dump) => try!(writeln!(f, "{}", dump)),
+            None => try!(writeln!(f, "(not available)"))
+        }
+        return Ok(());
+    }
+
+}
+
+/// Keeps the checksums of recent ticks so they can be compared against those reported by a
+/// peer or stored in a replay. Checksums from the other side may arrive before or after the
+/// local simulation reaches the same tick, so both orders are handled.
+pub struct DesyncDetector<S> {
+    history: VecDeque<TickChecksum<S>>,             // Most recent local checksums, oldest first.
+    capacity: usize,                                // Number of local ticks to remember.
+    pending: HashMap<u64, (u64, Option<String>)>,   // Remote checksums for ticks not yet reached.
+    desync: Option<Desync<S>>                       // The first divergence found, if any.
+}
+
+impl<S: Checksummable + Clone> DesyncDetector<S> {
+
+    pub fn new(capacity: usize) -> DesyncDetector<S> {
+        return DesyncDetector {
+            history: VecDeque::new(),
+            capacity: capacity,
+            pending: HashMap::new(),
+            desync: Option::None
+        };
+    }
+
+    /// Record the local checksum for a tick and check it against any remote checksum that
+    /// arrived early. Remote checksums for ticks skipped over can no longer be checked and are
+    /// dropped.
+    pub fn record(&mut self, local: TickChecksum<S>) {
+        if let Some((remote_checksum, remote_dump)) = self.pending.remove(&local.tick) {
+            self.check(&local, remote_checksum, remote_dump);
+        }
+        let skipped: Vec<u64> = self.pending.keys().filter(|&&tick| tick < local.tick)
+            .cloned().collect();
+        for tick in skipped {
+            self.pending.remove(&tick);
+        }
+        self.history.push_back(local);
+        while self.history.len() > self.capacity {
+            self.history.pop_front();
+        }
+    }
+
+    /// Compare a checksum reported by the other side against the local one for the same tick.
+    /// Returns true if a desync has been detected, either now or earlier.
+    pub fn compare(&mut self, tick: u64, remote_checksum: u64, remote_dump: Option<String>)
+                   -> bool {
+        let local = self.history.iter().find(|c| c.tick == tick).map(|c| c.clone());
+        match local {
+            Some(local) => self.check(&local, remote_checksum, remote_dump),
+            None => {
+                // Only hold on to checksums for ticks we have yet to simulate. Anything older
+                // has already dropped out of the history and can no longer be checked.
+                let newest_tick = self.history.back().map(|c| c.tick);
+                if newest_tick.map_or(true, |newest_tick| tick > newest_tick) {
+                    self.pending.insert(tick, (remote_checksum, remote_dump));
+                }
+            }
+        }
+        return self.desync.is_some();
+    }
+
+    fn check(&mut self, local: &TickChecksum<S>, remote_checksum: u64,
+             remote_dump: Option<String>) {
+        if local.checksum == remote_checksum {
+            return;
+        }
+
+        // Only the first diverging tick is interesting. Everything after it follows from it.
+        let is_earlier = self.desync.as_ref().map_or(true, |desync| local.tick < desync.tick);
+        if is_earlier {
+            self.desync = Some(Desync {
+                tick: local.tick,
+                local: local.clone(),
+                remote_checksum: remote_checksum,
+                remote_dump: remote_dump
+            });
+        }
+    }
+
+    /// The most recent local checksum, if any tick has been recorded yet.
+    pub fn latest(&self) -> Option<&TickChecksum<S>> {
+        return self.history.back();
+    }
+
+    pub fn desync(&self) -> Option<&Desync<S>> {
+        return self.desync.as_ref();
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.pending.clear();
+        self.desync = Option::None;
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct Counter(u32);
+
+    impl Checksummable for Counter {
+
+        fn hash_state(&self, hasher: &mut StateHasher) {
+            hasher.write_u32(self.0);
+        }
+
+        fn dump_state(&self) -> String {
+            return format!("counter={}", self.0);
+        }
+
+    }
+
+    #[test]
+    fn hasher_is_deterministic() {
+        let mut a = StateHasher::new();
+        let mut b = StateHasher::new();
+        a.write_u64(42);
+        a.write_f32(1.5);
+        b.write_u64(42);
+        b.write_f32(1.5);
+        assert_eq!(a.finish(), b.finish());
+    }
+
+    #[test]
+    fn hasher_tells_values_apart() {
+        let mut a = StateHasher::new();
+        let mut b = StateHasher::new();
+        a.write_u32(1);
+        b.write_u32(2);
+        assert!(a.finish() != b.finish());
+    }
+
+    #[test]
+    fn hasher_treats_both_zeroes_alike() {
+        let mut a = StateHasher::new();
+        let mut b = StateHasher::new();
+        a.write_f32(0.);
+        b.write_f32(-0.);
+        assert_eq!(a.finish(), b.finish());
+    }
+
+    #[test]
+    fn matching_checksum_is_not_a_desync() {
+        let mut detector = DesyncDetector::new(8);
+        let local = TickChecksum::new(3, Counter(7));
+        let checksum = local.checksum;
+        detector.record(local);
+        assert!(!detector.compare(3, checksum, None));
+    }
+
+    #[test]
+    fn differing_checksum_is_a_desync() {
+        let mut detector = DesyncDetector::new(8);
+        detector.record(TickChecksum::new(3, Counter(7)));
+        let remote = TickChecksum::new(3, Counter(8)).checksum;
+        assert!(detector.compare(3, remote, None));
+        assert_eq!(detector.desync().map(|desync| desync.tick), Some(3));
+    }
+
+    #[test]
+    fn checksum_arriving_early_is_checked_when_reached() {
+        let mut detector = DesyncDetector::new(8);
+        let remote = TickChecksum::new(5, Counter(1)).checksum;
+        assert!(!detector.compare(5, remote, None));
+        detector.record(TickChecksum::new(5, Counter(2)));
+        assert_eq!(detector.desync().map(|desync| desync.tick), Some(5));
+    }
+
+    #[test]
+    fn checksum_for_skipped_tick_is_dropped() {
+        let mut detector = DesyncDetector::new(8);
+        let remote = TickChecksum::new(5, Counter(1)).checksum;
+        detector.compare(5, remote, None);
+        detector.record(TickChecksum::new(6, Counter(2)));
+        assert!(detector.pending.is_empty());
+        assert!(detector.desync().is_none());
+    }
+
+    #[test]
+    fn earliest_desync_is_kept() {
+        let mut detector = DesyncDetector::new(8);
+        for tick in 0..4 {
+            detector.record(TickChecksum::new(tick, Counter(tick as u32)));
+        }
+        detector.compare(3, 0, None);
+        detector.compare(1, 0, None);
+        detector.compare(2, 0, None);
+        assert_eq!(detector.desync().map(|desync| desync.tick), Some(1));
+    }
+
+    #[test]
+    fn dump_is_written_from_the_local_state() {
+        let mut detector = DesyncDetector::new(8);
+        detector.record(TickChecksum::new(0, Counter(9)));
+        detector.compare(0, 0, None);
+        let report = format!("{}", detector.desync().unwrap());
+        assert!(report.contains("counter=9"));
+    }
+
+}
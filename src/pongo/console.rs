@@ -0,0 +1,375 @@
+use pongo::text_entry::{TextEntry, TextEntryStatus};
+use pongo::ui::{ScalableFont, Ui};
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+
+use sdl2_gfx::primitives::DrawRenderer;
+
+use std::collections::VecDeque;
+
+/// Longest command that can be typed.
+const MAX_LINE_LEN: usize = 60;
+
+/// Lines of output kept on screen.
+const OUTPUT_LINES: usize = 8;
+
+/// Commands kept in the history.
+const HISTORY_SIZE: usize = 50;
+
+/// How opaque the shade behind the console is.
+const SHADE_ALPHA: u8 = 0xd8;
+
+/// Carries out a console command on `T`. Gets the words typed after the command, or after the
+/// variable for `set`, and returns the reply to show or what went wrong.
+pub type CommandHandler<T> = fn(&mut T, &[&str]) -> Result<String, String>;
+
+/// Lists the words tab offers for a value that depends on what is around when it is pressed,
+/// such as the themes on disk.
+pub type CompletionLister = fn() -> Vec<String>;
+
+/// Words tab offers for an argument.
+enum Completions {
+    Fixed(&'static [&'static str]),
+    Listed(CompletionLister)
+}
+
+impl Completions {
+
+    fn to_vec(&self) -> Vec<String> {
+        return match *self {
+            Completions::Fixed(words) => words.iter().map(|word| word.to_string()).collect(),
+            Completions::Listed(list) => list()
+        };
+    }
+
+}
+
+/// A command typed as its name followed by arguments.
+struct Command<T> {
+    name: &'static str,
+    usage: &'static str,                    // Shown by help, such as "score <left> <right>".
+    completions: &'static [&'static str],   // Offered by tab for the first argument.
+    handler: CommandHandler<T>
+}
+
+/// A setting changed with `set <name> <value>`.
+struct Variable<T> {
+    name: &'static str,
+    completions: Completions,               // Offered by tab for the value.
+    handler: CommandHandler<T>
+}
+
+/// The commands and variables the console knows about. Each part of the game adds its own.
+pub struct CommandRegistry<T> {
+    commands: Vec<Command<T>>,
+    variables: Vec<Variable<T>>
+}
+
+impl<T> CommandRegistry<T> {
+
+    pub fn new() -> CommandRegistry<T> {
+        return CommandRegistry { commands: Vec::new(), variables: Vec::new() };
+    }
+
+    pub fn add_command(&mut self,
+                       name: &'static str,
+                       usage: &'static str,
+                       completions: &'static [&'static str],
+                       handler: CommandHandler<T>) {
+        self.commands.push(Command {
+            name: name,
+            usage: usage,
+            completions: completions,
+            handler: handler
+        });
+    }
+
+    pub fn add_variable(&mut self,
+                        name: &'static str,
+                        completions: &'static [&'static str],
+                        handler: CommandHandler<T>) {
+        self.variables.push(Variable {
+            name: name,
+            completions: Completions::Fixed(completions),
+            handler: handler
+        });
+    }
+
+    /// Add a variable whose values tab lists afresh each time it is pressed.
+    pub fn add_listed_variable(&mut self,
+                               name: &'static str,
+                               list: CompletionLister,
+                               handler: CommandHandler<T>) {
+        self.variables.push(Variable {
+            name: name,
+            completions: Completions::Listed(list),
+            handler: handler
+        });
+    }
+
+    /// Words tab may complete the word at `position` to, given the words before it.
+    fn candidates(&self, words: &[&str], position: usize) -> Vec<String> {
+        if position == 0 {
+            let mut names: Vec<String> = vec!["clear".to_string(), "help".to_string(),
+                                              "set".to_string()];
+            names.extend(self.commands.iter().map(|c| c.name.to_string()));
+            return names;
+        }
+        if words[0] == "set" {
+            return match position {
+                1 => self.variables.iter().map(|v| v.name.to_string()).collect(),
+                2 => self.variables.iter()
+                    .find(|v| v.name == words[1])
+                    .map_or(Vec::new(), |v| v.completions.to_vec()),
+                _ => Vec::new()
+            };
+        }
+        if position == 1 {
+            if let Some(command) = self.commands.iter().find(|c| c.name == words[0]) {
+                return command.completions.iter().map(|word| word.to_string()).collect();
+            }
+        }
+        return Vec::new();
+    }
+
+}
+
+/// A command the game has to carry out, as found by the console.
+pub struct Invocation<T> {
+    pub handler: CommandHandler<T>,
+    pub args: Vec<String>
+}
+
+impl<T> Invocation<T> {
+
+    pub fn run(&self, target: &mut T) -> Result<String, String> {
+        let args: Vec<&str> = self.args.iter().map(|arg| &arg[..]).collect();
+        return (self.handler)(target, &args);
+    }
+
+}
+
+/// A drop-down command line for tuning the game while it runs. Commands typed are looked up
+/// in the registry. Up and down walk through earlier commands and tab completes the word being
+/// typed.
+pub struct Console<T> {
+    pub registry: CommandRegistry<T>,
+    pub is_open: bool,
+    entry: TextEntry,
+    output: VecDeque<String>,
+    history: Vec<String>,
+    history_pos: Option<usize>      // The earlier command being shown, if any.
+}
+
+impl<T> Console<T> {
+
+    pub fn new(registry: CommandRegistry<T>) -> Console<T> {
+        return Console {
+            registry: registry,
+            is_open: false,
+            entry: TextEntry::new("", MAX_LINE_LEN),
+            output: VecDeque::new(),
+            history: Vec::new(),
+            history_pos: None
+        };
+    }
+
+    pub fn open(&mut self, ui: &Ui) {
+        self.is_open = true;
+        self.entry.text.clear();
+        self.history_pos = None;
+        TextEntry::start(ui);
+    }
+
+    pub fn close(&mut self, ui: &Ui) {
+        self.is_open = false;
+        TextEntry::stop(ui);
+    }
+
+    /// Add a line of output, dropping the oldest once the console is full.
+    pub fn print(&mut self, line: &str) {
+        self.output.push_back(line.to_string());
+        while self.output.len() > OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    /// Show what a command replied.
+    pub fn print_result(&mut self, result: Result<String, String>) {
+        match result {
+            Ok(ref reply) if reply.is_empty() => {},
+            Ok(reply) => self.print(&reply),
+            Err(e) => self.print(&format!("error: {}", e))
+        }
+    }
+
+    /// Handle an event while the console is open. Returns the command to carry out once one
+    /// has been entered. Backtick and escape close the console.
+    pub fn handle_event(&mut self, ui: &Ui, event: &Event) -> Option<Invocation<T>> {
+        match *event {
+            Event::KeyDown { keycode: Some(Keycode::Backquote), .. } => {
+                self.close(ui);
+                return None;
+            },
+            // The backtick that opened or closes the console is not part of the command.
+            Event::TextInput { ref text, .. } if text.contains('`') => return None,
+            Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                let pos = match self.history_pos {
+                    Some(pos) => pos.saturating_sub(1),
+                    None if !self.history.is_empty() => self.history.len() - 1,
+                    None => return None
+                };
+                self.history_pos = Some(pos);
+                self.entry.text = self.history[pos].clone();
+                return None;
+            },
+            Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                if let Some(pos) = self.history_pos {
+                    if pos + 1 < self.history.len() {
+                        self.history_pos = Some(pos + 1);
+                        self.entry.text = self.history[pos + 1].clone();
+                    } else {
+                        self.history_pos = None;
+                        self.entry.text.clear();
+                    }
+                }
+                return None;
+            },
+            Event::KeyDown { keycode: Some(Keycode::Tab), .. } => {
+                self.complete();
+                return None;
+            },
+            _ => {}
+        }
+
+        match self.entry.handle_event(event) {
+            TextEntryStatus::Editing => return None,
+            TextEntryStatus::Cancelled => {
+                self.close(ui);
+                return None;
+            },
+            TextEntryStatus::Done => {
+                let line = self.entry.text.trim().to_string();
+                self.entry.text.clear();
+                self.history_pos = None;
+                if self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                    if self.history.len() > HISTORY_SIZE {
+                        self.history.remove(0);
+                    }
+                }
+                self.print(&format!("> {}", line));
+                return self.submit(&line);
+            }
+        }
+    }
+
+    /// Look up an entered command. Help and clear are handled here, everything else is left
+    /// to the game.
+    fn submit(&mut self, line: &str) -> Option<Invocation<T>> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let args: Vec<String> = words[1..].iter().map(|word| word.to_string()).collect();
+        match words[0] {
+            "clear" => {
+                self.output.clear();
+                return None;
+            },
+            "help" => {
+                let mut usages = vec!["set <variable> <value>".to_string()];
+                usages.extend(self.registry.commands.iter().map(|c| c.usage.to_string()));
+                self.print(&usages.join("   "));
+                let names: Vec<&str> = self.registry.variables.iter().map(|v| v.name).collect();
+                self.print(&format!("variables: {}", names.join(" ")));
+                return None;
+            },
+            "set" => {
+                if args.len() < 2 {
+                    self.print("usage: set <variable> <value>");
+                    return None;
+                }
+                let handler = self.registry.variables.iter()
+                    .find(|v| v.name == args[0])
+                    .map(|v| v.handler);
+                return match handler {
+                    Some(handler) => {
+                        Some(Invocation { handler: handler, args: args[1..].to_vec() })
+                    },
+                    None => {
+                        self.print(&format!("error: unknown variable '{}'", args[0]));
+                        None
+                    }
+                };
+            },
+            name => {
+                let handler = self.registry.commands.iter()
+                    .find(|c| c.name == name)
+                    .map(|c| c.handler);
+                return match handler {
+                    Some(handler) => Some(Invocation { handler: handler, args: args }),
+                    None => {
+                        self.print(&format!("error: unknown command '{}', try help", name));
+                        None
+                    }
+                };
+            }
+        }
+    }
+
+    /// Complete the word being typed. A single match is filled in. Several are shown, and the
+    /// word is extended as far as they agree.
+    fn complete(&mut self) {
+        let text = self.entry.text.clone();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (position, partial) = if text.is_empty() || text.ends_with(' ') {
+            (words.len(), "")
+        } else {
+            (words.len() - 1, words[words.len() - 1])
+        };
+        let matches: Vec<String> = self.registry.candidates(&words, position)
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(partial))
+            .collect();
+        if matches.is_empty() {
+            return;
+        }
+
+        let mut completed = matches[0].to_string();
+        for candidate in matches[1..].iter() {
+            while !candidate.starts_with(&completed[..]) {
+                completed.pop();
+            }
+        }
+        let mut line = words[..position].join(" ");
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&completed);
+        if matches.len() == 1 {
+            line.push(' ');
+        } else {
+            self.print(&matches.join("  "));
+        }
+        self.entry.text = line.chars().take(MAX_LINE_LEN).collect();
+    }
+
+    /// Draw the console over the top of the screen.
+    pub fn draw(&self, ui: &mut Ui, font: &ScalableFont) {
+        let (_, line_height) = ui.text_size(font, "> ");
+        let line_height = line_height + 2.;
+        let height = line_height * (OUTPUT_LINES + 1) as f32 + 10.;
+        let (shade_red, shade_green, shade_blue) = ui.theme.shade.rgb();
+        let shade_color = Color::RGBA(shade_red, shade_green, shade_blue, SHADE_ALPHA);
+        let _ = ui.renderer.box_(0, 0, ui.width as i16, height as i16, shade_color);
+
+        let (text_color, entry_color) = (ui.theme.text, ui.theme.highlight);
+        let mut y = 5. + line_height * (OUTPUT_LINES - self.output.len()) as f32;
+        for line in self.output.iter() {
+            ui.draw_text(font, line, text_color, 10., y);
+            y += line_height;
+        }
+        ui.draw_text(font, &format!("] {}", self.entry.display_text()), entry_color, 10., y);
+    }
+
+}
@@ -0,0 +1,102 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Directory the game keeps its files in between runs. `PONGO_DATA_DIR` overrides the platform
+/// default, which is `$XDG_DATA_HOME/pongo`, `%APPDATA%\pongo` or `~/.local/share/pongo`. Falls
+/// back to the current directory if none of these are known.
+pub fn data_dir() -> PathBuf {
+    if let Ok(dir) = env::var("PONGO_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    if let Ok(dir) = env::var("XDG_DATA_HOME") {
+        return PathBuf::from(dir).join("pongo");
+    }
+    if let Ok(dir) = env::var("APPDATA") {
+        return PathBuf::from(dir).join("pongo");
+    }
+    if let Ok(dir) = env::var("HOME") {
+        return PathBuf::from(dir).join(".local").join("share").join("pongo");
+    }
+    return PathBuf::from(".");
+}
+
+/// A file of lines in the data directory. Its first line names what it holds and the version of
+/// its layout. A file with any other first line, for instance one written by a newer version of
+/// the game, is refused rather than misread, and then overwritten.
+pub struct DataFile {
+    path: PathBuf,
+    header: &'static str,   // First word of the file.
+    version: u32,           // Follows the header. Bumped whenever the layout changes.
+    writable: bool          // False if the file could not be read, to avoid overwriting it.
+}
+
+impl DataFile {
+
+    pub fn new(path: &Path, header: &'static str, version: u32) -> DataFile {
+        return DataFile {
+            path: path.to_path_buf(),
+            header: header,
+            version: version,
+            writable: true
+        };
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    /// Stop the file from being written, since it could not be read.
+    pub fn read_only(mut self) -> DataFile {
+        self.writable = false;
+        return self;
+    }
+
+    /// Read the lines that follow the header. None if the file does not exist yet.
+    pub fn read_lines(&self) -> io::Result<Option<Vec<String>>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e)
+        };
+        let mut lines = BufReader::new(file).lines();
+        let header = match lines.next() {
+            Some(line) => try!(line),
+            None => String::new()
+        };
+        if header != format!("{} {}", self.header, self.version) {
+            return Err(io::Error::new(ErrorKind::InvalidData,
+                                      format!("unsupported {} file '{}'", self.header, header)));
+        }
+        let mut body = Vec::new();
+        for line in lines {
+            body.push(try!(line));
+        }
+        return Ok(Some(body));
+    }
+
+    /// Write the header followed by the given lines. The file is replaced in one go, so that a
+    /// crash halfway through leaves the previous version intact.
+    pub fn write_lines(&self, lines: &[String]) -> io::Result<()> {
+        if !self.writable {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            if !dir.as_os_str().is_empty() {
+                try!(fs::create_dir_all(dir));
+            }
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut file = try!(File::create(&tmp_path));
+            try!(writeln!(file, "{} {}", self.header, self.version));
+            for line in lines.iter() {
+                try!(writeln!(file, "{}", line));
+            }
+            try!(file.sync_all());
+        }
+        return fs::rename(&tmp_path, &self.path);
+    }
+
+}
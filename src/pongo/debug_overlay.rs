@@ -0,0 +1,72 @@
+use pongo::ball::Ball;
+use pongo::paddle::Paddle;
+use pongo::ui::{ScalableFont, Ui};
+
+use sdl2_gfx::primitives::DrawRenderer;
+
+/// Seconds of travel the velocity vector is drawn for.
+const VELOCITY_VECTOR_SECS: f32 = 0.2;
+
+/// What the debug overlay shows besides the hitboxes, worked out by the game.
+pub struct DebugInfo {
+    pub ball_vx: f32,               // Velocity in pixels per second, with speedups and slow
+    pub ball_vy: f32,               // motion applied.
+    pub tracking_y: Option<f32>,    // The height the computer moves its paddle towards, if it
+                                    // plays.
+    pub values: Vec<String>         // Current values, one per line.
+}
+
+/// Draw the hitboxes of the ball and paddles, where the ball is heading and, when the computer
+/// plays the right paddle, the point it moves towards and the bands it keeps that point in. The
+/// values are listed in the top left.
+pub fn draw(ui: &mut Ui,
+            font: &ScalableFont,
+            ball: &Ball,
+            lpaddle: &Paddle,
+            rpaddle: &Paddle,
+            info: &DebugInfo) {
+
+    let hitbox_color = ui.theme.positive;
+    let vector_color = ui.theme.accent;
+    let ai_color = ui.theme.highlight;
+    let text_color = ui.theme.text;
+
+    // The ball is drawn as a circle, but collides as a square.
+    let _ = ui.renderer.rectangle(ball.x as i16, ball.y as i16,
+                                  (ball.x + ball.diameter) as i16, (ball.y + ball.diameter) as i16,
+                                  hitbox_color);
+    for paddle in [lpaddle, rpaddle].iter() {
+        let _ = ui.renderer.rectangle(paddle.x as i16, paddle.y as i16,
+                                      (paddle.x + paddle.width) as i16,
+                                      (paddle.y + paddle.height) as i16,
+                                      hitbox_color);
+    }
+
+    let center_x = ball.x + ball.diameter / 2.;
+    let center_y = ball.y + ball.diameter / 2.;
+    let _ = ui.renderer.line(center_x as i16, center_y as i16,
+                             (center_x + info.ball_vx * VELOCITY_VECTOR_SECS) as i16,
+                             (center_y + info.ball_vy * VELOCITY_VECTOR_SECS) as i16,
+                             vector_color);
+
+    // The computer does not look ahead. It moves its paddle towards the height of the ball, or
+    // the middle once the ball heads away, while that point lies outside the band between a
+    // quarter and three quarters down the paddle.
+    if let Some(tracking_y) = info.tracking_y {
+        let band_left = (rpaddle.x - 30.) as i16;
+        let band_right = (rpaddle.x + rpaddle.width) as i16;
+        for fraction in [1. / 4., 3. / 4.].iter() {
+            let y = (rpaddle.y + rpaddle.height * fraction) as i16;
+            let _ = ui.renderer.hline(band_left, band_right, y, ai_color);
+        }
+        let (x, y) = (rpaddle.x as i16 - 15, tracking_y as i16);
+        let _ = ui.renderer.line(x - 5, y - 5, x + 5, y + 5, ai_color);
+        let _ = ui.renderer.line(x - 5, y + 5, x + 5, y - 5, ai_color);
+    }
+
+    let mut y = 30.;
+    for line in info.values.iter() {
+        ui.draw_text(font, line, text_color, 10., y);
+        y += 18.;
+    }
+}
@@ -0,0 +1,103 @@
+extern crate clock_ticks;
+
+use std::str::FromStr;
+use std::thread;
+
+/// How close to a frame's deadline the pacer stops sleeping and starts spinning. Sleeps can
+/// overshoot by a millisecond or more, depending on the system's timer resolution.
+const SPIN_NS: u64 = 2000000;
+
+/// How the game loop waits for the next frame.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FramePacing {
+    VSync = 0,      // Presenting waits for the display's refresh. Set when the window opens.
+    Limited = 1,    // Frames start at a steady rate, the game's frames per second.
+    Uncapped = 2    // Frames follow each other as fast as they can be drawn.
+}
+
+/// Every pacing mode. A mode's value indexes this array.
+pub const FRAME_PACINGS: [FramePacing; 3] =
+    [FramePacing::VSync, FramePacing::Limited, FramePacing::Uncapped];
+
+impl FramePacing {
+
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            FramePacing::VSync => "vsync",
+            FramePacing::Limited => "limited",
+            FramePacing::Uncapped => "uncapped"
+        };
+    }
+
+    pub fn next(&self) -> FramePacing {
+        return FRAME_PACINGS[(*self as usize + 1) % FRAME_PACINGS.len()];
+    }
+
+}
+
+impl FromStr for FramePacing {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FramePacing, String> {
+        return match FRAME_PACINGS.iter().find(|pacing| pacing.name() == s) {
+            Some(pacing) => Ok(*pacing),
+            None => Err(format!("unknown frame pacing '{}', expected vsync, limited or \
+                                 uncapped", s))
+        };
+    }
+
+}
+
+/// Keeps frames evenly spaced when pacing is limited. Each frame has a deadline a fixed
+/// interval after the previous one, so a slow frame is made up for by a shorter wait rather
+/// than pushing every later frame back. The pacer sleeps until just before the deadline, then
+/// spins for the last stretch to hit it precisely.
+pub struct FramePacer {
+    pub pacing: FramePacing,
+    frame_ns: u64,
+    deadline_ns: Option<u64>    // When the next frame should start.
+}
+
+impl FramePacer {
+
+    pub fn new(pacing: FramePacing, fps: u32) -> FramePacer {
+        return FramePacer {
+            pacing: pacing,
+            frame_ns: 1000000000 / fps as u64,
+            deadline_ns: None
+        };
+    }
+
+    /// Start afresh, for instance after play was held up by a menu, so that the frames that
+    /// follow are not rushed to catch up.
+    pub fn restart(&mut self) {
+        self.deadline_ns = None;
+    }
+
+    /// Wait until the next frame is due. Returns straight away unless pacing is limited, as
+    /// presenting the frame already waited for VSync.
+    pub fn wait(&mut self) {
+        if self.pacing != FramePacing::Limited {
+            return;
+        }
+        let now_ns = clock_ticks::precise_time_ns();
+        let deadline_ns = match self.deadline_ns {
+            Some(deadline_ns) => deadline_ns,
+            None => now_ns + self.frame_ns
+        };
+
+        // More than a frame behind, there is no catching up. Carry on from now.
+        if now_ns > deadline_ns + self.frame_ns {
+            self.deadline_ns = Some(now_ns + self.frame_ns);
+            return;
+        }
+
+        if deadline_ns > now_ns + SPIN_NS {
+            thread::sleep_ms(((deadline_ns - now_ns - SPIN_NS) / 1000000) as u32);
+        }
+        while clock_ticks::precise_time_ns() < deadline_ns {}
+        self.deadline_ns = Some(deadline_ns + self.frame_ns);
+    }
+
+}
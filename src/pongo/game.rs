@@ -1,25 +1,59 @@
 extern crate clock_ticks;
+extern crate rand;
 extern crate sdl2_mixer;
-extern crate sdl2_ttf;
 
+use pongo::ai_level::AiLevel;
 use pongo::ball::Ball;
+use pongo::checksum::{Checksummable, DesyncDetector, StateHasher, TickChecksum};
+use pongo::console::{CommandRegistry, Console};
+use pongo::debug_overlay::{self, DebugInfo};
+use pongo::frame_pacer::{FramePacer, FramePacing};
+use pongo::instant_replay::{self, InstantReplayBuffer, InstantReplayFrame};
+use pongo::history::{self, MatchRecord};
+use pongo::lan::{self, LanPeer, Message};
+use pongo::layout::{Align, Bounds, Element, Padding};
+use pongo::leaderboard::{Leaderboards, BOARDS};
+use pongo::leaderboard_screen;
+use pongo::lobby;
+use pongo::match_rules::{MatchRules, MatchScore};
+use pongo::match_stats::{MatchStats, Side};
+use pongo::menu::{self, Menu, MenuEvent, Widget};
 use pongo::net::Net;
 use pongo::paddle::Paddle;
-use pongo::score_card::ScoreCard;
-use pongo::ui::{Drawable,Ui};
-
+use pongo::palette::ColorPalette;
+use pongo::profile::{Controls, ProfileStore, PADDLE_COLORS};
+use pongo::profile_menu;
+use pongo::profiler::{Phase, Profiler};
+use pongo::replay::{self, Replay, ReplayHeader, ReplayRecorder, TickInput, TickRecord};
+use pongo::scoreboard::Scoreboard;
+use pongo::settings::Settings;
+use pongo::settings_menu;
+use pongo::snapshot::Snapshot;
+use pongo::spectator::{SnapshotBuffer, SpectatorServer};
+use pongo::summary;
+use pongo::text_renderer::TextStyle;
+use pongo::theme::Theme;
+use pongo::ui::{Drawable, ScalableFont, Ui};
+
+use rand::{Rng, XorShiftRng};
+
+use sdl2::controller::Button;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::mouse::Mouse;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 
+use sdl2_gfx::primitives::DrawRenderer;
 use sdl2_image::LoadTexture; 
 use sdl2_mixer::Music; 
-use sdl2_ttf::Font; 
 
 use std::cell::RefCell;
-use std::path::Path;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::str::FromStr;
 use std::thread;
 use std::vec::Vec;
 
@@ -44,9 +78,133 @@ impl GameLoopContext {
 
 }
 
+/// What the player picked on the welcome screen.
+enum MatchMode {
+    Quit,
+    VsComputer,
+    LocalVersus,
+    HostLan,
+    JoinLan,
+    Online,
+    Watch
+}
+
+/// Who controls the right paddle.
+enum Opponent {
+    Computer,               // The computer player, simulated locally.
+    LocalPlayer,            // A second player at this machine.
+    RemoteGuest(LanPeer),   // A player who joined our game. We run the simulation.
+    RemoteHost(LanPeer),    // The host of a game we joined. The host runs the simulation
+                            // and we draw the snapshots it sends.
+    Spectating(LanPeer),    // Nobody. We are watching a match simulated by this host.
+    Replayed(ReplayHeader)  // Whoever played in a recorded match, replaying their inputs.
+}
+
+/// Number of ticks between the keyframes a replay is seeked with.
+const KEYFRAME_INTERVAL_TICKS: u64 = 100;
+
+/// Buttons of the main menu, in order. Quit comes last.
+const MAIN_MENU: &'static [&'static str] =
+    &["Play vs AI", "Local Versus", "Online", "Replays", "Profiles", "High Scores", "Settings",
+      "Quit"];
+
+/// Ways of playing against someone elsewhere, in order.
+const ONLINE_MENU: &'static [&'static str] =
+    &["Online match", "Host a LAN game", "Join a LAN game", "Watch a LAN game"];
+
+/// What the second player in a local match is called.
+const SECOND_PLAYER_NAME: &'static str = "Player 2";
+
+/// Playback speeds a replay can be watched at.
+const REPLAY_SPEEDS: [f32; 5] = [0.25, 0.5, 1., 2., 4.];
+
+/// Milliseconds of play a rewind goes back.
+const REWIND_MS: u64 = 3000;
+
+/// Milliseconds of play shown again once a point is scored.
+const INSTANT_REPLAY_MS: u64 = 3000;
+
+/// Milliseconds between the snapshots sent to a LAN guest and to spectators, however fast frames
+/// come.
+const SNAPSHOT_INTERVAL_MS: u64 = 25;
+
+/// Number of rewinds the player gets per match.
+const REWINDS_PER_MATCH: u32 = 2;
+
+/// Speed in pixels per second of a paddle moved with the arrow keys.
+const KEYBOARD_PADDLE_SPEED: f32 = 500.;
+
+/// Point size of the text drawn over the court, such as the replay controls and the console.
+const HUD_FONT_SIZE: u16 = 14;
+
+/// Space between the text drawn over the court and the edges of the screen.
+const HUD_MARGIN: f32 = 10.;
+
+/// The complete simulation state at the start of a tick.
+#[derive(Clone)]
+struct SimState {
+    snapshot: Snapshot,
+    time_ball_last_speedup_ms: Option<u64>,
+    time_slow_motion_started_ms: Option<u64>,
+    ball_rng: XorShiftRng,      // Picks the launch angles still to come.
+    stats: MatchStats
+}
+
+/// Everything the per tick checksum covers, copied at the end of a tick so that a readable dump
+/// can be written should the checksum turn out to differ from the other side's.
+#[derive(Clone)]
+struct TickState {
+    tick: u64,
+    ball: Ball,
+    lpaddle: Paddle,
+    rpaddle: Paddle,
+    score: MatchScore,
+    clock_ms: u64,
+    slow_motions_remaining: u32,
+    slow_motion_active: bool,
+    rewinds_remaining: u32
+}
+
+impl Checksummable for TickState {
+
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u64(self.tick);
+        self.ball.hash_state(hasher);
+        self.lpaddle.hash_state(hasher);
+        self.rpaddle.hash_state(hasher);
+        self.score.hash_state(hasher);
+        hasher.write_u64(self.clock_ms);
+        hasher.write_u32(self.slow_motions_remaining);
+        hasher.write_bool(self.slow_motion_active);
+        hasher.write_u32(self.rewinds_remaining);
+    }
+
+    fn dump_state(&self) -> String {
+        return format!("tick={}\n{}\nleft {}\nright {}\n{}\nclock_ms={}\n\
+                        slow_motions_remaining={} slow_motion_active={} rewinds_remaining={}",
+                       self.tick,
+                       self.ball.dump_state(),
+                       self.lpaddle.dump_state(),
+                       self.rpaddle.dump_state(),
+                       self.score.dump_state(),
+                       self.clock_ms,
+                       self.slow_motions_remaining,
+                       self.slow_motion_active,
+                       self.rewinds_remaining);
+    }
+
+}
+
+/// A point a replay can be seeked to. Restoring a keyframe and simulating the recorded inputs
+/// from there arrives at the same state as playing from the start.
+struct Keyframe {
+    state: SimState,
+    rewind_history: VecDeque<SimState>     // A rewind made soon after must go back as far.
+}
+
 pub struct Game {
     ui: Ui,
-    background_color: Color,
+    settings: Settings,                     // Passed on to the UI wherever it needs them.
     width: f32,
     height: f32,
     fps: u32,
@@ -54,12 +212,47 @@ pub struct Game {
     ball: Rc<RefCell<Ball>>,
     lpaddle: Rc<RefCell<Paddle>>,
     rpaddle: Rc<RefCell<Paddle>>,
-    lscore_card: Rc<RefCell<ScoreCard>>,
-    rscore_card: Rc<RefCell<ScoreCard>>,
+    scoreboard: Rc<RefCell<Scoreboard>>,
+    sim_time_ms: u64,                       // Simulated time since the match began.
     time_ball_last_speedup_ms: Option<u64>,
     slow_motions_remaining: u32,
     time_slow_motion_started_ms: Option<u64>,
     running: bool,
+    rules: MatchRules,
+    score: MatchScore,
+    player_name: String,
+    profiles: ProfileStore,                 // The local players. The selected one is playing.
+    ai_level: AiLevel,
+    leaderboards: Leaderboards,
+    opponent: Opponent,
+    spectators: Option<SpectatorServer>,    // Set while others can watch our match.
+    spectator_count: u32,
+    spectator_buffer: SnapshotBuffer,       // Used while we are the one watching.
+    time_watch_last_sent_ms: u64,           // When we last asked the host to keep sending.
+    time_snapshot_last_sent_ms: u64,        // When we last sent a snapshot to those watching.
+    time_status_last_sent_ms: u64,          // When we last sent the guest the spectator count
+                                            // and a checksum.
+    hud_font: Rc<ScalableFont>,
+    console: Console<Game>,                 // Developer console, opened with backtick.
+    debug_overlay: bool,                    // Draw hitboxes and what the physics and AI see.
+    profiler: Profiler,
+    pacer: FramePacer,
+    tick: u64,                              // Number of game loop iterations since the match began.
+    desync_detector: DesyncDetector<TickState>,
+    desync_reported: bool,
+    console_changed: bool,                  // The console changed the match, so it does not count.
+    record_replays: bool,
+    replay_path: Option<PathBuf>,           // Where to record. A new file per match if not set.
+    replays_recorded: u32,                  // Matches recorded so far, to number their files.
+    recorder: Option<ReplayRecorder>,
+    instant_replays: bool,                  // Show each point again once it has been scored.
+    instant_replay_buffer: InstantReplayBuffer,
+    point_scored: bool,                     // A point was scored during the last tick.
+    rewinds_remaining: u32,
+    rewind_history: VecDeque<SimState>,     // The last few seconds of play, oldest first.
+    rewind_frames: Vec<InstantReplayFrame>, // Play a rewind went back over, to show backwards.
+    stats: MatchStats,
+    rematch: bool,                          // Play the computer again once this match is over.
     resettables: Vec<Rc<RefCell<Resettable>>>
 }
 
@@ -67,7 +260,6 @@ pub struct Game {
 impl Game {
 
     pub fn new(ui: Ui, 
-           background_color: Color, 
            width: f32,
            height: f32,
            fps: u32, 
@@ -75,12 +267,15 @@ impl Game {
            ball: Ball, 
            lpaddle: Paddle, 
            rpaddle: Paddle,
-           lscore_card: ScoreCard,
-           rscore_card: ScoreCard) -> Game { 
+           scoreboard: Scoreboard,
+           settings: Settings) -> Game { 
         
+        let frame_pacing = settings.frame_pacing;
+        let rules = settings.rules;
+        let hud_font = Rc::new(ScalableFont::new(&ui.theme.detail_font, HUD_FONT_SIZE));
         let mut game = Game { 
             ui: ui, 
-            background_color: background_color,
+            settings: settings,
             width: width,
             height: height,
             fps: fps, 
@@ -88,235 +283,1339 @@ impl Game {
             ball: Rc::new(RefCell::new(ball)), 
             lpaddle: Rc::new(RefCell::new(lpaddle)), 
             rpaddle: Rc::new(RefCell::new(rpaddle)), 
-            lscore_card: Rc::new(RefCell::new(lscore_card)), 
-            rscore_card: Rc::new(RefCell::new(rscore_card)), 
+            scoreboard: Rc::new(RefCell::new(scoreboard)), 
+            sim_time_ms: 0,
             time_ball_last_speedup_ms: Option::None,
             slow_motions_remaining: 3,
             time_slow_motion_started_ms: Option::None,
             running: false, 
+            rules: rules,
+            score: MatchScore::new(),
+            player_name: lan::local_player_name(),
+            profiles: ProfileStore::load_or_default(),
+            ai_level: AiLevel::Normal,
+            leaderboards: Leaderboards::load_or_default(),
+            opponent: Opponent::Computer,
+            spectators: Option::None,
+            spectator_count: 0,
+            spectator_buffer: SnapshotBuffer::new(),
+            time_watch_last_sent_ms: 0,
+            time_snapshot_last_sent_ms: 0,
+            time_status_last_sent_ms: 0,
+            hud_font: hud_font,
+            console: Console::new(console_commands()),
+            debug_overlay: false,
+            profiler: Profiler::new(fps),
+            pacer: FramePacer::new(frame_pacing, fps),
+            tick: 0,
+            desync_detector: DesyncDetector::new(256),
+            desync_reported: false,
+            console_changed: false,
+            record_replays: false,
+            replay_path: Option::None,
+            replays_recorded: 0,
+            recorder: Option::None,
+            instant_replays: true,
+            instant_replay_buffer: InstantReplayBuffer::new(INSTANT_REPLAY_MS),
+            point_scored: false,
+            rewinds_remaining: REWINDS_PER_MATCH,
+            rewind_history: VecDeque::new(),
+            rewind_frames: Vec::new(),
+            stats: MatchStats::new(),
+            rematch: false,
             resettables: Vec::new()
         };
         
         game.resettables.push(game.ball.clone());
         game.resettables.push(game.lpaddle.clone());
         game.resettables.push(game.rpaddle.clone());
-        game.resettables.push(game.lscore_card.clone());
-        game.resettables.push(game.rscore_card.clone());
+        game.apply_theme();
         game.reset();
 
         return game;
 
     }
+
+    /// Play every match hosted or played against the computer by these rules.
+    pub fn set_rules(&mut self, rules: MatchRules) {
+        self.rules = rules;
+    }
+
+    /// Log the time each frame took to a CSV file.
+    pub fn log_frame_times(&mut self, path: &Path) -> io::Result<()> {
+        return self.profiler.log_to(path);
+    }
+
+    /// Turn the instant replay shown after each point on or off.
+    pub fn set_instant_replays(&mut self, instant_replays: bool) {
+        self.instant_replays = instant_replays;
+    }
+
+    /// Record every match simulated on this machine to a replay file. With a path, the first
+    /// match is written to it and later ones are numbered after it. Without one, each match is
+    /// written to a new file in the replay directory.
+    pub fn record_replays(&mut self, path: Option<PathBuf>) {
+        self.record_replays = true;
+        self.replay_path = path;
+    }
     
-    /// Display welcome screen containing title, game instructions and credits while playing
-    /// funky music. The music stops when the game starts. 
-    fn show_welcome_screen(&mut self) -> bool {
+    /// Display the main menu under the title, with the controls of the selected profile and
+    /// the credits below it, while playing funky music. The music stops when a match starts.
+    fn show_welcome_screen(&mut self) -> MatchMode {
+        let items = MAIN_MENU.iter().map(|label| Widget::button(label)).collect();
+        let mut menu = Menu::new(items, 200.);
+        menu.line_height = 30.;
+        self.ui.show_cursor(true);
+        let mut _music = self.play_title_music();
 
-        // Play music in the background.
+        loop {
+            self.keep_window_mode();
+
+            // Closing the window anywhere, even deep inside another screen, quits the game.
+            if self.ui.quit_requested {
+                return MatchMode::Quit;
+            }
+            let mut chosen: Option<usize> = None;
+            while let Some(event) = self.ui.poll_event() {
+                match event {
+                    Event::Quit {..} => return MatchMode::Quit,
+                    _ => match menu.handle_event(&self.ui, &event) {
+                        MenuEvent::Activated(line) => chosen = Some(line),
+                        MenuEvent::Back => chosen = Some(MAIN_MENU.len() - 1),
+                        _ => {}
+                    }
+                }
+                if chosen.is_some() {
+                    break;
+                }
+            }
+
+            let mode = match chosen {
+                Some(0) => Some(MatchMode::VsComputer),
+                Some(1) => Some(MatchMode::LocalVersus),
+                Some(2) => self.choose_online_mode(),
+                Some(3) => {
+                    // Replays make their own sounds, so the music starts over afterwards.
+                    if self.choose_replay() {
+                        self.ui.show_cursor(true);
+                        _music = self.play_title_music();
+                    }
+                    None
+                },
+                // Pick who is playing, then come back here.
+                Some(4) => {
+                    profile_menu::choose(&mut self.ui, &mut self.profiles);
+                    None
+                },
+                Some(5) => {
+                    leaderboard_screen::show(&mut self.ui, &self.leaderboards, None);
+                    None
+                },
+                Some(6) => {
+                    self.show_settings();
+                    sdl2_mixer::Music::halt();
+                    _music = self.play_title_music();
+                    None
+                },
+                Some(_) => Some(MatchMode::Quit),
+                None => None
+            };
+            if let Some(mode) = mode {
+                sdl2_mixer::Music::halt();
+                return mode;
+            }
+
+            self.draw_welcome_screen(&menu);
+            thread::sleep_ms(50);
+        }
+    }
+
+    /// Start the title music playing on a loop, unless it is turned off. It plays until the
+    /// returned music is dropped or halted.
+    fn play_title_music(&self) -> Option<Music> {
+        if !self.settings.title_music {
+            return None;
+        }
         let music_path = Path::new("assets/sounds/more_monkey_island_band.wav");
         let music = sdl2_mixer::Music::from_file(music_path).unwrap();
         let _ = music.play(-1);
-        
+        return Some(music);
+    }
+
+    /// Let the player pick how to play against someone elsewhere. None if they back out.
+    fn choose_online_mode(&mut self) -> Option<MatchMode> {
+        let options: Vec<String> = ONLINE_MENU.iter().map(|label| label.to_string()).collect();
+        return match menu::choose(&mut self.ui, "Online", &options, "") {
+            Some(0) => Some(MatchMode::Online),
+            Some(1) => Some(MatchMode::HostLan),
+            Some(2) => Some(MatchMode::JoinLan),
+            Some(3) => Some(MatchMode::Watch),
+            _ => None
+        };
+    }
+
+    /// Let the player pick a recorded match from the replay directory and watch it. Returns
+    /// whether a replay was played.
+    fn choose_replay(&mut self) -> bool {
+        let paths = match replay::list_replays() {
+            Ok(paths) => paths,
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(), "Unable to list replays in {}: {}",
+                                 replay::REPLAY_DIR, e);
+                Vec::new()
+            }
+        };
+        let names: Vec<String> = paths.iter()
+            .map(|path| path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy().into_owned())
+            .collect();
+        let index = match menu::choose(&mut self.ui, "Replays", &names, "No replays recorded yet") {
+            Some(index) => index,
+            None => return false
+        };
+        let replay = match Replay::load(&paths[index]) {
+            Ok(replay) => replay,
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(), "Unable to load replay {}: {}",
+                                 paths[index].display(), e);
+                self.show_message("Unable to load replay");
+                return false;
+            }
+        };
+
+        // The replay plays to the rules it was recorded with.
+        let rules = self.rules;
+        sdl2_mixer::Music::halt();
+        self.ui.show_cursor(false);
+        self.play_replay(&replay);
+        self.rules = rules;
+        return true;
+    }
+
+    /// Show the settings screens, then take up the theme, how the ball and paddles are drawn
+    /// and any frame pacing chosen that needs no restart.
+    fn show_settings(&mut self) {
+        self.keep_window_mode();
+        settings_menu::show(&mut self.ui, &mut self.settings, &mut self.profiles,
+                            &mut self.ai_level, &mut self.rules);
+        self.ui.object_scale = self.settings.object_scale;
+        self.ui.shape_cues = self.settings.shape_cues;
+        self.apply_theme();
+        let pacing = self.settings.frame_pacing;
+        let vsync = FramePacing::VSync;
+        if pacing != self.pacer.pacing && pacing != vsync && self.pacer.pacing != vsync {
+            self.pacer.pacing = pacing;
+            self.pacer.restart();
+        }
+    }
+
+    /// Alt+Enter switches the window mode wherever the game is. Take up the mode it left the
+    /// window in, and save it.
+    fn keep_window_mode(&mut self) {
+        if self.ui.window_mode != self.settings.window_mode {
+            self.settings.window_mode = self.ui.window_mode;
+            self.settings.save_or_report();
+        }
+    }
+
+    /// Draw the title, the main menu, the instructions for the controls of the selected profile
+    /// and the credits.
+    fn draw_welcome_screen(&mut self, menu: &Menu) {
+
         // Draw background.
-        self.ui.renderer.set_draw_color(self.background_color);
+        let background = self.ui.theme.background;
+        self.ui.renderer.set_draw_color(background);
         self.ui.renderer.clear();
         
-        // Draw game title.
-        let title_font_path = Path::new("assets/fonts/djb_pokey_dots.ttf");
-        let title_font = sdl2_ttf::Font::from_file(title_font_path, 72).unwrap();
-        let mut title_x = self.width / 2. - 95. - 95. -47.;
-        let title_y = 100.;
-        self.draw_text(&title_font, "P", Color::RGB(0x03, 0x91, 0xcf), title_x, title_y);
-        title_x += 95.;
-        self.draw_text(&title_font, "O", Color::RGB(0xf6, 0x77, 0x34), title_x, title_y);
-        title_x += 95.;
-        self.draw_text(&title_font, "N", Color::RGB(0xfc, 0xef, 0x6d), title_x, title_y);
-        title_x += 95.;
-        self.draw_text(&title_font, "G", Color::RGB(0x6f, 0xc3, 0x2d), title_x, title_y);
-        title_x += 95.;
-        self.draw_text(&title_font, "O", Color::RGB(0xf0, 0x3b, 0x32), title_x, title_y);
+        // Draw game title, a letter in each of the theme's title colors.
+        let title_font = Rc::new(ScalableFont::new(&self.ui.theme.title_font, 72));
+        let title_colors = self.ui.theme.title;
+        let title = Element::row(Align::Start, 24., "PONGO".chars().zip(title_colors.iter())
+            .map(|(letter, color)| Element::label(&letter.to_string(), &title_font, *color))
+            .collect());
+        let title_area = Bounds::new(0., 70., self.width, 0.);
+        title.draw_in(&mut self.ui, title_area, Align::Center, Align::Start);
+
+        // Draw the menu.
+        let menu_font = Rc::new(ScalableFont::new(&self.ui.theme.heading_font, 26));
+        menu.draw(&mut self.ui, &menu_font);
        
-        // Draw instructions. 
-        let instruction_font_path = Path::new("assets/fonts/coffee_time.ttf");
-        let instruction_font = sdl2_ttf::Font::from_file(instruction_font_path, 26).unwrap();
-        self.draw_centered_text(&instruction_font, "Move the left paddle with the mouse...", 
-                                Color::RGB(0xff, 0xff, 0xff), 250.);
-        self.draw_centered_text(&instruction_font, "Left click the mouse to slow down time...", 
-                       Color::RGB(0xff, 0xff, 0xff), 300.);
-
-        // Press any key to start. 
-        let start_font_path = Path::new("assets/fonts/kghappysolid.ttf");
-        let start_font = sdl2_ttf::Font::from_file(start_font_path, 39).unwrap();
-        self.draw_centered_text(&start_font, "PRESS ANY KEY TO START!", 
-                                Color::RGB(0xec, 0x42, 0x35), 380.);
-        
-        // Draw credits. 
-        let credit_font_path = Path::new("assets/fonts/kg_cold_coffee.ttf");
-        let credit_font = sdl2_ttf::Font::from_file(credit_font_path, 12).unwrap();
-        self.draw_centered_text(&credit_font, "Programming by Wickus Martin", 
-                                Color::RGB(0xff, 0xff, 0xff), 500.);
-        self.draw_centered_text(&credit_font, "Music by Eric Matyas", 
-                                Color::RGB(0xff, 0xff, 0xff), 530.);
-
-        let mut start_game: Option<bool> = Option::None;
+        // Draw instructions and credits under the menu.
+        let instruction_font = Rc::new(ScalableFont::new(&self.ui.theme.text_font, 20));
+        let credit_font = Rc::new(ScalableFont::new(&self.ui.theme.detail_font, 12));
+        let instructions = match self.profiles.selected().controls {
+            Controls::Mouse => "Mouse moves the paddle, left click slows time, right click \
+                                rewinds",
+            Controls::Keyboard => "Arrow keys move the paddle, space slows time, backspace \
+                                   rewinds",
+            Controls::Controller => "Stick or d-pad moves the paddle, A slows time, B rewinds"
+        };
+        let profile_line = format!("Profile: {}  Computer level: {}",
+                                   self.profiles.selected().name, self.ai_level.name());
+        let text_color = self.ui.theme.text;
+        let footer = Element::column(Align::Center, 6., vec![
+            Element::label(instructions, &instruction_font, text_color),
+            Element::label(&profile_line, &instruction_font, self.player_color()),
+            Element::space(0., 8.),
+            Element::label("Programming by Wickus Martin", &credit_font, text_color),
+            Element::label("Music by Eric Matyas", &credit_font, text_color)
+        ]);
+        let footer_area = Bounds::new(0., 455., self.width, 0.);
+        footer.draw_in(&mut self.ui, footer_area, Align::Center, Align::Start);
+
         self.ui.renderer.present();
-        while start_game.is_none() {
-            thread::sleep_ms(100);
-            match self.ui.poll_event() {
-                Some(event) => {
-                    match event {
-                        // Quit
-                        Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                           start_game = Option::Some(false); 
+    }
+
+    /// Entry point into the game. Handles transition between showing the welcome screen, running
+    /// the game and returning to the welcome screen.
+    pub fn launch_then_block_until_exit(&mut self) {
+        loop {
+            // Closing the window anywhere, a match or a screen deep in the menus, ends the game.
+            if self.ui.quit_requested {
+                return;
+            }
+
+            // The game will exit when the user exits the welcome screen. Backing out of a LAN
+            // lobby returns to the welcome screen.
+            let mode = if self.rematch {MatchMode::VsComputer} else {self.show_welcome_screen()};
+            let rules = self.rules;
+            self.rematch = false;
+            self.apply_profile();
+            self.opponent = match mode {
+                MatchMode::Quit => return,
+                MatchMode::VsComputer => Opponent::Computer,
+                MatchMode::LocalVersus => Opponent::LocalPlayer,
+                MatchMode::HostLan => {
+                    match lobby::host(&mut self.ui, &self.player_name, self.rules) {
+                        Some(peer) => Opponent::RemoteGuest(peer),
+                        None => continue
+                    }
+                },
+                MatchMode::JoinLan => {
+                    match lobby::join(&mut self.ui, &self.player_name) {
+                        Some((peer, host_rules)) => {
+                            // The host's rules apply.
+                            self.rules = host_rules;
+                            Opponent::RemoteHost(peer)
                         },
-                        // Press any other key or click the mouse to start the game 
-                        Event::KeyDown { keycode: Some(..), .. } | Event::MouseButtonDown{..} => {
-                           start_game = Option::Some(true); 
+                        None => continue
+                    }
+                },
+                MatchMode::Online => {
+                    match lobby::online(&mut self.ui, &self.player_name, self.rules) {
+                        Some((peer, _, true)) => Opponent::RemoteGuest(peer),
+                        Some((peer, host_rules, false)) => {
+                            self.rules = host_rules;
+                            Opponent::RemoteHost(peer)
                         },
-                        _ => {}
+                        None => continue
+                    }
+                },
+                MatchMode::Watch => {
+                    match lobby::watch(&mut self.ui) {
+                        Some(peer) => Opponent::Spectating(peer),
+                        None => continue
+                    }
+                }
+            };
+
+            // Others on the LAN may watch a match we simulate for the player if they allow it,
+            // but not two players sharing this machine. Any of them may be recorded.
+            self.apply_ai_level();
+            match self.opponent {
+                Opponent::Computer | Opponent::RemoteGuest(..) => {
+                    if self.settings.allow_spectators {
+                        self.spectators = SpectatorServer::new(&self.player_name, 
+                                                               self.rules).ok();
+                    }
+                    self.start_recording();
+                },
+                Opponent::LocalPlayer => self.start_recording(),
+                _ => {}
+            }
+            
+            // Execute the game loop over and over again until the user quits or someone wins.
+            self.ui.show_cursor(false);
+            self.execute_game_loop();
+            
+            // Let a LAN opponent and spectators know we are gone, then transition back to the 
+            // welcome screen, but first revert the game to its initial state.
+            self.leave_match();
+            self.stop_recording();
+            self.rules = rules;
+            self.reset();
+
+        }
+    }
+
+    /// Play as the selected profile, with its name and paddle color.
+    fn apply_profile(&mut self) {
+        let color = self.player_color();
+        self.player_name = self.profiles.selected().name.clone();
+        self.lpaddle.borrow_mut().color = color;
+        self.scoreboard.borrow_mut().colors[Side::Left as usize] = color;
+    }
+
+    /// The color of the selected profile's paddle. The first of the paddle colors, which every
+    /// profile starts with, stands for the left paddle color of the theme. A palette for color
+    /// blindness overrides both.
+    fn player_color(&self) -> Color {
+        let palette = self.settings.palette;
+        if palette != ColorPalette::Theme {
+            return palette.colors(&self.ui.theme).left;
+        }
+        let profile = self.profiles.selected();
+        if profile.paddle_color == PADDLE_COLORS[0] {
+            return self.ui.theme.left_paddle;
+        }
+        return profile.color();
+    }
+
+    /// Draw the court and the HUD in the colors and fonts of the theme in use, with the players
+    /// in the colors of the chosen palette. The screens outside of a match look the theme up as
+    /// they are drawn.
+    fn apply_theme(&mut self) {
+        let theme = self.ui.theme.clone();
+        let right_color = self.settings.palette.colors(&theme).right;
+        self.net.borrow_mut().color = theme.net;
+        self.ball.borrow_mut().color = theme.ball;
+        self.rpaddle.borrow_mut().color = right_color;
+        self.scoreboard.borrow_mut().colors[Side::Right as usize] = right_color;
+        self.hud_font = Rc::new(ScalableFont::new(&theme.detail_font, HUD_FONT_SIZE));
+        self.apply_profile();
+    }
+
+    /// How well the computer plays the right paddle. Only a match against the computer uses the
+    /// chosen level, so that both machines in a LAN match agree on the paddle speed.
+    fn opponent_ai_level(&self) -> AiLevel {
+        return match self.opponent {
+            Opponent::Computer => self.ai_level,
+            Opponent::Replayed(ref header) => header.ai_level,
+            _ => AiLevel::Normal
+        };
+    }
+
+    fn apply_ai_level(&mut self) {
+        let ai_level = self.opponent_ai_level();
+        self.rpaddle.borrow_mut().speed = ai_level.paddle_speed();
+    }
+
+    /// Execute the game loop over and over again until the user quits or someone wins. 
+    fn execute_game_loop(&mut self) {
+
+        // The running flag is stored as a game wide field. This allows the flag to be changed
+        // at any point in the game. A method checking for user input can thus set the flag to
+        // false when the user indicates they want to quit. The same goes for a method that checks
+        // whether someone has won.
+        self.running = true;
+        let mut time_last_invocation = clock_ticks::precise_time_ms();
+        self.profiler.restart();
+        self.pacer.restart();
+
+        while self.running {
+            let time_this_invocation = clock_ticks::precise_time_ms();
+
+            // The delta time in millis is used to measure the duration of frame execution
+            // so that we can update the screen based on the time that has elapsed since the last
+            // frame was rendered. It is also used to cap the frame rate.
+            let dt_ms = time_this_invocation - time_last_invocation;
+            self.profiler.begin_frame();
+            self.profiler.begin_phase(Phase::Update);
+            self.execute_game_loop_iteration_per_frame(dt_ms); 
+            self.profiler.end_phase();
+            self.pacer.wait();
+            time_last_invocation = time_this_invocation;
+
+            // Show a rewind, or the point that was just scored again. The time spent watching
+            // does not count towards the next frame.
+            if !self.rewind_frames.is_empty() && self.running {
+                self.show_rewind();
+                time_last_invocation = clock_ticks::precise_time_ms();
+                self.profiler.restart();
+                self.pacer.restart();
+            } else if self.point_scored && self.running {
+                self.show_instant_replay();
+                time_last_invocation = clock_ticks::precise_time_ms();
+                self.profiler.restart();
+                self.pacer.restart();
+            }
+            self.point_scored = false;
+        } 
+
+    }
+    
+    /// Called once per frame. Essentially, an iteration of the game loop. 
+    fn execute_game_loop_iteration_per_frame(&mut self, dt_ms: u64) {
+        let mut ctx = GameLoopContext::new(dt_ms as f32 / 1000.);
+        let ctx = &mut ctx;
+
+        // The match stands still while the developer console is open.
+        if self.console.is_open {
+            self.run_console(ctx);
+            return;
+        }
+
+        // When we joined or are watching someone else's game, the host runs the simulation. 
+        match self.opponent {
+            Opponent::RemoteHost(..) => {
+                self.follow_host(ctx);
+                return;
+            },
+            Opponent::Spectating(..) => {
+                self.follow_as_spectator(ctx);
+                return;
+            },
+            _ => {}
+        }
+        
+        // Gather everything that feeds into this tick, then run the simulation on it. Keeping the
+        // two apart means a recorded match can be run again from its inputs alone.
+        let input = self.poll_input(dt_ms as u32);
+        if !self.running {
+            return;
+        }
+        self.simulate_tick(ctx, &input);
+        self.record_tick(&input);
+        
+        // Draw objects.
+        self.draw(ctx);
+
+        // Play audio.
+        self.play_audio(ctx);
+
+        // Let a LAN guest and spectators see the outcome of this tick.
+        self.send_snapshots();
+
+        // Check to see if either the human (left paddle) or computer (right paddle) has won.
+        self.check_for_win();
+    }
+
+    /// Advance the simulation by one tick. Everything that happens here follows from the input
+    /// and the state the tick started in, so it plays out the same way every time.
+    fn simulate_tick(&mut self, ctx: &mut GameLoopContext, input: &TickInput) {
+
+        // Go back a few seconds if the player asked to, then remember where this tick started
+        // in case they ask later.
+        if input.rewind {
+            self.rewind();
+        }
+        self.rewind_history.push_back(self.sim_state());
+        let oldest_kept_ms = self.sim_time_ms.saturating_sub(REWIND_MS);
+        while self.rewind_history.front()
+                  .map_or(false, |state| state.snapshot.clock_ms < oldest_kept_ms) {
+            self.rewind_history.pop_front();
+        }
+
+        // Move objects. The left paddle is moved based on user input. 
+        self.sim_time_ms += input.dt_ms as u64;
+        self.stats.tick(input.dt_ms);
+        self.move_ball(ctx);
+        self.move_left_paddle(ctx, input);
+        self.move_right_paddle(ctx, input);
+
+        // Once the time limit is up, whoever is ahead wins.
+        if let Some(time_limit_ms) = self.rules.time_limit_ms() {
+            if self.sim_time_ms >= time_limit_ms {
+                self.score.time_up();
+            }
+        }
+
+        // End slow motion mode if duration has elapsed.
+        if let Some(time_slow_motion_started_ms) = self.time_slow_motion_started_ms {
+            if self.sim_time_ms - time_slow_motion_started_ms >= 5000 {
+                self.time_slow_motion_started_ms = None;
+            }
+        }
+
+        // Record a checksum of the simulation state so that drift between peers or against a
+        // replay can be caught at the tick where it first happened.
+        self.record_checksum();
+    }
+
+    /// Read the mouse or keyboard, and the paddle movements of a LAN guest, for the coming tick.
+    /// Only one event is handled per frame.
+    fn poll_input(&mut self, dt_ms: u32) -> TickInput {
+        let mut input = TickInput::new(dt_ms);
+        let controls = self.profiles.selected().controls;
+        match self.ui.poll_event() {
+            Some(event) => {
+                match event {
+                    // Quit the game and return back to the welcome screen.
+                    Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                        self.running = false;
+                    },
+                    // Show or hide the frame rate and frame times, or the debug overlay.
+                    Event::KeyDown { keycode: Some(key), .. } if is_debug_display_key(key) => {
+                        self.toggle_debug_display(key);
+                    },
+                    // Open the developer console. Only against the computer, since the match
+                    // stands still while it is open and its commands change the match from
+                    // outside the simulation.
+                    Event::KeyDown { keycode: Some(Keycode::Backquote), .. } => {
+                        if let Opponent::Computer = self.opponent {
+                            self.console.open(&self.ui);
+                        }
+                    },
+                    // Leave the match with the controller's back button.
+                    Event::ControllerButtonDown { button: Button::Back, .. } 
+                        if controls == Controls::Controller => {
+                        self.running = false;
+                    },
+                    // Rewind time. Only against the computer, which does not mind having its
+                    // points taken back.
+                    Event::MouseButtonDown{mouse_btn: Mouse::Right, ..} | 
+                        Event::KeyDown { keycode: Some(Keycode::Backspace), repeat: false, .. } => {
+                        if let Opponent::Computer = self.opponent {
+                            input.rewind = true;
+                        }
+                    },
+                    Event::ControllerButtonDown { button: Button::B, .. } 
+                        if controls == Controls::Controller => {
+                        if let Opponent::Computer = self.opponent {
+                            input.rewind = true;
+                        }
+                    },
+                    // Enter slow motion mode.
+                    Event::MouseButtonDown{..} | 
+                        Event::KeyDown { keycode: Some(Keycode::Space), repeat: false, .. } => {
+                        input.slow_motion = true;
+                    },
+                    Event::ControllerButtonDown { button: Button::A, .. } 
+                        if controls == Controls::Controller => {
+                        input.slow_motion = true;
+                    },
+                    // Move left paddle with mouse. 
+                    Event::MouseMotion{y, ..} if controls == Controls::Mouse => {
+                        input.lpaddle_y = Some(y as f32);
                     }
+                    _ => {}
+                }
+            },
+            None => {}
+        }
+
+        // Move left paddle with the arrow keys or a controller for as long as they are held.
+        // The position it ends up at is the input, the same as with the mouse.
+        let distance = KEYBOARD_PADDLE_SPEED * dt_ms as f32 / 1000.;
+        let direction = self.held_direction(controls);
+        if direction != 0. {
+            input.lpaddle_y = Some(self.lpaddle.borrow().y + direction * distance);
+        }
+        match self.opponent {
+            Opponent::RemoteGuest(..) => input.rpaddle_y = self.receive_from_guest(),
+            Opponent::LocalPlayer => {
+                let direction = self.second_player_direction(controls);
+                if direction != 0. {
+                    input.rpaddle_y = Some(self.rpaddle.borrow().y + direction * distance);
+                }
+            },
+            _ => {}
+        }
+        return input;
+    }
+
+    /// Which way the player is holding their paddle: from -1 for up to 1 for down, and 0 when
+    /// it is left alone or follows the mouse. With the keyboard, the arrow keys are held. With
+    /// a controller, the d-pad or left stick of the first one connected.
+    fn held_direction(&self, controls: Controls) -> f32 {
+        let mut direction = 0.;
+        match controls {
+            Controls::Mouse => {},
+            Controls::Keyboard => {
+                if self.ui.is_key_pressed(Scancode::Up) {
+                    direction -= 1.;
+                }
+                if self.ui.is_key_pressed(Scancode::Down) {
+                    direction += 1.;
+                }
+            },
+            Controls::Controller => direction = self.ui.controller_direction(0)
+        }
+        return direction;
+    }
+
+    /// Which way the second player in a local match is holding the right paddle, with the W and
+    /// S keys or the first controller the first player is not using.
+    fn second_player_direction(&self, first_player_controls: Controls) -> f32 {
+        let controller = if first_player_controls == Controls::Controller {1} else {0};
+        let mut direction = self.ui.controller_direction(controller);
+        if self.ui.is_key_pressed(Scancode::W) {
+            direction -= 1.;
+        }
+        if self.ui.is_key_pressed(Scancode::S) {
+            direction += 1.;
+        }
+        return direction.max(-1.).min(1.);
+    }
+
+    /// The first serve goes to whoever the ball launches away from.
+    fn serve_from_launch(&mut self) {
+        self.score.server = if self.ball.borrow().vx > 0. {Side::Left} else {Side::Right};
+    }
+
+    /// Start recording the match about to be played, if replays are being recorded. The ball is
+    /// given a fresh seed, which goes into the replay so that its launches can be repeated.
+    fn start_recording(&mut self) {
+        let mut seed: [u32; 4] = rand::thread_rng().gen();
+        while seed == [0; 4] {
+            seed = rand::thread_rng().gen();
+        }
+        {
+            let mut ball = self.ball.borrow_mut();
+            ball.seed(seed);
+            ball.reset();
+        }
+        self.serve_from_launch();
+        if !self.record_replays {
+            return;
+        }
+
+        let (right_name, right_is_guest) = match self.opponent {
+            Opponent::RemoteGuest(ref peer) => (peer.name.clone(), true),
+            Opponent::LocalPlayer => (SECOND_PLAYER_NAME.to_string(), true),
+            _ => ("Computer".to_string(), false)
+        };
+        let header = ReplayHeader {
+            seed: seed,
+            width: self.width,
+            height: self.height,
+            fps: self.fps,
+            rules: self.rules,
+            slow_motions: self.slow_motions_remaining,
+            left_name: self.player_name.clone(),
+            right_name: right_name,
+            right_is_guest: right_is_guest,
+            ai_level: self.opponent_ai_level()
+        };
+        self.replays_recorded += 1;
+        let recorder = match self.replay_path {
+            Some(ref path) => {
+                ReplayRecorder::create(&replay::numbered_path(path, self.replays_recorded), &header)
+            },
+            None => ReplayRecorder::create_timestamped(&header)
+        };
+        match recorder {
+            Ok(recorder) => self.recorder = Some(recorder),
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(), "Unable to record replay: {}", e);
+            }
+        }
+    }
+
+    /// Write the input of the tick just simulated to the replay. Whenever the simulated time
+    /// passes a whole second, the checksum of the resulting state goes along with it.
+    fn record_tick(&mut self, input: &TickInput) {
+        let mut failed = false;
+        if let Some(ref mut recorder) = self.recorder {
+            let tick_started_ms = self.sim_time_ms.saturating_sub(input.dt_ms as u64);
+            let second_passed = self.sim_time_ms / 1000 != tick_started_ms / 1000;
+            let checksum = self.desync_detector.latest()
+                .and_then(|c| if second_passed {Some(c.checksum)} else {None});
+            let record = TickRecord { input: *input, checksum: checksum };
+            if let Err(e) = recorder.record(&record) {
+                let _ = writeln!(&mut io::stderr(), "Unable to record replay: {}", e);
+                failed = true;
+            }
+        }
+        if failed {
+            self.recorder = None;
+        }
+    }
+
+    /// Close the replay of the match that just ended.
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            let path = recorder.path.clone();
+            match recorder.finish() {
+                Ok(()) => println!("Replay saved to {}", path.display()),
+                Err(e) => {
+                    let _ = writeln!(&mut io::stderr(), "Unable to record replay: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Watch a recorded match. The recorded inputs are fed through the simulation again, so the
+    /// match plays out exactly as it did. Space pauses and up and down change the speed. Left
+    /// and right skip five seconds back or forward, or a single tick while paused.
+    pub fn play_replay(&mut self, replay: &Replay) {
+        let header = &replay.header;
+        if header.width != self.width || header.height != self.height {
+            let _ = writeln!(&mut io::stderr(), 
+                             "The replay was recorded on a {}x{} court and may play back \
+                              differently", header.width, header.height);
+        }
+        self.reset();
+        self.rules = header.rules;
+        self.slow_motions_remaining = header.slow_motions;
+        {
+            let mut ball = self.ball.borrow_mut();
+            ball.seed(header.seed);
+            ball.reset();
+        }
+        self.serve_from_launch();
+        self.opponent = Opponent::Replayed(header.clone());
+        self.apply_ai_level();
+
+        let total_ticks = replay.ticks.len() as u64;
+        let total_ms = replay.ticks.iter().fold(0, |sum, record| sum + record.input.dt_ms as u64);
+        let skip_ticks = header.fps as u64 * 5;
+        let mut keyframes: Vec<Keyframe> = Vec::new();
+        let mut speed_index = 2;
+        let mut paused = false;
+        let mut ticks_owed = 0.;    // Ticks to play so far at the current speed.
+
+        self.running = true;
+        let mut time_last_invocation = clock_ticks::precise_time_ms();
+        self.profiler.restart();
+        self.pacer.restart();
+        while self.running {
+            let time_this_invocation = clock_ticks::precise_time_ms();
+            let dt_ms = time_this_invocation - time_last_invocation;
+            let mut ctx = GameLoopContext::new(dt_ms as f32 / 1000.);
+            self.profiler.begin_frame();
+
+            let mut seek_to: Option<u64> = None;
+            while let Some(event) = self.ui.poll_event() {
+                let step = if paused {1} else {skip_ticks};
+                match event {
+                    Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                        self.running = false;
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                        paused = !paused;
+                    },
+                    Event::KeyDown { keycode: Some(key), .. } if is_debug_display_key(key) => {
+                        self.toggle_debug_display(key);
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                        speed_index = (speed_index + 1).min(REPLAY_SPEEDS.len() - 1);
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                        speed_index = if speed_index > 0 {speed_index - 1} else {0};
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
+                        seek_to = Some(if self.tick > step {self.tick - step} else {0});
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
+                        seek_to = Some((self.tick + step).min(total_ticks));
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Home), .. } => {
+                        seek_to = Some(0);
+                    },
+                    _ => {}
+                }
+            }
+
+            // Slow speeds play a tick every few frames, fast speeds several ticks per frame. 
+            if let Some(tick) = seek_to {
+                self.seek_replay(replay, &mut keyframes, tick);
+                ticks_owed = 0.;
+            } else if !paused {
+                ticks_owed += REPLAY_SPEEDS[speed_index];
+                while ticks_owed >= 1. && self.tick < total_ticks {
+                    let audible = self.replay_tick(replay, &mut keyframes);
+                    ctx.audible_queue.extend(audible);
+                    ticks_owed -= 1.;
+                }
+            }
+
+            ctx.layered_draw_queue[1].push(self.ball.clone());
+            ctx.layered_draw_queue[1].push(self.lpaddle.clone());
+            ctx.layered_draw_queue[1].push(self.rpaddle.clone());
+            self.draw_scene(&mut ctx);
+            let state = if self.tick >= total_ticks {"  END"} else if paused {"  PAUSED"} else {""};
+            let status = format!("REPLAY  {}x  {} / {}{}", REPLAY_SPEEDS[speed_index], 
+                                 format_time(self.sim_time_ms), format_time(total_ms), state);
+            self.draw_hud_text(&status, Align::Start);
+            self.ui.renderer.present();
+            self.play_audio(&mut ctx);
+
+            self.pacer.wait();
+            time_last_invocation = time_this_invocation;
+        }
+
+        self.opponent = Opponent::Computer;
+        self.reset();
+    }
+
+    /// Simulate the next recorded tick and check it against the checksum recorded with it, if
+    /// any. Returns the sounds the tick made. A keyframe is kept every so often for seeking.
+    fn replay_tick(&mut self, replay: &Replay, keyframes: &mut Vec<Keyframe>) -> Vec<Rc<Music>> {
+        if self.tick % KEYFRAME_INTERVAL_TICKS == 0 && 
+            keyframes.len() as u64 == self.tick / KEYFRAME_INTERVAL_TICKS {
+            keyframes.push(self.keyframe());
+        }
+        let tick = self.tick;
+        let record = replay.ticks[tick as usize];
+        let mut ctx = GameLoopContext::new(record.input.dt_ms as f32 / 1000.);
+        self.simulate_tick(&mut ctx, &record.input);
+        if let Some(checksum) = record.checksum {
+            self.compare_checksum(tick, checksum, None);
+        }
+        return ctx.audible_queue;
+    }
+
+    /// Move the replay to the given tick. Going back restarts from the closest keyframe before
+    /// it. Going forward simulates the ticks in between, keeping keyframes along the way.
+    fn seek_replay(&mut self, replay: &Replay, keyframes: &mut Vec<Keyframe>, tick: u64) {
+        let index = (tick / KEYFRAME_INTERVAL_TICKS) as usize;
+        let keyframe_ahead = index < keyframes.len() && 
+            keyframes[index].state.snapshot.tick > self.tick;
+        if tick < self.tick || keyframe_ahead {
+            let index = index.min(keyframes.len() - 1);
+            self.restore_keyframe(&keyframes[index]);
+        }
+        while self.tick < tick {
+            self.replay_tick(replay, keyframes);
+        }
+    }
+
+    fn keyframe(&self) -> Keyframe {
+        return Keyframe { state: self.sim_state(), rewind_history: self.rewind_history.clone() };
+    }
+
+    fn restore_keyframe(&mut self, keyframe: &Keyframe) {
+        self.restore_sim_state(&keyframe.state);
+        self.rewind_history = keyframe.rewind_history.clone();
+
+        // Checksums of the ticks after the keyframe are about to be recorded again.
+        self.desync_detector.clear();
+    }
+
+    fn sim_state(&self) -> SimState {
+        return SimState {
+            snapshot: self.snapshot(),
+            time_ball_last_speedup_ms: self.time_ball_last_speedup_ms,
+            time_slow_motion_started_ms: self.time_slow_motion_started_ms,
+            ball_rng: self.ball.borrow().launch_rng(),
+            stats: self.stats.clone()
+        };
+    }
+
+    fn restore_sim_state(&mut self, state: &SimState) {
+        self.apply_snapshot(&state.snapshot);
+        self.time_ball_last_speedup_ms = state.time_ball_last_speedup_ms;
+        self.time_slow_motion_started_ms = state.time_slow_motion_started_ms;
+        self.ball.borrow_mut().restore_launch_rng(state.ball_rng.clone());
+        self.stats = state.stats.clone();
+    }
+
+    /// Go back to the oldest state in the rewind history, so the player can have another go at
+    /// the last few seconds. The ball, paddles, scores and match statistics go back. The tick
+    /// count and powers already spent do not.
+    fn rewind(&mut self) {
+        if self.rewinds_remaining == 0 {
+            return;
+        }
+        let oldest = match self.rewind_history.front() {
+            Some(state) => state.clone(),
+            None => return
+        };
+        self.rewind_frames = self.rewind_history.iter().rev().map(|state| {
+            InstantReplayFrame {
+                time_ms: state.snapshot.clock_ms,
+                ball_x: state.snapshot.ball_x,
+                ball_y: state.snapshot.ball_y,
+                lpaddle_y: state.snapshot.lpaddle_y,
+                rpaddle_y: state.snapshot.rpaddle_y
+            }
+        }).collect();
+
+        let tick = self.tick;
+        let slow_motions_remaining = self.slow_motions_remaining;
+        let slow_motions_used = self.stats.slow_motions_used;
+        self.restore_sim_state(&oldest);
+        self.tick = tick;
+        self.slow_motions_remaining = slow_motions_remaining;
+        self.stats.slow_motions_used = slow_motions_used;
+        self.rewinds_remaining -= 1;
+        self.rewind_history.clear();
+    }
+
+    /// Capture the simulation state of this tick.
+    fn snapshot(&self) -> Snapshot {
+        let ball = self.ball.borrow();
+        let rpaddle = self.rpaddle.borrow();
+        return Snapshot {
+            tick: self.tick,
+            ball_x: ball.x,
+            ball_y: ball.y,
+            ball_vx: ball.vx,
+            ball_vy: ball.vy,
+            ball_speed_multiplier: ball.speed_multiplier,
+            lpaddle_y: self.lpaddle.borrow().y,
+            rpaddle_y: rpaddle.y,
+            rpaddle_speed_multiplier: rpaddle.speed_multiplier,
+            score: self.score,
+            clock_ms: self.sim_time_ms,
+            slow_motions_remaining: self.slow_motions_remaining,
+            slow_motion_active: self.time_slow_motion_started_ms.is_some(),
+            rewinds_remaining: self.rewinds_remaining
+        };
+    }
+
+    /// Put the simulation into the state captured by a snapshot.
+    fn apply_snapshot(&mut self, snapshot: &Snapshot) {
+        {
+            let mut ball = self.ball.borrow_mut();
+            ball.x = snapshot.ball_x;
+            ball.y = snapshot.ball_y;
+            ball.vx = snapshot.ball_vx;
+            ball.vy = snapshot.ball_vy;
+            ball.speed_multiplier = snapshot.ball_speed_multiplier;
+        }
+        {
+            let mut rpaddle = self.rpaddle.borrow_mut();
+            rpaddle.y = snapshot.rpaddle_y;
+            rpaddle.speed_multiplier = snapshot.rpaddle_speed_multiplier;
+        }
+        self.lpaddle.borrow_mut().y = snapshot.lpaddle_y;
+        self.score = snapshot.score;
+        self.sim_time_ms = snapshot.clock_ms;
+        self.slow_motions_remaining = snapshot.slow_motions_remaining;
+        self.rewinds_remaining = snapshot.rewinds_remaining;
+        if !snapshot.slow_motion_active {
+            self.time_slow_motion_started_ms = None;
+        } else if self.time_slow_motion_started_ms.is_none() {
+            self.time_slow_motion_started_ms = Some(self.sim_time_ms);
+        }
+        self.tick = snapshot.tick;
+    }
+
+    /// Send the state of this tick to a LAN guest and spectators, at a steady rate however fast
+    /// frames come. A tick that scored a point or ended the match always goes out, so that
+    /// nobody misses it.
+    fn send_snapshots(&mut self) {
+        let now_ms = clock_ticks::precise_time_ms();
+        if now_ms - self.time_snapshot_last_sent_ms < SNAPSHOT_INTERVAL_MS &&
+           !self.point_scored && self.score.winner.is_none() {
+            return;
+        }
+        self.time_snapshot_last_sent_ms = now_ms;
+        self.send_snapshot_to_guest(now_ms);
+        self.send_snapshot_to_spectators();
+    }
+
+    /// Send the state of this tick to the guest, if we are hosting a LAN game. Once a second,
+    /// also tell the guest how many spectators are watching and the checksum of the tick just
+    /// simulated, so that the guest can check it is drawing the same match.
+    fn send_snapshot_to_guest(&mut self, now_ms: u64) {
+        let snapshot = self.snapshot();
+        if let Opponent::RemoteGuest(ref peer) = self.opponent {
+            peer.send(&Message::State(snapshot));
+            if now_ms - self.time_status_last_sent_ms >= 1000 {
+                peer.send(&Message::Spectators(self.spectator_count));
+                if let Some(latest) = self.desync_detector.latest() {
+                    peer.send(&Message::Checksum { tick: latest.tick, checksum: latest.checksum });
+                }
+                self.time_status_last_sent_ms = now_ms;
+            }
+        }
+    }
+
+    /// Send the state of this tick to everyone watching, and take in new spectators.
+    fn send_snapshot_to_spectators(&mut self) {
+        let snapshot = self.snapshot();
+        if let Some(ref mut spectators) = self.spectators {
+            spectators.update();
+            spectators.send_snapshot(&snapshot);
+            self.spectator_count = spectators.count();
+        }
+    }
+
+    /// Take in the paddle movements of a LAN guest. The guest controls the right paddle. Returns
+    /// the latest position the guest moved it to, if any.
+    fn receive_from_guest(&mut self) -> Option<f32> {
+        let mut latest: Option<f32> = None;
+        let mut lost_msg: Option<String> = None;
+        if let Opponent::RemoteGuest(ref mut peer) = self.opponent {
+            while let Some(message) = peer.recv() {
+                match message {
+                    Message::Input { paddle_y, .. } => {
+                        latest = Some(paddle_y);
+                    },
+                    Message::Leave => {
+                        lost_msg = Some(format!("{} left!", peer.name));
+                    },
+                    _ => {}
+                }
+            }
+            if lost_msg.is_none() && peer.timed_out() {
+                lost_msg = Some("Connection lost!".to_string());
+            }
+        }
+        if let Some(msg) = lost_msg {
+            self.running = false;
+            self.show_message(&msg);
+        }
+        return latest;
+    }
+
+    /// One iteration of the game loop when the host runs the simulation. We send our paddle
+    /// position, then draw the latest state received from the host. Our paddle is drawn where
+    /// we put it rather than where the host last saw it, so that it responds without lag.
+    fn follow_host(&mut self, ctx: &mut GameLoopContext) {
+        let mut paddle_y = self.rpaddle.borrow().y;
+        let controls = self.profiles.selected().controls;
+        while let Some(event) = self.ui.poll_event() {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    self.running = false;
+                    return;
+                },
+                Event::MouseMotion{y, ..} if controls == Controls::Mouse => {
+                    let rpaddle = self.rpaddle.borrow();
+                    paddle_y = (y as f32).max(0.).min(self.height - rpaddle.height);
                 },
-                None => {}
+                Event::KeyDown { keycode: Some(key), .. } if is_debug_display_key(key) => {
+                    self.toggle_debug_display(key);
+                },
+                _ => {}
+            }
+        }
+        let direction = self.held_direction(controls);
+        if direction != 0. {
+            let rpaddle_height = self.rpaddle.borrow().height;
+            paddle_y += direction * KEYBOARD_PADDLE_SPEED * ctx.dt_sec;
+            paddle_y = paddle_y.max(0.).min(self.height - rpaddle_height);
+        }
+
+        let mut latest: Option<Snapshot> = None;
+        let mut checksums: Vec<(u64, u64)> = Vec::new();
+        let mut lost_msg: Option<String> = None;
+        if let Opponent::RemoteHost(ref mut peer) = self.opponent {
+            peer.send(&Message::Input { tick: self.tick, paddle_y: paddle_y });
+            while let Some(message) = peer.recv() {
+                match message {
+                    Message::State(snapshot) => {
+                        // Datagrams may arrive out of order. Only move forward in time.
+                        if latest.map_or(snapshot.tick >= self.tick, |l| snapshot.tick > l.tick) {
+                            latest = Some(snapshot);
+                        }
+                    },
+                    Message::Spectators(count) => {
+                        self.spectator_count = count;
+                    },
+                    Message::Checksum { tick, checksum } => {
+                        checksums.push((tick, checksum));
+                    },
+                    Message::Leave => {
+                        lost_msg = Some(format!("{} left!", peer.name));
+                    },
+                    _ => {}
+                }
+            }
+            if lost_msg.is_none() && peer.timed_out() {
+                lost_msg = Some("Connection lost!".to_string());
             }
         }
-        sdl2_mixer::Music::halt();
-        return start_game.unwrap();
-    }
 
-    /// Draw text to the screen. The width and height are calculated from the font supplied.
-    /// The position is specified as a top y location only. The x location is calculated
-    /// so that the text centers on the screen.
-    fn draw_centered_text(&mut self, font: &Font, text: &str, color: Color, y: f32) {
-        let (width, _) = font.size(text).unwrap(); 
-        let x = self.width / 2. - (width as f32) / 2.;
-        self.draw_text(font, text, color, x, y);
+        // A snapshot holds the state at the end of the tick before the one it starts, which is
+        // the tick the host checksummed it under. Our own paddle is only put back afterwards.
+        if let Some(snapshot) = latest {
+            self.apply_received_snapshot(ctx, &snapshot);
+            let checksum = self.state_checksum(snapshot.tick.saturating_sub(1));
+            self.desync_detector.record(checksum);
+            self.report_desync();
+        }
+        for (tick, checksum) in checksums {
+            self.compare_checksum(tick, checksum, None);
+        }
+        self.rpaddle.borrow_mut().y = paddle_y;
+
+        ctx.layered_draw_queue[1].push(self.ball.clone());
+        ctx.layered_draw_queue[1].push(self.lpaddle.clone());
+        ctx.layered_draw_queue[1].push(self.rpaddle.clone());
+        self.draw(ctx);
+        self.play_audio(ctx);
+
+        if let Some(msg) = lost_msg {
+            self.running = false;
+            self.show_message(&msg);
+            return;
+        }
+        self.check_for_win();
     }
 
+    /// One iteration of the game loop while watching someone else's match. Snapshots from the
+    /// host pass through a small delay buffer, so that the match plays back smoothly even when
+    /// they arrive unevenly.
+    fn follow_as_spectator(&mut self, ctx: &mut GameLoopContext) {
+        while let Some(event) = self.ui.poll_event() {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    self.running = false;
+                    return;
+                },
+                Event::KeyDown { keycode: Some(key), .. } if is_debug_display_key(key) => {
+                    self.toggle_debug_display(key);
+                },
+                _ => {}
+            }
+        }
 
-    /// Draw text to the screen. The width and height are calculated from the font supplied.
-    fn draw_text(&mut self, font: &Font, text: &str, color: Color, x: f32, y: f32) {
-        let surface = font.render(text, sdl2_ttf::blended(color)).unwrap();
-        let texture = self.ui.renderer.create_texture_from_surface(&surface).unwrap();
-        let (width, height) = font.size(text).unwrap(); 
-        let target = Rect::new_unwrap(x as i32, y as i32, width as u32, height as u32);
-        self.ui.renderer.copy(&texture, None, Some(target));
-    }
+        let mut ended_msg: Option<String> = None;
+        if let Opponent::Spectating(ref mut peer) = self.opponent {
 
-    /// Entry point into the game. Handles transition between showing the welcome screen, running
-    /// the game and returning to the welcome screen.
-    pub fn launch_then_block_until_exit(&mut self) {
-        loop {
-            
-            // The game will exit when the user exits the welcome screen.
-            if !self.show_welcome_screen() {
-               return; 
+            // Keep asking every second, so the host knows we are still watching.
+            let now_ms = clock_ticks::precise_time_ms();
+            if now_ms - self.time_watch_last_sent_ms >= 1000 {
+                peer.send(&Message::Watch);
+                self.time_watch_last_sent_ms = now_ms;
             }
-            
-            // Execute the game loop over and over again until the user quits or someone wins.
-            self.execute_game_loop();
-            
-            // Transition back to the welcome screen, but first revert the game to its initial 
-            // state.
-            self.reset();
+            while let Some(message) = peer.recv() {
+                match message {
+                    Message::State(snapshot) => self.spectator_buffer.push(snapshot),
+                    Message::Spectators(count) => self.spectator_count = count,
+                    Message::Leave => ended_msg = Some("The match is over!".to_string()),
+                    _ => {}
+                }
+            }
+            if ended_msg.is_none() && peer.timed_out() {
+                ended_msg = Some("Connection lost!".to_string());
+            }
+        }
 
+        match self.spectator_buffer.next() {
+            Some(snapshot) => self.apply_received_snapshot(ctx, &snapshot),
+            None => self.tick += 1
         }
-    }
 
-    /// Execute the game loop over and over again until the user quits or someone wins. 
-    fn execute_game_loop(&mut self) {
+        ctx.layered_draw_queue[1].push(self.ball.clone());
+        ctx.layered_draw_queue[1].push(self.lpaddle.clone());
+        ctx.layered_draw_queue[1].push(self.rpaddle.clone());
+        self.draw(ctx);
+        self.play_audio(ctx);
 
-        // The running flag is stored as a game wide field. This allows the flag to be changed
-        // at any point in the game. A method checking for user input can thus set the flag to
-        // false when the user indicates they want to quit. The same goes for a method that checks
-        // whether someone has won.
-        self.running = true;
-        let mut time_last_invocation = clock_ticks::precise_time_ms();
+        if let Some(msg) = ended_msg {
+            self.running = false;
+            self.show_message(&msg);
+            return;
+        }
+        self.check_for_win();
+    }
 
-        while self.running {
-            let time_this_invocation = clock_ticks::precise_time_ms();
+    /// Draw the state of a snapshot received from the host, sounding the bounces the host
+    /// would have heard.
+    fn apply_received_snapshot(&mut self, ctx: &mut GameLoopContext, snapshot: &Snapshot) {
+        let (old_vx, old_vy) = {
+            let ball = self.ball.borrow();
+            (ball.vx, ball.vy)
+        };
+        if old_vx * snapshot.ball_vx < 0. {
+            ctx.audible_queue.push(self.ui.pong_sound.clone());
+        } else if old_vy * snapshot.ball_vy < 0. {
+            ctx.audible_queue.push(self.ui.ping_sound.clone());
+        }
+        self.apply_snapshot(snapshot);
+    }
 
-            // The delta time in millis is used to measure the duration of frame execution
-            // so that we can update the screen based on the time that has elapsed since the last
-            // frame was rendered. It is also used to cap the frame rate.
-            let dt_ms = time_this_invocation - time_last_invocation;
-            let mut ctx = GameLoopContext::new(dt_ms as f32 / 1000.);
-            self.execute_game_loop_iteration_per_frame(&mut ctx); 
-            self.cap_frames_per_second(dt_ms);
-            time_last_invocation = time_this_invocation;
-        } 
+    /// Tell a LAN opponent and anyone watching that we are leaving, then fall back to playing
+    /// the computer. 
+    fn leave_match(&mut self) {
+        match self.opponent {
+            Opponent::RemoteGuest(ref peer) | Opponent::RemoteHost(ref peer) | 
+                Opponent::Spectating(ref peer) => peer.leave(),
+            Opponent::Computer | Opponent::LocalPlayer | Opponent::Replayed(..) => {}
+        }
+        if let Some(ref spectators) = self.spectators {
+            spectators.close();
+        }
+        self.opponent = Opponent::Computer;
+        self.spectators = None;
+        self.spectator_count = 0;
+        self.spectator_buffer = SnapshotBuffer::new();
+    }
 
+    /// Checksum the full simulation state as the state at the end of the given tick: positions,
+    /// velocities, speed multipliers, the score, the match clock and slow motion state.
+    fn state_checksum(&self, tick: u64) -> TickChecksum<TickState> {
+        let state = TickState {
+            tick: tick,
+            ball: self.ball.borrow().clone(),
+            lpaddle: self.lpaddle.borrow().clone(),
+            rpaddle: self.rpaddle.borrow().clone(),
+            score: self.score,
+            clock_ms: self.sim_time_ms,
+            slow_motions_remaining: self.slow_motions_remaining,
+            slow_motion_active: self.time_slow_motion_started_ms.is_some(),
+            rewinds_remaining: self.rewinds_remaining
+        };
+        return TickChecksum::new(tick, state);
     }
-    
-    /// Called once per frame. Essentially, an iteration of the game loop. 
-    fn execute_game_loop_iteration_per_frame(&mut self, ctx: &mut GameLoopContext) {
-        
-        // Move objects. The left paddle is moved based on user input. 
-        self.move_ball(ctx);
-        self.move_left_paddle(ctx);
-        self.move_right_paddle(ctx);
-        
-        // Draw objects.
-        self.draw(ctx);
 
-        // Play audio.
-        self.play_audio(ctx);
+    /// Checksum the state at the end of this tick and advance the tick counter.
+    fn record_checksum(&mut self) {
+        let checksum = self.state_checksum(self.tick);
+        self.desync_detector.record(checksum);
+        self.report_desync();
+        self.tick += 1;
+    }
 
-        // End slow motion mode if duration has elapsed.
-        if let Some(time_slow_motion_started_ms) = self.time_slow_motion_started_ms {
-            if clock_ticks::precise_time_ms() - time_slow_motion_started_ms >= 5000 {
-                self.time_slow_motion_started_ms = None;
-            }
-        }
+    /// Compare a checksum received from a peer or read from a replay against the local
+    /// simulation. Returns true once the two have diverged.
+    pub fn compare_checksum(&mut self, tick: u64, checksum: u64, dump: Option<String>) -> bool {
+        let desynced = self.desync_detector.compare(tick, checksum, dump);
+        self.report_desync();
+        return desynced;
+    }
 
-        // Check to see if either the human (left paddle) or computer (right paddle) has won.
-        self.check_for_win();
+    /// Write the first diverging tick and a dump of both states to stderr. Only done once per
+    /// match, since every tick after the first divergence will differ as well.
+    fn report_desync(&mut self) {
+        if self.desync_reported {
+            return;
+        }
+        if let Some(desync) = self.desync_detector.desync() {
+            let _ = write!(&mut io::stderr(), "{}", desync);
+            self.desync_reported = true;
+        }
     }
     
     /// Move the left paddle based on user input. 
-    fn move_left_paddle(&mut self, ctx: &mut GameLoopContext) {
-        match self.ui.poll_event() {
-            Some(event) => {
-                match event {
-                    // Quit the game and return back to the welcome screen.
-                    Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                        self.running = false;
-                    },
-                    // Enter slow motion mode.
-                    Event::MouseButtonDown{..} => {
-                        if self.slow_motions_remaining > 0 && 
-                            self.time_slow_motion_started_ms.is_none() {
-                            self.slow_motions_remaining -= 1;
-                            self.time_slow_motion_started_ms = Some(clock_ticks::precise_time_ms());
-                        }
-                    },
-                    // Move left paddle with mouse. 
-                    Event::MouseMotion{x,y, ..} => {
-                        let y = y as f32;
-                        let mut lpaddle = self.lpaddle.borrow_mut();
-                        lpaddle.y = y; 
-                        // Guard against moving up or down beyond the screen bounds.
-                        if lpaddle.y < 0. { 
-                            lpaddle.y = 0.; 
-                        } else if lpaddle.y + lpaddle.height > self.height {
-                            lpaddle.y = self.height - lpaddle.height; 
-                        }
-                    }
-                    _ => {}
-                }
-            },
-            None => {}
+    fn move_left_paddle(&mut self, ctx: &mut GameLoopContext, input: &TickInput) {
+
+        // Enter slow motion mode.
+        if input.slow_motion && self.slow_motions_remaining > 0 && 
+            self.time_slow_motion_started_ms.is_none() {
+            self.slow_motions_remaining -= 1;
+            self.time_slow_motion_started_ms = Some(self.sim_time_ms);
+            self.stats.slow_motion_used();
+        }
+
+        // Move left paddle with mouse. 
+        if let Some(y) = input.lpaddle_y {
+            let mut lpaddle = self.lpaddle.borrow_mut();
+            lpaddle.y = y; 
+            // Guard against moving up or down beyond the screen bounds.
+            if lpaddle.y < 0. { 
+                lpaddle.y = 0.; 
+            } else if lpaddle.y + lpaddle.height > self.height {
+                lpaddle.y = self.height - lpaddle.height; 
+            }
         }
         ctx.layered_draw_queue[1].push(self.lpaddle.clone());
     }
 
-    /// The computer player moves the right paddle. 
-    fn move_right_paddle(&mut self, ctx: &mut GameLoopContext) {
-        let ball = self.ball.borrow();
-        let mut rpaddle = self.rpaddle.borrow_mut(); 
+    /// The computer player moves the right paddle, unless a LAN guest or a second player here
+    /// is controlling it. 
+    fn move_right_paddle(&mut self, ctx: &mut GameLoopContext, input: &TickInput) {
+        let guest_controlled = match self.opponent {
+            Opponent::RemoteGuest(..) | Opponent::LocalPlayer => true,
+            Opponent::Replayed(ref header) => header.right_is_guest,
+            _ => false
+        };
+        if guest_controlled {
+            if let Some(y) = input.rpaddle_y {
+                let mut rpaddle = self.rpaddle.borrow_mut();
+                rpaddle.y = y.max(0.).min(self.height - rpaddle.height);
+            }
+            ctx.layered_draw_queue[1].push(self.rpaddle.clone());
+            return;
+        }
 
-        // If ball is moving toward the paddle, then track the ball. If the ball is moving away
-        // from the paddle, then move toward the home position.
-        let tracking_y = if ball.vx > 0. {ball.y + ball.diameter / 2.} else {self.height / 2.}; 
+        let tracking_y = self.ai_tracking_y();
+        let mut rpaddle = self.rpaddle.borrow_mut(); 
 
         // We use non-overlapping segments of the paddle (3/4 vs 1/4) when deciding whether to move
         // the paddle up or down. Using the center of the paddle against the center of the ball is
@@ -350,6 +1649,23 @@ impl Game {
         ctx.layered_draw_queue[1].push(self.rpaddle.clone());
     }
         
+    /// Show or hide the frame rate and frame times with F2, or the debug overlay with F3.
+    fn toggle_debug_display(&mut self, key: Keycode) {
+        match key {
+            Keycode::F2 => self.profiler.visible = !self.profiler.visible,
+            Keycode::F3 => self.debug_overlay = !self.debug_overlay,
+            _ => {}
+        }
+    }
+
+    /// The height the computer moves its paddle towards. If ball is moving toward the paddle,
+    /// then track the ball. If the ball is moving away from the paddle, then move toward the
+    /// home position.
+    fn ai_tracking_y(&self) -> f32 {
+        let ball = self.ball.borrow();
+        return if ball.vx > 0. {ball.y + ball.diameter / 2.} else {self.height / 2.};
+    }
+
     /// Move the ball and deal with collisions. 
     fn move_ball(&mut self, ctx: &mut GameLoopContext) {
         let mut ball = self.ball.borrow_mut(); 
@@ -370,10 +1686,12 @@ impl Game {
             new_ball_y = -new_ball_y;
             ball.vy = -ball.vy;
             ctx.audible_queue.push(self.ui.ping_sound.clone());
+            self.stats.wall_bounce();
         } else if new_ball_y + ball.diameter >= self.height { 
             new_ball_y = self.height - (new_ball_y + ball.diameter - self.height) - ball.diameter;
             ball.vy = -ball.vy;
             ctx.audible_queue.push(self.ui.ping_sound.clone());
+            self.stats.wall_bounce();
         } 
 
         let mut bounce_that_allows_speedup: bool = false;
@@ -387,14 +1705,16 @@ impl Game {
             // The gradient of the straight line from (ball.x,ball.y) to (bounce_x,bounce_y) to
             // (new_ball_x,new_ball_y) stays constant, so we can use that to find the value of the
             // top left corner of the ball when it bounces.  
-            let bounce_y = (new_ball_y - ball.y) / (new_ball_x - ball.x) * (bounce_x - ball.x) + ball.y;
+            let bounce_y =
+                (new_ball_y - ball.y) / (new_ball_x - ball.x) * (bounce_x - ball.x) + ball.y;
 
             if bounce_y + ball.diameter >= lpaddle.y && bounce_y <= lpaddle.y + lpaddle.height {
                
                 // The y position indicates a hit also! 
                 
                 // Calculate where the center of the ball hit relative to the center of the paddle.
-                let relative_y = (lpaddle.y + lpaddle.height / 2.) - (bounce_y + ball.diameter / 2.);
+                let relative_y =
+                    (lpaddle.y + lpaddle.height / 2.) - (bounce_y + ball.diameter / 2.);
                 
                 // Use the ratio of the bounce position to half the height of the paddle as an
                 // angle multiplier.
@@ -420,6 +1740,7 @@ impl Game {
                 new_ball_x = bounce_x + ball.vx * bounce_dt_sec;
                 new_ball_y = bounce_y + ball.vy * bounce_dt_sec;
                 ctx.audible_queue.push(self.ui.pong_sound.clone()); 
+                self.stats.paddle_hit(Side::Left);
 
                 // May speedup after hitting the left paddle.
                 bounce_that_allows_speedup = true;
@@ -430,10 +1751,12 @@ impl Game {
             // hitting the left paddle.
 
             let bounce_x = rpaddle.x - ball.diameter; 
-            let bounce_y = (new_ball_y - ball.y) / (new_ball_x - ball.x) * (bounce_x - ball.x) + ball.y;
+            let bounce_y =
+                (new_ball_y - ball.y) / (new_ball_x - ball.x) * (bounce_x - ball.x) + ball.y;
 
             if bounce_y + ball.diameter  >= rpaddle.y && bounce_y <= rpaddle.y + rpaddle.height {
-                let relative_y = (rpaddle.y + rpaddle.height / 2.) - (bounce_y + ball.diameter / 2.);
+                let relative_y =
+                    (rpaddle.y + rpaddle.height / 2.) - (bounce_y + ball.diameter / 2.);
                 let bounce_angle_multiplier = (relative_y / (rpaddle.height / 2.)).abs();
                 let bounce_angle = bounce_angle_multiplier * ball.max_bounce_angle;
                 ball.vx = ball.speed * bounce_angle.cos() * -1.;
@@ -442,6 +1765,7 @@ impl Game {
                 new_ball_x = bounce_x + ball.vx * bounce_dt_sec;
                 new_ball_y = bounce_y + ball.vy * bounce_dt_sec;
                 ctx.audible_queue.push(self.ui.pong_sound.clone()); 
+                self.stats.paddle_hit(Side::Right);
 
                 // May speedup after hitting the right paddle.
                 bounce_that_allows_speedup = true;
@@ -456,14 +1780,18 @@ impl Game {
             new_ball_x = -new_ball_x;
             ball.vx = -ball.vx;
             // Right player scored.
-            self.rscore_card.borrow_mut().score += 1;
+            self.score.point(&self.rules, Side::Right);
+            self.point_scored = true;
+            self.stats.point(Side::Right);
             ctx.audible_queue.push(self.ui.ping_sound.clone()); 
             bounce_that_allows_speedup = true;
         } else if new_ball_x + ball.diameter > self.width { 
             new_ball_x = self.width - (new_ball_x + ball.diameter - self.width) - ball.diameter;
             ball.vx = -ball.vx;
             // Left player scored.
-            self.lscore_card.borrow_mut().score += 1;
+            self.score.point(&self.rules, Side::Left);
+            self.point_scored = true;
+            self.stats.point(Side::Left);
             ctx.audible_queue.push(self.ui.ping_sound.clone()); 
             bounce_that_allows_speedup = true;
         } 
@@ -472,8 +1800,22 @@ impl Game {
         ball.y = new_ball_y;
         ctx.layered_draw_queue[1].push(self.ball.clone());
 
+        // Keep track of the fastest the ball has travelled, slow motion included.
+        let speed = (ball.vx * ball.vx + ball.vy * ball.vy).sqrt();
+        let speed = self.mod_speed(speed, ball.speed_multiplier);
+        self.stats.ball_speed(speed);
+
+        // Keep the last few seconds of play around for the instant replay.
+        self.instant_replay_buffer.push(InstantReplayFrame {
+            time_ms: self.sim_time_ms,
+            ball_x: ball.x,
+            ball_y: ball.y,
+            lpaddle_y: lpaddle.y,
+            rpaddle_y: rpaddle.y
+        });
+
         // Speedup the ball periodically until max speed reached. 
-        let time_now_ms = clock_ticks::precise_time_ms();
+        let time_now_ms = self.sim_time_ms;
         match self.time_ball_last_speedup_ms {
             None => {   
                 self.time_ball_last_speedup_ms = Option::Some(time_now_ms);
@@ -491,14 +1833,30 @@ impl Game {
     }
 
     fn draw(&mut self, ctx: &mut GameLoopContext) {
+        self.profiler.begin_phase(Phase::Draw);
+        self.draw_scene(ctx);
+        self.ui.renderer.present();
+        self.profiler.end_phase();
+    }
+
+    /// Draw the court, the queued items and the HUD without presenting them, so that more can
+    /// be drawn on top.
+    fn draw_scene(&mut self, ctx: &mut GameLoopContext) {
         
         // Set background color and clear the screen.
-        self.ui.renderer.set_draw_color(self.background_color);
+        let background = self.ui.theme.background;
+        self.ui.renderer.set_draw_color(background);
         self.ui.renderer.clear();
         
         ctx.layered_draw_queue[0].push(self.net.clone());
-        ctx.layered_draw_queue[0].push(self.lscore_card.clone());
-        ctx.layered_draw_queue[0].push(self.rscore_card.clone());
+        {
+            let mut scoreboard = self.scoreboard.borrow_mut();
+            scoreboard.rules = self.rules;
+            scoreboard.score = self.score;
+            scoreboard.clock_ms = self.rules.time_limit_ms()
+                .map(|time_limit_ms| time_limit_ms.saturating_sub(self.sim_time_ms));
+        }
+        ctx.layered_draw_queue[0].push(self.scoreboard.clone());
 
         // Higher layers are drawn on top of lower layers. Allows us to for instance, ensure the 
         // ball passes over the top of the net instead of underneath it.
@@ -512,55 +1870,339 @@ impl Game {
             let png_path = Path::new("assets/images/turtle.png");
             self.ui.renderer.load_texture(png_path).unwrap() 
         };
-        let mut x = 300;
-        let y = 550;
+        // Turtles and rewinds sit side by side, centered below the court. With shape cues on,
+        // the ones used up are crossed out or left hollow, so they are told apart by more than
+        // their color.
         let w = 15;
+        let icons = Element::row(Align::Start, 25., vec![
+            Element::row(Align::Start, 5., (0..3).map(|_| Element::space(w as f32, 20.)).collect()),
+            Element::row(Align::Start, 5., (0..REWINDS_PER_MATCH)
+                .map(|_| Element::space(20., 20.)).collect())
+        ]);
+        let below_court = Bounds::new(0., 0., self.width, self.height - 30.);
+        let icons_bounds = icons.draw_in(&mut self.ui, below_court, Align::Center, Align::End);
+        let mut x = icons_bounds.x as i32;
+        let y = icons_bounds.y as i32;
+        let colors = self.settings.palette.colors(&self.ui.theme);
+        let shape_cues = self.settings.shape_cues;
+        let cross_color = self.ui.theme.text;
         for i in 0..3 {
-            if i < self.slow_motions_remaining {
-                match Color::RGB(0x6f, 0xc3, 0x2d) {
-                    Color::RGB(r,g,b) => png_texture.set_color_mod(r,g,b),
-                    _ => {}
-                }
-            } else {
-                png_texture.set_color_mod(0x69, 0x69, 0x69);
-            }
+            let remaining = i < self.slow_motions_remaining;
+            let (r, g, b) = if remaining {colors.available.rgb()} else {colors.used.rgb()};
+            png_texture.set_color_mod(r, g, b);
             let target = Rect::new_unwrap(x, y, w, 20);
             self.ui.renderer.copy(&png_texture, None, Some(target));
+            if shape_cues && !remaining {
+                let (left, right, top, bottom) = (x as i16, (x + w as i32) as i16, y as i16,
+                                                  y as i16 + 20);
+                let _ = self.ui.renderer.line(left, top, right, bottom, cross_color);
+                let _ = self.ui.renderer.line(left, bottom, right, top, cross_color);
+            }
             x += w as i32 + 5;
         }
-        self.ui.renderer.present();
+
+        // Rewinds are shown as rewind symbols next to the turtles.
+        x += 20;
+        for i in 0..REWINDS_PER_MATCH {
+            let remaining = i < self.rewinds_remaining;
+            let color = if remaining {colors.available} else {colors.used};
+            let (left, mid, right, top, bottom) = (x as i16, x as i16 + 10, x as i16 + 20, 
+                                                   y as i16, y as i16 + 20);
+            let renderer = &self.ui.renderer;
+            if shape_cues && !remaining {
+                let _ = renderer.trigon(left, top + 10, mid, top, mid, bottom, color);
+                let _ = renderer.trigon(mid, top + 10, right, top, right, bottom, color);
+            } else {
+                let _ = renderer.filled_trigon(left, top + 10, mid, top, mid, bottom, color);
+                let _ = renderer.filled_trigon(mid, top + 10, right, top, right, bottom, color);
+            }
+            x += 25;
+        }
+
+        if self.debug_overlay {
+            self.draw_debug_overlay(ctx);
+        }
+
+        if self.profiler.visible {
+            self.profiler.draw(&mut self.ui, &self.hud_font, self.pacer.pacing.name());
+        }
+
+        // Let the players know when others are watching.
+        if self.spectator_count > 0 {
+            let watching = format!("{} watching", self.spectator_count);
+            self.draw_hud_text(&watching, Align::End);
+        }
+    }
+
+    /// Draw a line of text over the bottom of the court, at the side `horizontal` puts it.
+    fn draw_hud_text(&mut self, text: &str, horizontal: Align) {
+        let text_color = self.ui.theme.text;
+        let label = Element::label(text, &self.hud_font, text_color);
+        let area = Bounds::new(HUD_MARGIN, 0., self.width - HUD_MARGIN * 2.,
+                               self.height - HUD_MARGIN);
+        label.draw_in(&mut self.ui, area, horizontal, Align::End);
+    }
+
+    /// Draw hitboxes, the ball's velocity, how the computer tracks the ball and the values
+    /// that drive them over the court.
+    fn draw_debug_overlay(&mut self, ctx: &GameLoopContext) {
+        let info = {
+            let ball = self.ball.borrow();
+            let rpaddle = self.rpaddle.borrow();
+            let computer_plays_right = match self.opponent {
+                Opponent::Computer => true,
+                Opponent::Replayed(ref header) => !header.right_is_guest,
+                _ => false
+            };
+            let tracking_y = if computer_plays_right {Some(self.ai_tracking_y())} else {None};
+            DebugInfo {
+                ball_vx: self.mod_speed(ball.vx, ball.speed_multiplier),
+                ball_vy: self.mod_speed(ball.vy, ball.speed_multiplier),
+                tracking_y: tracking_y,
+                values: vec![
+                    format!("tick {}  dt_sec {:.4}", self.tick, ctx.dt_sec),
+                    format!("ai target y {}",
+                            tracking_y.map_or("-".to_string(), |y| format!("{:.1}", y))),
+                    format!("ball x {:.1} y {:.1} vx {:.1} vy {:.1}", 
+                            ball.x, ball.y, ball.vx, ball.vy),
+                    format!("ball.speed {}  ball.speed_multiplier {:.2}", 
+                            ball.speed, ball.speed_multiplier),
+                    format!("rpaddle.speed {}  rpaddle.speed_multiplier {:.2}", 
+                            rpaddle.speed, rpaddle.speed_multiplier),
+                    format!("slow motion {}", 
+                            if self.time_slow_motion_started_ms.is_some() {"on"} else {"off"})
+                ]
+            }
+        };
+        let ball = self.ball.borrow();
+        let lpaddle = self.lpaddle.borrow();
+        let rpaddle = self.rpaddle.borrow();
+        debug_overlay::draw(&mut self.ui, &self.hud_font, &ball, &lpaddle, &rpaddle, &info);
     }
 
     fn play_audio(&mut self, ctx: &mut GameLoopContext) {
+        self.profiler.begin_phase(Phase::Audio);
         for a in ctx.audible_queue.iter() {
             let _ = a.play(1);
         }
+        self.profiler.end_phase();
     }
 
     fn check_for_win(&mut self) {
-        let mut msg: Option<&str> = Option::None;
-        let points_to_win = 5;
+        let mut msg: Option<String> = Option::None;
+
+        // The local player is on the left, unless we joined someone else's game.
+        let (you, them) = match self.opponent {
+            Opponent::Computer => ("You win!".to_string(), "I win!".to_string()),
+            Opponent::LocalPlayer => 
+                (format!("{} wins!", self.player_name), format!("{} wins!", SECOND_PLAYER_NAME)),
+            Opponent::RemoteGuest(ref peer) => 
+                ("You win!".to_string(), format!("{} wins!", peer.name)),
+            Opponent::RemoteHost(ref peer) => 
+                (format!("{} wins!", peer.name), "You win!".to_string()),
+            Opponent::Spectating(ref peer) => 
+                (format!("{} wins!", peer.name), "The challenger wins!".to_string()),
+            Opponent::Replayed(ref header) => 
+                (format!("{} wins!", header.left_name), format!("{} wins!", header.right_name))
+        };
 
-        if self.lscore_card.borrow().score >= points_to_win {
-            msg = Option::Some("You win!");
-        } else if self.rscore_card.borrow().score >= points_to_win {
-            msg = Option::Some("I win!");
+        match self.score.winner {
+            Some(Side::Left) => msg = Option::Some(you),
+            Some(Side::Right) => msg = Option::Some(them),
+            None => {}
         }
         
-        // There is a win message, therefore there is a winner. 
+        // There is a win message, therefore there is a winner. Statistics are only known for
+        // matches simulated here, so a match followed from another machine just announces
+        // the winner.
         if let Some(msg) = msg {
             self.running = false;
-            self.ui.renderer.set_draw_color(self.background_color);
-            self.ui.renderer.clear();
-            let font_path = Path::new("assets/fonts/kghappysolid.ttf");
-            let font = sdl2_ttf::Font::from_file(font_path, 60).unwrap();
-            let (_, height) = font.size(msg).unwrap();
-            let y = self.height / 2. - (height as f32) / 2.;
-            self.draw_centered_text(&font, msg, Color::RGB(0xfc, 0xef, 0x6d), y);
+            let right_name = match self.opponent {
+                Opponent::Computer => Some("Computer".to_string()),
+                Opponent::LocalPlayer => Some(SECOND_PLAYER_NAME.to_string()),
+                Opponent::RemoteGuest(ref peer) => Some(peer.name.clone()),
+                _ => None
+            };
+            // Two players sharing this machine are not the selected profile playing, so their
+            // match is kept out of its history and statistics.
+            let counts = match self.opponent {
+                Opponent::Computer | Opponent::RemoteGuest(..) => !self.console_changed,
+                _ => false
+            };
+            match right_name {
+                Some(right_name) => {
+                    if counts {
+                        self.save_to_history(&right_name);
+                        self.save_to_profile();
+                        if let Opponent::Computer = self.opponent {
+                            self.enter_records();
+                        }
+                    }
+                    let can_rematch = match self.opponent {
+                        Opponent::Computer => true,
+                        _ => false
+                    };
+                    let names = [&self.player_name[..], &right_name[..]];
+                    self.rematch = summary::show(&mut self.ui, &msg, &self.stats, names,
+                                                 can_rematch);
+                },
+                None => self.show_message(&msg)
+            }
+        }
+
+    }
+
+    /// Add the match that just finished to the history file.
+    fn save_to_history(&self, opponent_name: &str) {
+        let ai_level = match self.opponent {
+            Opponent::Computer => Some(self.ai_level),
+            _ => None
+        };
+        let record = MatchRecord::new(&self.player_name, 
+                                      opponent_name, 
+                                      ai_level,
+                                      self.rules.points_per_game as i32, 
+                                      &self.score,
+                                      &self.stats);
+        if let Err(e) = history::append(&history::history_path(), &record) {
+            let _ = writeln!(&mut io::stderr(), "Unable to save match history: {}", e);
+        }
+    }
+
+    /// Add the match that just finished to the lifetime statistics of the player's profile.
+    fn save_to_profile(&mut self) {
+        let ai_level = match self.opponent {
+            Opponent::Computer => Some(self.ai_level),
+            _ => None
+        };
+        let won = self.score.winner == Some(Side::Left);
+        let longest_rally = self.stats.longest_rally;
+        self.profiles.selected_mut().record_match(ai_level, won, longest_rally);
+        self.profiles.save_or_report();
+    }
+
+    /// Put the match on every leaderboard it set a record on, under the name the player enters.
+    fn enter_records(&mut self) {
+        for board in BOARDS.iter() {
+            if self.ui.quit_requested {
+                return;
+            }
+            let score = board.score(&self.stats);
+            let rank = match self.leaderboards.rank(*board, score) {
+                Some(rank) => rank,
+                None => continue
+            };
+            let name = leaderboard_screen::enter_name(&mut self.ui, *board, score, rank,
+                                                      &self.player_name);
+            if let Some(name) = name {
+                let rank = self.leaderboards.insert(*board, &name, score);
+                self.leaderboards.save_or_report();
+                leaderboard_screen::show(&mut self.ui, &self.leaderboards,
+                                         rank.map(|rank| (*board, rank)));
+            }
+        }
+    }
+
+    /// Play the last few seconds before the point was scored at half speed, under a REPLAY
+    /// banner. Any key or mouse click skips the rest. Only matches played at this machine are
+    /// paused for it, since a LAN opponent would have to wait on us, and never with reduced
+    /// motion.
+    fn show_instant_replay(&mut self) {
+        match self.opponent {
+            Opponent::Computer | Opponent::LocalPlayer => {},
+            _ => return
+        }
+        if !self.instant_replays || self.settings.reduced_motion {
+            return;
+        }
+
+        // Each frame is shown twice as long as it took to play.
+        let frames = self.instant_replay_buffer.frames();
+        self.show_frames(&frames, "REPLAY", 0.5);
+        self.instant_replay_buffer.clear();
+    }
+
+    /// Show the play a rewind went back over, backwards and at three times the speed. With
+    /// reduced motion, play carries on from the earlier moment straight away.
+    fn show_rewind(&mut self) {
+        if !self.settings.reduced_motion {
+            let frames = self.rewind_frames.clone();
+            self.show_frames(&frames, "REWIND", 3.);
+        }
+        self.rewind_frames.clear();
+
+        // The play leading up to the point is gone, so it can no longer be replayed.
+        self.instant_replay_buffer.clear();
+    }
+
+    /// Draw the court as it was in each of the given frames, under a banner, at the given
+    /// multiple of the speed they were played at. Any key or mouse click skips the rest.
+    /// Afterwards, everything is put back where it was.
+    fn show_frames(&mut self, frames: &[InstantReplayFrame], banner: &str, speed: f32) {
+        let live = {
+            let ball = self.ball.borrow();
+            InstantReplayFrame {
+                time_ms: self.sim_time_ms,
+                ball_x: ball.x,
+                ball_y: ball.y,
+                lpaddle_y: self.lpaddle.borrow().y,
+                rpaddle_y: self.rpaddle.borrow().y
+            }
+        };
+        let banner_font = ScalableFont::new(&self.ui.theme.heading_font, 39);
+        let banner_style = TextStyle::plain(self.ui.theme.heading)
+            .outlined(self.ui.theme.shade, 2);
+        let (banner_width, _) = self.ui.text_size(&banner_font, banner);
+        let banner_x = self.width / 2. - banner_width / 2.;
+
+        'frames: for (i, frame) in frames.iter().enumerate() {
+            while let Some(event) = self.ui.poll_event() {
+                match event {
+                    Event::Quit {..} => {
+                        self.running = false;
+                        break 'frames;
+                    },
+                    Event::KeyDown {..} | Event::MouseButtonDown {..} => break 'frames,
+                    _ => {}
+                }
+            }
+            self.show_frame(frame);
+            let mut ctx = GameLoopContext::new(0.);
+            ctx.layered_draw_queue[1].push(self.ball.clone());
+            ctx.layered_draw_queue[1].push(self.lpaddle.clone());
+            ctx.layered_draw_queue[1].push(self.rpaddle.clone());
+            self.draw_scene(&mut ctx);
+            // Outlined to stand out against the court behind it.
+            self.ui.draw_styled_text(&banner_font, banner, &banner_style, banner_x, 80.);
             self.ui.renderer.present();
-            thread::sleep_ms(1500);
+            thread::sleep_ms((instant_replay::frame_duration_ms(frames, i) as f32 / speed) as u32);
         }
 
+        // Carry on from where play stopped.
+        self.show_frame(&live);
+    }
+
+    fn show_frame(&mut self, frame: &InstantReplayFrame) {
+        let mut ball = self.ball.borrow_mut();
+        ball.x = frame.ball_x;
+        ball.y = frame.ball_y;
+        self.lpaddle.borrow_mut().y = frame.lpaddle_y;
+        self.rpaddle.borrow_mut().y = frame.rpaddle_y;
+    }
+
+    /// Clear the screen and show a message in large letters for a moment.
+    fn show_message(&mut self, msg: &str) {
+        let background = self.ui.theme.background;
+        self.ui.renderer.set_draw_color(background);
+        self.ui.renderer.clear();
+        let font = Rc::new(ScalableFont::new(&self.ui.theme.heading_font, 60));
+        let color = self.ui.theme.highlight;
+        let message = Element::panel(Element::label(msg, &font, color), Padding::uniform(24.),
+                                     None, Some(color));
+        let screen = Bounds::screen(&self.ui);
+        message.draw_in(&mut self.ui, screen, Align::Center, Align::Center);
+        self.ui.renderer.present();
+        thread::sleep_ms(1500);
     }
 
     /// Modify speed by applying indicated multiplier. Additionally, if a slow motion turn is
@@ -576,12 +2218,52 @@ impl Game {
         return modified_speed;
     }
 
-    /// Ensure we run no faster than the desired fps by introducing a delay if necessary.
-    fn cap_frames_per_second(&self, duration_of_last_frame_execution_ms: u64) {
-        let max_delay_ms = 1000 / self.fps as u64;
-        if max_delay_ms > duration_of_last_frame_execution_ms {
-            thread::sleep_ms((max_delay_ms - duration_of_last_frame_execution_ms) as u32);
+    /// Handle the developer console for a frame: carry out the commands typed and draw the
+    /// console over the paused court.
+    fn run_console(&mut self, ctx: &mut GameLoopContext) {
+        while let Some(event) = self.ui.poll_event() {
+            if let Event::Quit {..} = event {
+                self.console.close(&self.ui);
+                self.running = false;
+                return;
+            }
+            if let Some(invocation) = self.console.handle_event(&self.ui, &event) {
+                let result = invocation.run(self);
+                self.console.print_result(result);
+            }
+            if !self.console.is_open {
+                break;
+            }
+        }
+
+        ctx.layered_draw_queue[1].push(self.ball.clone());
+        ctx.layered_draw_queue[1].push(self.lpaddle.clone());
+        ctx.layered_draw_queue[1].push(self.rpaddle.clone());
+        self.draw_scene(ctx);
+        if self.console.is_open {
+            self.console.draw(&mut self.ui, &self.hud_font);
+        }
+        self.ui.renderer.present();
+    }
+
+    /// Let a console command change the match. The console only opens against the computer,
+    /// and a replay only holds the input of the players, so it could not repeat the change: it
+    /// is refused while recording. A changed match is kept out of the history, the profile and
+    /// the leaderboards.
+    fn change_match_from_console(&mut self) -> Result<(), String> {
+        match self.opponent {
+            Opponent::Computer => {},
+            _ => return Err("only matches against the computer can be changed".to_string())
+        }
+        if self.recorder.is_some() {
+            return Err("replays are being recorded, the match cannot be changed".to_string());
         }
+        if !self.console_changed {
+            self.console_changed = true;
+            self.console.print("This match no longer counts towards the history, the profile \
+                                or the leaderboards");
+        }
+        return Ok(());
     }
     
 }
@@ -590,11 +2272,33 @@ impl Resettable for Game {
 
     fn reset(&mut self) {
 
+        self.score = MatchScore::new();
+
         // Reset slow motion status.
+        self.sim_time_ms = 0;
         self.time_ball_last_speedup_ms = Option::None;
         self.slow_motions_remaining = 3;
         self.time_slow_motion_started_ms = Option::None;
 
+        // Start counting ticks from scratch for the next match.
+        self.tick = 0;
+        self.desync_detector.clear();
+        self.desync_reported = false;
+        self.console_changed = false;
+
+        self.instant_replay_buffer.clear();
+        self.point_scored = false;
+        self.rewinds_remaining = REWINDS_PER_MATCH;
+        self.rewind_history.clear();
+        self.rewind_frames.clear();
+        self.stats.reset();
+
+        // Undo any change the console made to the speed of the ball for the last match.
+        {
+            let mut ball = self.ball.borrow_mut();
+            ball.speed = ball.initial_speed;
+        }
+
         // Reset objects.
         for r in self.resettables.iter() {
             r.borrow_mut().reset();
@@ -602,3 +2306,211 @@ impl Resettable for Game {
     } 
 
 }
+
+/// Whether a key shows or hides one of the debug displays, wherever a match is played, replayed
+/// or followed.
+fn is_debug_display_key(key: Keycode) -> bool {
+    return key == Keycode::F2 || key == Keycode::F3;
+}
+
+/// Format a duration as minutes and seconds.
+fn format_time(ms: u64) -> String {
+    return format!("{}:{:02}", ms / 60000, ms / 1000 % 60);
+}
+
+/// Tab completions of console arguments.
+const NO_COMPLETIONS: &'static [&'static str] = &[];
+const AI_LEVEL_NAMES: &'static [&'static str] = &["easy", "normal", "hard"];
+const SPAWNABLES: &'static [&'static str] = &["ball"];
+const SLOWMO_ACTIONS: &'static [&'static str] = &["add"];
+const SHOW_HIDE: &'static [&'static str] = &["show", "hide"];
+const PACING_NAMES: &'static [&'static str] = &["vsync", "limited", "uncapped"];
+const FPS_ACTIONS: &'static [&'static str] = &["show", "hide", "log"];
+const WINDOW_MODE_NAMES: &'static [&'static str] = &["windowed", "borderless", "fullscreen"];
+
+/// The commands and variables of the developer console, grouped by the part of the game they
+/// change.
+fn console_commands() -> CommandRegistry<Game> {
+    let mut registry = CommandRegistry::new();
+
+    // Ball.
+    registry.add_variable("ball.speed", NO_COMPLETIONS, set_ball_speed);
+    registry.add_command("spawn", "spawn ball", SPAWNABLES, spawn);
+
+    // Computer player.
+    registry.add_variable("ai", AI_LEVEL_NAMES, set_ai_level);
+
+    // Score and powers.
+    registry.add_command("score", "score <left> <right>", NO_COMPLETIONS, set_score);
+    registry.add_command("slowmo", "slowmo add <count>", SLOWMO_ACTIONS, add_slow_motions);
+
+    // Display.
+    registry.add_variable("pacing", PACING_NAMES, set_frame_pacing);
+    registry.add_command("fps", "fps show|hide|log <file>", FPS_ACTIONS, fps);
+    registry.add_command("debug", "debug show|hide", SHOW_HIDE, show_debug_overlay);
+
+    // Window.
+    registry.add_variable("window", WINDOW_MODE_NAMES, set_window_mode);
+    registry.add_variable("display", NO_COMPLETIONS, set_display);
+    registry.add_variable("resolution", NO_COMPLETIONS, set_resolution);
+    registry.add_command("displays", "displays", NO_COMPLETIONS, list_displays);
+    registry.add_listed_variable("theme", Theme::names, set_theme);
+
+    return registry;
+}
+
+/// Parse the argument at `index`, naming it in the error if it is missing or malformed.
+fn console_arg<T: FromStr>(args: &[&str], index: usize, name: &str) -> Result<T, String> {
+    return match args.get(index) {
+        Some(arg) => arg.parse().map_err(|_| format!("'{}' is not a valid {}", arg, name)),
+        None => Err(format!("missing {}", name))
+    };
+}
+
+fn set_ball_speed(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    let speed: f32 = try!(console_arg(args, 0, "speed"));
+    if speed <= 0. {
+        return Err("the speed must be above 0".to_string());
+    }
+    try!(game.change_match_from_console());
+
+    // Keep the ball heading the same way.
+    let mut ball = game.ball.borrow_mut();
+    let scale = speed / ball.speed;
+    ball.vx *= scale;
+    ball.vy *= scale;
+    ball.speed = speed;
+    return Ok(format!("ball.speed is {}", speed));
+}
+
+fn spawn(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    if args.first() != Some(&"ball") {
+        return Err("usage: spawn ball".to_string());
+    }
+    try!(game.change_match_from_console());
+
+    // There is only ever one ball, so it is served afresh from the center.
+    game.ball.borrow_mut().reset();
+    game.serve_from_launch();
+    return Ok("ball served from the center".to_string());
+}
+
+fn set_ai_level(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    let ai_level: AiLevel = try!(console_arg(args, 0, "AI level"));
+    try!(game.change_match_from_console());
+    game.ai_level = ai_level;
+    game.apply_ai_level();
+    return Ok(format!("ai is {}", ai_level.name()));
+}
+
+fn set_score(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    let left: u32 = try!(console_arg(args, 0, "left score"));
+    let right: u32 = try!(console_arg(args, 1, "right score"));
+
+    // Points only ever make up the game being played, so a score that would have won it is
+    // not one the match can be at.
+    if game.rules.game_won(left, right) || game.rules.game_won(right, left) {
+        return Err(format!("{}-{} would already have won the game", left, right));
+    }
+    try!(game.change_match_from_console());
+    game.score.points = [left, right];
+    return Ok(format!("score is {}-{}", left, right));
+}
+
+fn add_slow_motions(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    if args.first() != Some(&"add") {
+        return Err("usage: slowmo add <count>".to_string());
+    }
+    let count: u32 = try!(console_arg(args, 1, "count"));
+    try!(game.change_match_from_console());
+    game.slow_motions_remaining += count;
+    return Ok(format!("{} slow motions left", game.slow_motions_remaining));
+}
+
+fn fps(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    match args.first() {
+        Some(&"show") => game.profiler.visible = true,
+        Some(&"hide") => game.profiler.visible = false,
+        Some(&"log") => {
+            let path = match args.get(1) {
+                Some(path) => PathBuf::from(path),
+                None => return Err("usage: fps log <file>".to_string())
+            };
+            try!(game.profiler.log_to(&path).map_err(|e| e.to_string()));
+            return Ok(format!("logging frame times to {}", path.display()));
+        },
+        _ => return Err("usage: fps show|hide|log <file>".to_string())
+    }
+    return Ok(String::new());
+}
+
+fn show_debug_overlay(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    game.debug_overlay = match args.first() {
+        Some(&"show") => true,
+        Some(&"hide") => false,
+        _ => return Err("usage: debug show|hide".to_string())
+    };
+    return Ok(String::new());
+}
+
+fn set_frame_pacing(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    let pacing: FramePacing = try!(console_arg(args, 0, "frame pacing"));
+    game.settings.frame_pacing = pacing;
+    game.settings.save_or_report();
+
+    // VSync is set up along with the window, so turning it on or off waits for a restart.
+    let vsync = FramePacing::VSync;
+    if pacing != game.pacer.pacing && (pacing == vsync || game.pacer.pacing == vsync) {
+        return Ok(format!("pacing will be {} from the next start", pacing.name()));
+    }
+    game.pacer.pacing = pacing;
+    game.pacer.restart();
+    return Ok(format!("pacing is {}", pacing.name()));
+}
+
+fn set_window_mode(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    game.settings.window_mode = try!(console_arg(args, 0, "window mode"));
+    return apply_window_settings(game);
+}
+
+fn set_display(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    game.keep_window_mode();
+    game.settings.display = try!(console_arg(args, 0, "display"));
+    return apply_window_settings(game);
+}
+
+fn set_resolution(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    game.keep_window_mode();
+    game.settings.resolution = try!(console_arg(args, 0, "resolution"));
+    return apply_window_settings(game);
+}
+
+/// Save the window settings just changed and show the window accordingly.
+fn apply_window_settings(game: &mut Game) -> Result<String, String> {
+    game.settings.save_or_report();
+    let settings = &game.settings;
+    try!(game.ui.set_window_mode(settings.window_mode, settings.display, settings.resolution)
+         .map_err(|e| e.to_string()));
+    return Ok(format!("window is {} on display {} at {}", settings.window_mode.name(),
+                      settings.display, settings.resolution));
+}
+
+fn set_theme(game: &mut Game, args: &[&str]) -> Result<String, String> {
+    let name = match args.first() {
+        Some(name) => *name,
+        None => return Err("missing theme".to_string())
+    };
+    try!(game.ui.set_theme(name).map_err(|e| format!("unable to load theme {}: {}", name, e)));
+    game.settings.theme = name.to_string();
+    game.settings.save_or_report();
+    game.apply_theme();
+    return Ok(format!("theme is {}", name));
+}
+
+fn list_displays(game: &mut Game, _: &[&str]) -> Result<String, String> {
+    let lines = try!(game.ui.describe_displays().map_err(|e| e.to_string()));
+    for line in lines.iter() {
+        game.console.print(line);
+    }
+    return Ok(String::new());
+}
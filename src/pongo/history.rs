@@ -0,0 +1,536 @@
+use pongo::ai_level::AiLevel;
+use pongo::data_dir;
+use pongo::match_rules::MatchScore;
+use pongo::match_stats::{MatchStats, Side};
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version written at the start of every line of the history file. Lines with any other
+/// version are skipped.
+const HISTORY_VERSION: &'static str = "3";
+
+/// Number of fields on a line of the current version.
+const HISTORY_FIELDS: usize = 22;
+
+/// A finished match, as kept in the history file. The local player always played on the left.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchRecord {
+    pub finished_at: u64,       // Seconds since the Unix epoch.
+    pub player_name: String,
+    pub opponent_name: String,
+    pub ai_level: Option<AiLevel>,  // How well the computer played, if it was the opponent.
+    pub points_to_win: i32,         // Points needed to win a game.
+    pub player_score: i32,          // Points in the last game played.
+    pub opponent_score: i32,
+    pub player_games: i32,          // Games in the last set played.
+    pub opponent_games: i32,
+    pub player_sets: i32,
+    pub opponent_sets: i32,
+    pub stats: MatchStats
+}
+
+impl MatchRecord {
+
+    pub fn new(player_name: &str,
+               opponent_name: &str,
+               ai_level: Option<AiLevel>,
+               points_to_win: i32,
+               score: &MatchScore,
+               stats: &MatchStats) -> MatchRecord {
+        let (left, right) = (Side::Left as usize, Side::Right as usize);
+        let finished_at = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        return MatchRecord {
+            finished_at: finished_at,
+            player_name: player_name.to_string(),
+            opponent_name: opponent_name.to_string(),
+            ai_level: ai_level,
+            points_to_win: points_to_win,
+            player_score: score.points[left] as i32,
+            opponent_score: score.points[right] as i32,
+            player_games: score.games[left] as i32,
+            opponent_games: score.games[right] as i32,
+            player_sets: score.sets[left] as i32,
+            opponent_sets: score.sets[right] as i32,
+            stats: stats.clone()
+        };
+    }
+
+    /// Whoever is ahead on sets, then games, then points won, the same as on the court.
+    pub fn player_won(&self) -> bool {
+        return (self.player_sets, self.player_games, self.player_score) >
+            (self.opponent_sets, self.opponent_games, self.opponent_score);
+    }
+
+    /// The opponent's name, followed by the computer's level when it was the opponent, so that
+    /// matches against each level are told apart.
+    pub fn opponent_label(&self) -> String {
+        return match self.ai_level {
+            Some(level) => format!("{} ({})", self.opponent_name, level.name()),
+            None => self.opponent_name.clone()
+        };
+    }
+
+    /// Encode as a single line of tab separated fields. Names may contain spaces, but tabs are
+    /// replaced.
+    pub fn encode(&self) -> String {
+        let stats = &self.stats;
+        let (left, right) = (Side::Left as usize, Side::Right as usize);
+        let fields = [
+            HISTORY_VERSION.to_string(),
+            self.finished_at.to_string(),
+            self.player_name.replace("\t", " "),
+            self.opponent_name.replace("\t", " "),
+            self.ai_level.map_or("-", |level| level.name()).to_string(),
+            self.points_to_win.to_string(),
+            self.player_score.to_string(),
+            self.opponent_score.to_string(),
+            self.player_games.to_string(),
+            self.opponent_games.to_string(),
+            self.player_sets.to_string(),
+            self.opponent_sets.to_string(),
+            stats.points.to_string(),
+            stats.longest_rally.to_string(),
+            stats.paddle_hits[left].to_string(),
+            stats.paddle_hits[right].to_string(),
+            stats.wall_bounces.to_string(),
+            format!("{:?}", stats.top_ball_speed),
+            stats.slow_motions_used.to_string(),
+            stats.points_won_on_serve[left].to_string(),
+            stats.points_won_on_serve[right].to_string(),
+            stats.duration_ms.to_string()
+        ];
+        return fields.join("\t");
+    }
+
+    /// Decode a line produced by `encode`. Returns None if the line is malformed or was
+    /// written by another version.
+    pub fn decode(line: &str) -> Option<MatchRecord> {
+        let fields: Vec<&str> = line.trim_right_matches(|c| c == '\r' || c == '\n')
+            .split('\t')
+            .collect();
+        if fields.len() != HISTORY_FIELDS || fields[0] != HISTORY_VERSION {
+            return Option::None;
+        }
+        let ai_level: Option<AiLevel> = match fields[4] {
+            "-" => None,
+            level => Some(try_opt!(parse(level)))
+        };
+        let counts = &fields[12..];
+        let mut stats = MatchStats::new();
+        stats.points = try_opt!(parse(counts[0]));
+        stats.longest_rally = try_opt!(parse(counts[1]));
+        stats.paddle_hits = [try_opt!(parse(counts[2])), try_opt!(parse(counts[3]))];
+        stats.wall_bounces = try_opt!(parse(counts[4]));
+        stats.top_ball_speed = try_opt!(parse(counts[5]));
+        stats.slow_motions_used = try_opt!(parse(counts[6]));
+        stats.points_won_on_serve = [try_opt!(parse(counts[7])), try_opt!(parse(counts[8]))];
+        stats.duration_ms = try_opt!(parse(counts[9]));
+        return Some(MatchRecord {
+            finished_at: try_opt!(parse(fields[1])),
+            player_name: fields[2].to_string(),
+            opponent_name: fields[3].to_string(),
+            ai_level: ai_level,
+            points_to_win: try_opt!(parse(fields[5])),
+            player_score: try_opt!(parse(fields[6])),
+            opponent_score: try_opt!(parse(fields[7])),
+            player_games: try_opt!(parse(fields[8])),
+            opponent_games: try_opt!(parse(fields[9])),
+            player_sets: try_opt!(parse(fields[10])),
+            opponent_sets: try_opt!(parse(fields[11])),
+            stats: stats
+        });
+    }
+
+}
+
+fn parse<T: FromStr>(field: &str) -> Option<T> {
+    return field.parse::<T>().ok();
+}
+
+/// The history file in the data directory.
+pub fn history_path() -> PathBuf {
+    return data_dir::data_dir().join("history.txt");
+}
+
+/// Add a finished match to the end of the history file, creating it if need be.
+pub fn append(path: &Path, record: &MatchRecord) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            try!(fs::create_dir_all(dir));
+        }
+    }
+    let mut file = try!(OpenOptions::new().create(true).append(true).open(path));
+    return writeln!(file, "{}", record.encode());
+}
+
+/// Read every match in the history file, oldest first. A missing file is an empty history.
+pub fn load(path: &Path) -> io::Result<Vec<MatchRecord>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e)
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Some(record) = MatchRecord::decode(&try!(line)) {
+            records.push(record);
+        }
+    }
+    return Ok(records);
+}
+
+/// Totals over a number of matches.
+pub struct Aggregate {
+    pub opponent_name: String,  // Or "all" for every match. The computer's level follows its
+                                // name, so each level has totals of its own.
+    pub matches: u32,
+    pub wins: u32,
+    pub points: u32,
+    pub total_rally_hits: u32,
+    pub longest_rally: u32,
+    pub top_ball_speed: f32,
+    pub duration_ms: u64
+}
+
+impl Aggregate {
+
+    fn new(opponent_name: &str) -> Aggregate {
+        return Aggregate {
+            opponent_name: opponent_name.to_string(),
+            matches: 0,
+            wins: 0,
+            points: 0,
+            total_rally_hits: 0,
+            longest_rally: 0,
+            top_ball_speed: 0.,
+            duration_ms: 0
+        };
+    }
+
+    fn add(&mut self, record: &MatchRecord) {
+        self.matches += 1;
+        if record.player_won() {
+            self.wins += 1;
+        }
+        self.points += record.stats.points;
+        self.total_rally_hits += record.stats.total_rally_hits();
+        self.longest_rally = self.longest_rally.max(record.stats.longest_rally);
+        self.top_ball_speed = self.top_ball_speed.max(record.stats.top_ball_speed);
+        self.duration_ms += record.stats.duration_ms;
+    }
+
+    pub fn win_rate(&self) -> f32 {
+        return if self.matches == 0 {0.} else {self.wins as f32 / self.matches as f32};
+    }
+
+    /// Average number of paddle hits per point.
+    pub fn average_rally(&self) -> f32 {
+        return if self.points == 0 {0.} else {self.total_rally_hits as f32 / self.points as f32};
+    }
+
+}
+
+/// Totals over all matches, followed by totals per opponent in the order first played. Each
+/// level of the computer counts as an opponent of its own.
+pub fn aggregate(records: &[MatchRecord]) -> Vec<Aggregate> {
+    let mut aggregates = vec![Aggregate::new("all")];
+    for record in records.iter() {
+        aggregates[0].add(record);
+        let opponent = record.opponent_label();
+        let position = aggregates.iter().skip(1).position(|a| a.opponent_name == opponent);
+        let i = match position {
+            Some(i) => i + 1,
+            None => {
+                aggregates.push(Aggregate::new(&opponent));
+                aggregates.len() - 1
+            }
+        };
+        aggregates[i].add(record);
+    }
+    return aggregates;
+}
+
+/// Output formats for exported statistics.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Json,
+    Csv
+}
+
+impl FromStr for ExportFormat {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExportFormat, String> {
+        return match s {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!("unknown format '{}', expected json or csv", s))
+        };
+    }
+
+}
+
+/// What a CSV export holds. A CSV file has room for a single table, so the matches and the
+/// totals are exported one at a time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportTable {
+    Matches,
+    Totals
+}
+
+impl FromStr for ExportTable {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExportTable, String> {
+        return match s {
+            "matches" => Ok(ExportTable::Matches),
+            "totals" => Ok(ExportTable::Totals),
+            _ => Err(format!("unknown table '{}', expected matches or totals", s))
+        };
+    }
+
+}
+
+/// Write the matches and their aggregated totals in the given format. JSON holds both, while
+/// CSV holds the chosen table.
+pub fn export<W: Write>(writer: &mut W, records: &[MatchRecord], format: ExportFormat,
+                        table: ExportTable) -> io::Result<()> {
+    return match (format, table) {
+        (ExportFormat::Json, _) => export_json(writer, records),
+        (ExportFormat::Csv, ExportTable::Matches) => export_csv_matches(writer, records),
+        (ExportFormat::Csv, ExportTable::Totals) => export_csv_totals(writer, records)
+    };
+}
+
+/// A JSON object with a "matches" array holding one object per match and an "aggregates"
+/// array holding the totals.
+fn export_json<W: Write>(writer: &mut W, records: &[MatchRecord]) -> io::Result<()> {
+    let (left, right) = (Side::Left as usize, Side::Right as usize);
+    try!(writeln!(writer, "{{"));
+    try!(writeln!(writer, "  \"matches\": ["));
+    for (i, record) in records.iter().enumerate() {
+        let stats = &record.stats;
+        try!(write!(writer,
+                    "    {{\"finished_at\": {}, \"player\": {}, \"opponent\": {}, \
+                     \"ai_level\": {}, \"points_to_win\": {}, \"player_score\": {}, \
+                     \"opponent_score\": {}, \"player_games\": {}, \"opponent_games\": {}, \
+                     \"player_sets\": {}, \"opponent_sets\": {}, \"won\": {}, \"points\": {}, \
+                     \"longest_rally\": {}, \
+                     \"total_rally_hits\": {}, \"player_paddle_hits\": {}, \
+                     \"opponent_paddle_hits\": {}, \"wall_bounces\": {}, \
+                     \"top_ball_speed\": {:.1}, \"slow_motions_used\": {}, \
+                     \"player_points_won_on_serve\": {}, \
+                     \"opponent_points_won_on_serve\": {}, \"duration_ms\": {}}}",
+                    record.finished_at, json_string(&record.player_name),
+                    json_string(&record.opponent_name),
+                    record.ai_level.map_or("null".to_string(), |level| json_string(level.name())),
+                    record.points_to_win,
+                    record.player_score, record.opponent_score, record.player_games,
+                    record.opponent_games, record.player_sets, record.opponent_sets,
+                    record.player_won(),
+                    stats.points, stats.longest_rally, stats.total_rally_hits(),
+                    stats.paddle_hits[left], stats.paddle_hits[right], stats.wall_bounces,
+                    stats.top_ball_speed, stats.slow_motions_used,
+                    stats.points_won_on_serve[left], stats.points_won_on_serve[right],
+                    stats.duration_ms));
+        try!(writeln!(writer, "{}", if i + 1 < records.len() {","} else {""}));
+    }
+    try!(writeln!(writer, "  ],"));
+    try!(writeln!(writer, "  \"aggregates\": ["));
+    let aggregates = aggregate(records);
+    for (i, a) in aggregates.iter().enumerate() {
+        try!(write!(writer,
+                    "    {{\"opponent\": {}, \"matches\": {}, \"wins\": {}, \
+                     \"win_rate\": {:.3}, \"points\": {}, \"average_rally\": {:.2}, \
+                     \"longest_rally\": {}, \"top_ball_speed\": {:.1}, \"duration_ms\": {}}}",
+                    json_string(&a.opponent_name), a.matches, a.wins, a.win_rate(), a.points,
+                    a.average_rally(), a.longest_rally, a.top_ball_speed, a.duration_ms));
+        try!(writeln!(writer, "{}", if i + 1 < aggregates.len() {","} else {""}));
+    }
+    try!(writeln!(writer, "  ]"));
+    return writeln!(writer, "}}");
+}
+
+/// A header, then one row per match.
+fn export_csv_matches<W: Write>(writer: &mut W, records: &[MatchRecord]) -> io::Result<()> {
+    let (left, right) = (Side::Left as usize, Side::Right as usize);
+    try!(writeln!(writer, "finished_at,player,opponent,ai_level,points_to_win,player_score,\
+                           opponent_score,player_games,opponent_games,player_sets,\
+                           opponent_sets,won,points,longest_rally,total_rally_hits,\
+                           player_paddle_hits,opponent_paddle_hits,wall_bounces,\
+                           top_ball_speed,slow_motions_used,player_points_won_on_serve,\
+                           opponent_points_won_on_serve,duration_ms"));
+    for record in records.iter() {
+        let stats = &record.stats;
+        try!(writeln!(writer, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},\
+                               {:.1},{},{},{},{}",
+                      record.finished_at, csv_field(&record.player_name),
+                      csv_field(&record.opponent_name),
+                      record.ai_level.map_or("", |level| level.name()), record.points_to_win,
+                      record.player_score, record.opponent_score, record.player_games,
+                      record.opponent_games, record.player_sets, record.opponent_sets,
+                      record.player_won(),
+                      stats.points, stats.longest_rally, stats.total_rally_hits(),
+                      stats.paddle_hits[left], stats.paddle_hits[right], stats.wall_bounces,
+                      stats.top_ball_speed, stats.slow_motions_used,
+                      stats.points_won_on_serve[left], stats.points_won_on_serve[right],
+                      stats.duration_ms));
+    }
+    return Ok(());
+}
+
+/// A header, then one row per aggregate.
+fn export_csv_totals<W: Write>(writer: &mut W, records: &[MatchRecord]) -> io::Result<()> {
+    try!(writeln!(writer, "opponent,matches,wins,win_rate,points,average_rally,longest_rally,\
+                           top_ball_speed,duration_ms"));
+    for a in aggregate(records).iter() {
+        try!(writeln!(writer, "{},{},{},{:.3},{},{:.2},{},{:.1},{}",
+                      csv_field(&a.opponent_name), a.matches, a.wins, a.win_rate(), a.points,
+                      a.average_rally(), a.longest_rally, a.top_ball_speed, a.duration_ms));
+    }
+    return Ok(());
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    return escaped;
+}
+
+/// Quote a field that holds a separator, a quote or a line break, doubling any quotes in it.
+fn csv_field(value: &str) -> String {
+    if value.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        return format!("\"{}\"", value.replace("\"", "\"\""));
+    }
+    return value.to_string();
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{csv_field, export_csv_matches, export_json, json_string, MatchRecord};
+
+    use pongo::ai_level::AiLevel;
+    use pongo::match_stats::MatchStats;
+
+    use std::str;
+
+    fn record() -> MatchRecord {
+        let mut stats = MatchStats::new();
+        stats.points = 21;
+        stats.longest_rally = 14;
+        stats.paddle_hits = [63, 58];
+        stats.wall_bounces = 40;
+        stats.top_ball_speed = 812.375;
+        stats.slow_motions_used = 2;
+        stats.points_won_on_serve = [3, 1];
+        stats.duration_ms = 254_321;
+        return MatchRecord {
+            finished_at: 1_500_000_000,
+            player_name: "Zoë".to_string(),
+            opponent_name: "Computer".to_string(),
+            ai_level: Some(AiLevel::Hard),
+            points_to_win: 11,
+            player_score: 11,
+            opponent_score: 9,
+            player_games: 2,
+            opponent_games: 1,
+            player_sets: 1,
+            opponent_sets: 0,
+            stats: stats
+        };
+    }
+
+    /// The line of a record with one field replaced.
+    fn with_field(line: &str, i: usize, value: &str) -> String {
+        let mut fields: Vec<&str> = line.split('\t').collect();
+        fields[i] = value;
+        return fields.join("\t");
+    }
+
+    #[test]
+    fn record_survives_a_round_trip() {
+        assert_eq!(MatchRecord::decode(&record().encode()), Some(record()));
+
+        let mut human = record();
+        human.opponent_name = "Player 2".to_string();
+        human.ai_level = None;
+        assert_eq!(MatchRecord::decode(&format!("{}\r\n", human.encode())), Some(human));
+    }
+
+    #[test]
+    fn tabs_in_names_do_not_break_the_line() {
+        let mut record = record();
+        record.player_name = "Zo\të".to_string();
+        let decoded = MatchRecord::decode(&record.encode()).unwrap();
+        assert_eq!(decoded.player_name, "Zo ë");
+    }
+
+    #[test]
+    fn other_versions_and_malformed_lines_are_skipped() {
+        let line = record().encode();
+        assert_eq!(MatchRecord::decode(&with_field(&line, 0, "2")), None);
+        assert_eq!(MatchRecord::decode(&with_field(&line, 4, "expert")), None);
+        assert_eq!(MatchRecord::decode(&with_field(&line, 6, "eleven")), None);
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert_eq!(MatchRecord::decode(&fields[..21].join("\t")), None);
+        assert_eq!(MatchRecord::decode(&format!("{}\t0", line)), None);
+        assert_eq!(MatchRecord::decode(""), None);
+    }
+
+    #[test]
+    fn json_strings_escape_quotes_and_control_characters() {
+        assert_eq!(json_string("Zoë"), "\"Zoë\"");
+        assert_eq!(json_string("\"Ace\" \\o/"), "\"\\\"Ace\\\" \\\\o/\"");
+        assert_eq!(json_string("a\nb\rc\td"), "\"a\\nb\\rc\\td\"");
+        assert_eq!(json_string("\u{1}\u{1f}"), "\"\\u0001\\u001f\"");
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("Player 2"), "Player 2");
+        assert_eq!(csv_field("Smith, Jo"), "\"Smith, Jo\"");
+        assert_eq!(csv_field("\"Ace\""), "\"\"\"Ace\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("two\r\nlines"), "\"two\r\nlines\"");
+    }
+
+    #[test]
+    fn exports_escape_player_names() {
+        let mut record = record();
+        record.player_name = "\"Ace\", Jr.".to_string();
+
+        let mut json = Vec::new();
+        export_json(&mut json, &[record.clone()]).unwrap();
+        let json = str::from_utf8(&json).unwrap();
+        assert!(json.contains("\"player\": \"\\\"Ace\\\", Jr.\""));
+        assert!(json.contains("\"ai_level\": \"hard\""));
+
+        let mut csv = Vec::new();
+        export_csv_matches(&mut csv, &[record]).unwrap();
+        let csv = str::from_utf8(&csv).unwrap();
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("1500000000,\"\"\"Ace\"\", Jr.\",Computer,hard,11,11,9,"));
+        assert!(row.ends_with(",812.4,2,3,1,254321"));
+    }
+
+}
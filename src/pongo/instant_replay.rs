@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+
+/// Positions of the ball and paddles at the end of a tick. Enough to draw the court again.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InstantReplayFrame {
+    pub time_ms: u64,       // Simulated time since the match began.
+    pub ball_x: f32,
+    pub ball_y: f32,
+    pub lpaddle_y: f32,
+    pub rpaddle_y: f32
+}
+
+/// Holds the last few seconds of play, so that a point can be shown again right after it was
+/// scored. Frames older than the duration of the buffer are pushed out by newer ones, however
+/// many ticks that takes.
+pub struct InstantReplayBuffer {
+    frames: VecDeque<InstantReplayFrame>,
+    duration_ms: u64
+}
+
+impl InstantReplayBuffer {
+
+    pub fn new(duration_ms: u64) -> InstantReplayBuffer {
+        return InstantReplayBuffer {
+            frames: VecDeque::new(),
+            duration_ms: duration_ms
+        };
+    }
+
+    pub fn push(&mut self, frame: InstantReplayFrame) {
+        let oldest_kept_ms = frame.time_ms.saturating_sub(self.duration_ms);
+        while self.frames.front().map_or(false, |oldest| oldest.time_ms < oldest_kept_ms) {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// The buffered frames, oldest first.
+    pub fn frames(&self) -> Vec<InstantReplayFrame> {
+        return self.frames.iter().cloned().collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+}
+
+/// How long the frame at `index` was on screen during play: the time until the next frame, or
+/// for the last frame, since the one before. Frames may run forwards or backwards in time.
+pub fn frame_duration_ms(frames: &[InstantReplayFrame], index: usize) -> u64 {
+    let other = match frames.get(index + 1) {
+        Some(next) => next,
+        None if index > 0 => &frames[index - 1],
+        None => return 0
+    };
+    let time_ms = frames[index].time_ms;
+    return if other.time_ms > time_ms {other.time_ms - time_ms} else {time_ms - other.time_ms};
+}
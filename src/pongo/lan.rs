@@ -0,0 +1,432 @@
+extern crate clock_ticks;
+
+use pongo::match_rules::MatchRules;
+use pongo::relay_protocol::{self, RelayMessage};
+use pongo::snapshot::Snapshot;
+
+use std::env;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::str;
+
+/// Port on which hosts announce their games and browsers listen for them.
+pub const DISCOVERY_PORT: u16 = 47800;
+
+/// Prefix on every datagram. Anything without it, or with a different protocol version, is
+/// ignored.
+const PROTOCOL: &'static str = relay_protocol::PROTOCOL;
+
+/// Milliseconds without hearing from a peer before the connection is considered lost.
+pub const PEER_TIMEOUT_MS: u64 = 5000;
+
+/// Messages exchanged between hosts, browsers and joined players. Each message is sent as a
+/// single datagram containing one line of text.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    Announce { rules: MatchRules, in_progress: bool,    // Host to LAN, once a second. Games
+               host_name: String },                     // in progress can be watched.
+    Join { name: String },                              // Guest to host.
+    Welcome { name: String },                           // Host to guest, accepting the join.
+    Full,                                               // Host to guest, rejecting the join.
+    Ready(bool),                                        // Either way, during the ready-check.
+    Start,                                              // Host to guest, the match begins.
+    Leave,                                              // Either way, the sender has gone.
+    Input { tick: u64, paddle_y: f32 },                 // Guest to host, during the match.
+    State(Snapshot),                                    // Host to guest and spectators.
+    Watch,                                              // Spectator to host, once a second.
+    Spectators(u32),                                    // Host to guest and spectators.
+    Checksum { tick: u64, checksum: u64 }               // Host to guest, once a second.
+}
+
+impl Message {
+
+    pub fn encode(&self) -> String {
+        let body = match *self {
+            Message::Announce { ref rules, in_progress, ref host_name } =>
+                format!("ANNOUNCE {} {} {}", rules, if in_progress {1} else {0}, host_name),
+            Message::Join { ref name } => format!("JOIN {}", name),
+            Message::Welcome { ref name } => format!("WELCOME {}", name),
+            Message::Full => "FULL".to_string(),
+            Message::Ready(ready) => format!("READY {}", if ready {1} else {0}),
+            Message::Start => "START".to_string(),
+            Message::Leave => "LEAVE".to_string(),
+            Message::Input { tick, paddle_y } => format!("INPUT {} {:?}", tick, paddle_y),
+            Message::State(ref snapshot) => format!("STATE {}", snapshot.encode()),
+            Message::Watch => "WATCH".to_string(),
+            Message::Spectators(count) => format!("SPECTATORS {}", count),
+            Message::Checksum { tick, checksum } => format!("CHECKSUM {} {:016x}", tick, checksum)
+        };
+        return format!("{} {}", PROTOCOL, body);
+    }
+
+    pub fn decode(line: &str) -> Option<Message> {
+        let mut parts = line.trim().splitn(3, ' ');
+        if parts.next() != Some(PROTOCOL) {
+            return Option::None;
+        }
+        let kind = try_opt!(parts.next());
+        let rest = parts.next().unwrap_or("");
+        return match kind {
+            "ANNOUNCE" => {
+                let mut fields = rest.splitn(3, ' ');
+                let rules = try_opt!(try_opt!(fields.next()).parse().ok());
+                let in_progress = try_opt!(fields.next()) == "1";
+                let host_name = fields.next().unwrap_or("").to_string();
+                Some(Message::Announce {
+                    rules: rules,
+                    in_progress: in_progress,
+                    host_name: host_name
+                })
+            },
+            "JOIN" => Some(Message::Join { name: rest.to_string() }),
+            "WELCOME" => Some(Message::Welcome { name: rest.to_string() }),
+            "FULL" => Some(Message::Full),
+            "READY" => Some(Message::Ready(rest == "1")),
+            "START" => Some(Message::Start),
+            "LEAVE" => Some(Message::Leave),
+            "INPUT" => {
+                let mut fields = rest.split(' ');
+                let tick = try_opt!(try_opt!(fields.next()).parse().ok());
+                let paddle_y = try_opt!(try_opt!(fields.next()).parse().ok());
+                Some(Message::Input { tick: tick, paddle_y: paddle_y })
+            },
+            "STATE" => Snapshot::decode(rest).map(Message::State),
+            "WATCH" => Some(Message::Watch),
+            "SPECTATORS" => rest.parse().ok().map(Message::Spectators),
+            "CHECKSUM" => {
+                let mut fields = rest.split(' ');
+                let tick = try_opt!(try_opt!(fields.next()).parse().ok());
+                let checksum = try_opt!(u64::from_str_radix(try_opt!(fields.next()), 16).ok());
+                Some(Message::Checksum { tick: tick, checksum: checksum })
+            },
+            _ => None
+        };
+    }
+
+}
+
+/// A non-blocking UDP socket that speaks in `Message`s.
+pub struct Endpoint {
+    socket: UdpSocket
+}
+
+impl Endpoint {
+
+    /// Bind to the given port on all interfaces. Use port 0 to let the OS choose one.
+    pub fn bind(port: u16) -> io::Result<Endpoint> {
+        let socket = try!(UdpSocket::bind(("0.0.0.0", port)));
+        try!(socket.set_nonblocking(true));
+        try!(socket.set_broadcast(true));
+        return Ok(Endpoint { socket: socket });
+    }
+
+    pub fn local_port(&self) -> u16 {
+        return self.socket.local_addr().map(|addr| addr.port()).unwrap_or(0);
+    }
+
+    pub fn send(&self, message: &Message, addr: SocketAddr) {
+        self.send_line(&message.encode(), addr);
+    }
+
+    pub fn send_line(&self, line: &str, addr: SocketAddr) {
+        // Datagrams may be lost anyway, so a failed send is treated the same way.
+        let _ = self.socket.send_to(line.as_bytes(), addr);
+    }
+
+    /// Receive the next pending message, skipping anything that does not decode. Returns None
+    /// once there is nothing left to read.
+    pub fn recv(&self) -> Option<(Message, SocketAddr)> {
+        while let Some((line, addr)) = self.recv_line() {
+            if let Some(message) = Message::decode(&line) {
+                return Some((message, addr));
+            }
+        }
+        return None;
+    }
+
+    /// Receive the next pending datagram as text, whatever it contains.
+    pub fn recv_line(&self) -> Option<(String, SocketAddr)> {
+        let mut buf = [0; 1024];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, addr)) => {
+                    if let Ok(line) = str::from_utf8(&buf[..len]) {
+                        return Some((line.to_string(), addr));
+                    }
+                },
+                Err(_) => return None
+            }
+        }
+    }
+
+}
+
+/// The other player in a match. Usually reached directly on the LAN, but it may also be
+/// reached through a relay server, in which case `addr` is the address of the relay.
+pub struct LanPeer {
+    pub endpoint: Endpoint,
+    pub addr: SocketAddr,
+    pub name: String,
+    pub last_heard_ms: u64,
+    pub relayed: bool
+}
+
+impl LanPeer {
+
+    pub fn new(endpoint: Endpoint, addr: SocketAddr, name: String) -> LanPeer {
+        return LanPeer {
+            endpoint: endpoint,
+            addr: addr,
+            name: name,
+            last_heard_ms: clock_ticks::precise_time_ms(),
+            relayed: false
+        };
+    }
+
+    /// A peer whose messages pass through the relay server at the given address.
+    pub fn relayed(endpoint: Endpoint, relay_addr: SocketAddr, name: String) -> LanPeer {
+        let mut peer = LanPeer::new(endpoint, relay_addr, name);
+        peer.relayed = true;
+        return peer;
+    }
+
+    pub fn send(&self, message: &Message) {
+        if self.relayed {
+            let relayed = RelayMessage::Relay(message.encode());
+            self.endpoint.send_line(&relayed.encode(), self.addr);
+        } else {
+            self.endpoint.send(message, self.addr);
+        }
+    }
+
+    /// Tell the peer we are gone. A relayed peer also lets the relay know it can forget us.
+    pub fn leave(&self) {
+        self.send(&Message::Leave);
+        if self.relayed {
+            self.endpoint.send_line(&RelayMessage::Bye.encode(), self.addr);
+        }
+    }
+
+    /// Receive the next message from the peer. Datagrams from anyone else are dropped.
+    pub fn recv(&mut self) -> Option<Message> {
+        while let Some((message, addr)) = self.endpoint.recv() {
+            if addr == self.addr {
+                self.last_heard_ms = clock_ticks::precise_time_ms();
+                return Some(message);
+            }
+        }
+        return None;
+    }
+
+    pub fn timed_out(&self) -> bool {
+        return clock_ticks::precise_time_ms() - self.last_heard_ms > PEER_TIMEOUT_MS;
+    }
+
+}
+
+/// Periodically broadcasts a host's game so that browsers on the LAN can list it. The
+/// announcement is also sent to the loopback address, so a game hosted on this machine shows up
+/// even where broadcast does not reach the local host.
+pub struct HostAnnouncer {
+    pub host_name: String,
+    pub rules: MatchRules,
+    pub in_progress: bool,      // Announce a match that can be watched rather than joined.
+    time_last_announced_ms: Option<u64>
+}
+
+impl HostAnnouncer {
+
+    pub fn new(host_name: &str, rules: MatchRules) -> HostAnnouncer {
+        return HostAnnouncer {
+            host_name: host_name.to_string(),
+            rules: rules,
+            in_progress: false,
+            time_last_announced_ms: Option::None
+        };
+    }
+
+    /// Send an announcement from the host's own endpoint if a second has passed since the last
+    /// one. Guests reply to the address the announcement came from.
+    pub fn announce_if_due(&mut self, endpoint: &Endpoint) {
+        let now_ms = clock_ticks::precise_time_ms();
+        if let Some(time_last_announced_ms) = self.time_last_announced_ms {
+            if now_ms - time_last_announced_ms < 1000 {
+                return;
+            }
+        }
+        let message = Message::Announce {
+            rules: self.rules,
+            in_progress: self.in_progress,
+            host_name: self.host_name.clone()
+        };
+        let broadcast = SocketAddrV4::new(Ipv4Addr::new(255, 255, 255, 255), DISCOVERY_PORT);
+        let loopback = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), DISCOVERY_PORT);
+        endpoint.send(&message, SocketAddr::V4(broadcast));
+        endpoint.send(&message, SocketAddr::V4(loopback));
+        self.time_last_announced_ms = Some(now_ms);
+    }
+
+}
+
+/// A game found on the LAN.
+#[derive(Clone)]
+pub struct DiscoveredHost {
+    pub addr: SocketAddr,
+    pub host_name: String,
+    pub rules: MatchRules,
+    pub in_progress: bool,
+    pub last_seen_ms: u64
+}
+
+/// Listens for host announcements and keeps a list of games that are currently open, or of
+/// matches in progress that can be watched.
+pub struct HostBrowser {
+    endpoint: Endpoint,
+    in_progress: bool,
+    pub hosts: Vec<DiscoveredHost>
+}
+
+impl HostBrowser {
+
+    /// Only one browser per machine can listen on the discovery port at a time.
+    pub fn new(in_progress: bool) -> io::Result<HostBrowser> {
+        let endpoint = try!(Endpoint::bind(DISCOVERY_PORT));
+        return Ok(HostBrowser { endpoint: endpoint, in_progress: in_progress, hosts: Vec::new() });
+    }
+
+    /// Take in any announcements received since the last call and forget hosts that have not
+    /// announced themselves for a few seconds.
+    pub fn refresh(&mut self) {
+        let now_ms = clock_ticks::precise_time_ms();
+        while let Some((message, addr)) = self.endpoint.recv() {
+            if let Message::Announce { rules, in_progress, host_name } = message {
+                if in_progress != self.in_progress {
+                    continue;
+                }
+                let host = DiscoveredHost {
+                    addr: addr,
+                    host_name: host_name,
+                    rules: rules,
+                    in_progress: in_progress,
+                    last_seen_ms: now_ms
+                };
+
+                remember_host(&mut self.hosts, host);
+            }
+        }
+        self.hosts.retain(|h| now_ms - h.last_seen_ms < 3000);
+    }
+
+}
+
+/// Add a host to those found, or update it if it was found before. A host announced on both
+/// broadcast and loopback is the same game.
+fn remember_host(hosts: &mut Vec<DiscoveredHost>, host: DiscoveredHost) {
+    match hosts.iter().position(|h| h.addr.port() == host.addr.port() &&
+                                    h.host_name == host.host_name) {
+        Some(i) => hosts[i] = host,
+        None => hosts.push(host)
+    }
+}
+
+/// The name shown to other players on the LAN.
+pub fn local_player_name() -> String {
+    return env::var("USER")
+        .or(env::var("USERNAME"))
+        .unwrap_or("Player".to_string());
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pongo::match_rules::{MatchRules, MatchScore};
+    use pongo::snapshot::Snapshot;
+
+    fn round_trip(message: Message) {
+        assert_eq!(Message::decode(&message.encode()), Some(message));
+    }
+
+    fn host(addr: &str, host_name: &str, last_seen_ms: u64) -> DiscoveredHost {
+        return DiscoveredHost {
+            addr: addr.parse().unwrap(),
+            host_name: host_name.to_string(),
+            rules: MatchRules::first_to(11),
+            in_progress: false,
+            last_seen_ms: last_seen_ms
+        };
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        round_trip(Message::Announce {
+            rules: MatchRules::first_to(5),
+            in_progress: true,
+            host_name: "Ada Lovelace".to_string()
+        });
+        round_trip(Message::Join { name: "Grace Hopper".to_string() });
+        round_trip(Message::Welcome { name: "Alan".to_string() });
+        round_trip(Message::Full);
+        round_trip(Message::Ready(true));
+        round_trip(Message::Ready(false));
+        round_trip(Message::Start);
+        round_trip(Message::Leave);
+        round_trip(Message::Input { tick: 1234, paddle_y: 270.125 });
+        let snapshot = Snapshot {
+            tick: 99,
+            ball_x: 0.1,
+            ball_y: 300.,
+            ball_vx: -383.04,
+            ball_vy: 321.39,
+            ball_speed_multiplier: 1.2,
+            lpaddle_y: 12.5,
+            rpaddle_y: 470.,
+            rpaddle_speed_multiplier: 1.,
+            score: MatchScore::new(),
+            clock_ms: 1650,
+            slow_motions_remaining: 2,
+            slow_motion_active: false,
+            rewinds_remaining: 1
+        };
+        round_trip(Message::State(snapshot));
+        round_trip(Message::Watch);
+        round_trip(Message::Spectators(3));
+        round_trip(Message::Checksum { tick: 60, checksum: 0x0123456789abcdef });
+    }
+
+    #[test]
+    fn malformed_messages_are_dropped() {
+        assert_eq!(Message::decode(""), None);
+        assert_eq!(Message::decode("START"), None);
+        assert_eq!(Message::decode("PONGO0 START"), None);
+        assert_eq!(Message::decode(&format!("{}", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} HELLO", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} ANNOUNCE", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} ANNOUNCE nonsense 1 Ada", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} INPUT 12", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} INPUT x 1.5", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} STATE 1 2 3", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} SPECTATORS many", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} CHECKSUM 60", PROTOCOL)), None);
+        assert_eq!(Message::decode(&format!("{} CHECKSUM 60 xyz", PROTOCOL)), None);
+    }
+
+    #[test]
+    fn line_endings_are_ignored() {
+        assert_eq!(Message::decode(&format!("{} LEAVE\r\n", PROTOCOL)), Some(Message::Leave));
+    }
+
+    #[test]
+    fn a_host_found_twice_is_listed_once() {
+        let mut hosts = Vec::new();
+        remember_host(&mut hosts, host("192.168.1.20:40000", "Ada", 100));
+        remember_host(&mut hosts, host("127.0.0.1:40000", "Ada", 200));
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].last_seen_ms, 200);
+
+        remember_host(&mut hosts, host("192.168.1.20:40001", "Ada", 300));
+        remember_host(&mut hosts, host("192.168.1.21:40000", "Grace", 300));
+        assert_eq!(hosts.len(), 3);
+    }
+
+}
@@ -0,0 +1,330 @@
+use pongo::ui::{ScalableFont, Ui};
+
+use sdl2::pixels::Color;
+
+use sdl2_gfx::primitives::DrawRenderer;
+
+use std::rc::Rc;
+
+/// Where something sits along one axis of the space it is given.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Align {
+    Start,      // Left, or top.
+    Center,
+    End         // Right, or bottom.
+}
+
+impl Align {
+
+    /// How far into `space` something `size` long starts.
+    fn offset(&self, size: f32, space: f32) -> f32 {
+        return match *self {
+            Align::Start => 0.,
+            Align::Center => (space - size) / 2.,
+            Align::End => space - size
+        };
+    }
+
+}
+
+/// Which way a stack lays out its children.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Horizontal,     // A row, left to right.
+    Vertical        // A column, top to bottom.
+}
+
+/// Space a panel leaves around what it holds.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Padding {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32
+}
+
+impl Padding {
+
+    pub fn uniform(padding: f32) -> Padding {
+        return Padding { top: padding, right: padding, bottom: padding, left: padding };
+    }
+
+}
+
+/// A rectangle in the logical space of the screen.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Bounds {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32
+}
+
+impl Bounds {
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Bounds {
+        return Bounds { x: x, y: y, width: width, height: height };
+    }
+
+    /// The whole screen.
+    pub fn screen(ui: &Ui) -> Bounds {
+        return Bounds::new(0., 0., ui.width, ui.height);
+    }
+
+}
+
+/// Part of a screen. Elements take the size of their contents, measured through their fonts,
+/// and stacks and panels place what they hold from that, so a screen is described by how its
+/// parts are arranged rather than where each one goes.
+pub enum Element {
+    Label { text: String, font: Rc<ScalableFont>, color: Color },
+    Button {
+        id: usize,                  // Told apart from other buttons by this.
+        text: String,
+        font: Rc<ScalableFont>,
+        color: Color,
+        focus_color: Color,
+        focused: bool               // Drawn in the focus color between markers.
+    },
+    Panel {
+        child: Box<Element>,
+        padding: Padding,
+        background: Option<Color>,
+        border: Option<Color>
+    },
+    Stack {
+        direction: Direction,
+        align: Align,               // Where each child sits across the stack.
+        spacing: f32,               // Between one child and the next.
+        children: Vec<Element>
+    },
+    Space { width: f32, height: f32 }
+}
+
+impl Element {
+
+    pub fn label(text: &str, font: &Rc<ScalableFont>, color: Color) -> Element {
+        return Element::Label { text: text.to_string(), font: font.clone(), color: color };
+    }
+
+    pub fn button(id: usize, text: &str, font: &Rc<ScalableFont>, color: Color,
+                  focus_color: Color) -> Element {
+        return Element::Button {
+            id: id,
+            text: text.to_string(),
+            font: font.clone(),
+            color: color,
+            focus_color: focus_color,
+            focused: false
+        };
+    }
+
+    pub fn panel(child: Element, padding: Padding, background: Option<Color>,
+                 border: Option<Color>) -> Element {
+        return Element::Panel {
+            child: Box::new(child),
+            padding: padding,
+            background: background,
+            border: border
+        };
+    }
+
+    /// Children side by side, aligned vertically within the row.
+    pub fn row(align: Align, spacing: f32, children: Vec<Element>) -> Element {
+        return Element::Stack {
+            direction: Direction::Horizontal,
+            align: align,
+            spacing: spacing,
+            children: children
+        };
+    }
+
+    /// Children one below the other, aligned horizontally within the column.
+    pub fn column(align: Align, spacing: f32, children: Vec<Element>) -> Element {
+        return Element::Stack {
+            direction: Direction::Vertical,
+            align: align,
+            spacing: spacing,
+            children: children
+        };
+    }
+
+    pub fn space(width: f32, height: f32) -> Element {
+        return Element::Space { width: width, height: height };
+    }
+
+    /// Measure the element and everything in it, each once.
+    fn measure(&self, ui: &Ui) -> Measured {
+        return match *self {
+            Element::Label { ref text, ref font, .. } => {
+                let (width, height) = if text.is_empty() {(0., 0.)} else {ui.text_size(font, text)};
+                Measured::leaf(width, height)
+            },
+            // Room is left for the focus markers, so focusing a button does not move it.
+            Element::Button { ref text, ref font, .. } => {
+                let (width, height) = ui.text_size(font, &focused_text(text));
+                Measured::leaf(width, height)
+            },
+            Element::Panel { ref child, padding, .. } => {
+                let child = child.measure(ui);
+                Measured {
+                    width: child.width + padding.left + padding.right,
+                    height: child.height + padding.top + padding.bottom,
+                    children: vec![child]
+                }
+            },
+            Element::Stack { direction, spacing, ref children, .. } => {
+                let children: Vec<Measured> = children.iter().map(|child| child.measure(ui))
+                    .collect();
+                let mut length: f32 = 0.;
+                let mut breadth: f32 = 0.;
+                for child in children.iter() {
+                    let (along, across) = match direction {
+                        Direction::Horizontal => (child.width, child.height),
+                        Direction::Vertical => (child.height, child.width)
+                    };
+                    length += along;
+                    breadth = breadth.max(across);
+                }
+                if children.len() > 1 {
+                    length += spacing * (children.len() - 1) as f32;
+                }
+                let (width, height) = match direction {
+                    Direction::Horizontal => (length, breadth),
+                    Direction::Vertical => (breadth, length)
+                };
+                Measured { width: width, height: height, children: children }
+            },
+            Element::Space { width, height } => Measured::leaf(width, height)
+        };
+    }
+
+    /// Work out where the element and everything in it goes with its top left corner at x, y,
+    /// from the sizes measured for them. Each is added after whatever it is drawn over.
+    fn arrange<'a>(&'a self, measured: &Measured, x: f32, y: f32,
+                   placed: &mut Vec<(Bounds, &'a Element)>) {
+        let (width, height) = (measured.width, measured.height);
+        placed.push((Bounds::new(x, y, width, height), self));
+        match *self {
+            Element::Panel { ref child, padding, .. } => {
+                child.arrange(&measured.children[0], x + padding.left, y + padding.top, placed);
+            },
+            Element::Stack { direction, align, spacing, ref children } => {
+                let mut along = 0.;
+                for (child, size) in children.iter().zip(measured.children.iter()) {
+                    match direction {
+                        Direction::Horizontal => {
+                            let child_y = y + align.offset(size.height, height);
+                            child.arrange(size, x + along, child_y, placed);
+                            along += size.width + spacing;
+                        },
+                        Direction::Vertical => {
+                            let child_x = x + align.offset(size.width, width);
+                            child.arrange(size, child_x, y + along, placed);
+                            along += size.height + spacing;
+                        }
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Draw the element with its top left corner at x, y.
+    pub fn draw(&self, ui: &mut Ui, x: f32, y: f32) {
+        let measured = self.measure(ui);
+        self.draw_measured(ui, &measured, x, y);
+    }
+
+    /// Draw the element aligned within an area of the screen. Returns where it went.
+    pub fn draw_in(&self, ui: &mut Ui, area: Bounds, horizontal: Align,
+                   vertical: Align) -> Bounds {
+        let measured = self.measure(ui);
+        let bounds = aligned(area, measured.width, measured.height, horizontal, vertical);
+        self.draw_measured(ui, &measured, bounds.x, bounds.y);
+        return bounds;
+    }
+
+    fn draw_measured(&self, ui: &mut Ui, measured: &Measured, x: f32, y: f32) {
+        let mut placed = Vec::new();
+        self.arrange(measured, x, y, &mut placed);
+        for &(bounds, element) in placed.iter() {
+            element.draw_self(ui, bounds);
+        }
+    }
+
+    /// Draw this element alone, not what it holds.
+    fn draw_self(&self, ui: &mut Ui, bounds: Bounds) {
+        match *self {
+            Element::Label { ref text, ref font, color } => {
+                if !text.is_empty() {
+                    ui.draw_text(font, text, color, bounds.x, bounds.y);
+                }
+            },
+            Element::Button { ref text, ref font, color, focus_color, focused, .. } => {
+                let (text, color) = if focused {
+                    (focused_text(text), focus_color)
+                } else {
+                    (text.clone(), color)
+                };
+                let (width, _) = ui.text_size(font, &text);
+                let x = bounds.x + Align::Center.offset(width, bounds.width);
+                ui.draw_text(font, &text, color, x, bounds.y);
+            },
+            Element::Panel { background, border, .. } => {
+                let (left, top) = (bounds.x as i16, bounds.y as i16);
+                let right = (bounds.x + bounds.width) as i16;
+                let bottom = (bounds.y + bounds.height) as i16;
+                if let Some(background) = background {
+                    let _ = ui.renderer.box_(left, top, right, bottom, background);
+                }
+                if let Some(border) = border {
+                    let _ = ui.renderer.rectangle(left, top, right, bottom, border);
+                }
+            },
+            Element::Stack {..} | Element::Space {..} => {}
+        }
+    }
+
+    /// Focus the button with the given id, if any, and no other.
+    pub fn set_focus(&mut self, focus: Option<usize>) {
+        match *self {
+            Element::Button { id, ref mut focused, .. } => *focused = focus == Some(id),
+            Element::Panel { ref mut child, .. } => child.set_focus(focus),
+            Element::Stack { ref mut children, .. } => {
+                for child in children.iter_mut() {
+                    child.set_focus(focus);
+                }
+            },
+            _ => {}
+        }
+    }
+
+}
+
+/// The size of an element and of everything in it. Laying out a screen measures each element
+/// once, rather than again at every level of stacks and panels it sits in.
+struct Measured {
+    width: f32,
+    height: f32,
+    children: Vec<Measured>     // In the order the element holds them.
+}
+
+impl Measured {
+
+    fn leaf(width: f32, height: f32) -> Measured {
+        return Measured { width: width, height: height, children: Vec::new() };
+    }
+
+}
+
+/// Where something of the given size goes when aligned within an area.
+fn aligned(area: Bounds, width: f32, height: f32, horizontal: Align, vertical: Align) -> Bounds {
+    return Bounds::new(area.x + horizontal.offset(width, area.width),
+                       area.y + vertical.offset(height, area.height), width, height);
+}
+
+/// A button's text as drawn while it has the focus.
+fn focused_text(text: &str) -> String {
+    return format!("> {} <", text);
+}
@@ -0,0 +1,283 @@
+use pongo::data_dir::{self, DataFile};
+use pongo::match_stats::MatchStats;
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// First line of the leaderboards file, followed by the version of its layout.
+const LEADERBOARDS_HEADER: &'static str = "pongo-leaderboards";
+const LEADERBOARDS_VERSION: u32 = 1;
+
+/// Number of entries kept on each board.
+pub const BOARD_SIZE: usize = 10;
+
+/// The records kept, each on its own board. A board's value indexes `BOARDS`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Board {
+    LongestRally = 0,   // Most paddle hits during a single point.
+    LongestPoint = 1    // Longest time the ball stayed in play during a single point.
+}
+
+pub const BOARDS: [Board; 2] = [Board::LongestRally, Board::LongestPoint];
+
+impl Board {
+
+    /// Name of the board in the leaderboards file.
+    fn key(&self) -> &'static str {
+        return match *self {
+            Board::LongestRally => "longest-rally",
+            Board::LongestPoint => "longest-point"
+        };
+    }
+
+    pub fn title(&self) -> &'static str {
+        return match *self {
+            Board::LongestRally => "LONGEST RALLY",
+            Board::LongestPoint => "LONGEST POINT"
+        };
+    }
+
+    /// The score a match reached on this board. Higher is better.
+    pub fn score(&self, stats: &MatchStats) -> u64 {
+        return match *self {
+            Board::LongestRally => stats.longest_rally as u64,
+            Board::LongestPoint => stats.longest_point_ms
+        };
+    }
+
+    pub fn format_score(&self, score: u64) -> String {
+        return match *self {
+            Board::LongestRally => format!("{} hits", score),
+            Board::LongestPoint => format!("{}.{} seconds", score / 1000, score / 100 % 10)
+        };
+    }
+
+}
+
+/// A record on one of the boards.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub score: u64,
+    pub recorded_at: u64    // Seconds since the Unix epoch.
+}
+
+/// The local high-score tables, saved in the data directory. Each board is sorted from best to
+/// worst. Of equal scores, the oldest comes first.
+pub struct Leaderboards {
+    boards: [Vec<Entry>; 2],
+    file: DataFile
+}
+
+impl Leaderboards {
+
+    /// The leaderboards file in the data directory.
+    pub fn default_path() -> PathBuf {
+        return data_dir::data_dir().join("leaderboards.txt");
+    }
+
+    /// Read the leaderboards. A missing file means no records have been set yet.
+    pub fn load(path: &Path) -> io::Result<Leaderboards> {
+        let mut leaderboards = Leaderboards {
+            boards: [Vec::new(), Vec::new()],
+            file: DataFile::new(path, LEADERBOARDS_HEADER, LEADERBOARDS_VERSION)
+        };
+        let lines = match try!(leaderboards.file.read_lines()) {
+            Some(lines) => lines,
+            None => return Ok(leaderboards)
+        };
+        for line in lines {
+            if let Some((board, entry)) = decode_entry(&line) {
+                leaderboards.boards[board as usize].push(entry);
+            }
+        }
+        // The file lists each board in order, but a board edited by hand may not be. Sorting
+        // is stable, so equal scores keep their order.
+        for board in leaderboards.boards.iter_mut() {
+            board.sort_by(|a, b| b.score.cmp(&a.score));
+            board.truncate(BOARD_SIZE);
+        }
+        return Ok(leaderboards);
+    }
+
+    /// Read the leaderboards from the data directory. Problems are reported on stderr, and the
+    /// game carries on with empty boards that are not saved.
+    pub fn load_or_default() -> Leaderboards {
+        let path = Leaderboards::default_path();
+        return match Leaderboards::load(&path) {
+            Ok(leaderboards) => leaderboards,
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(), "Unable to load leaderboards from {}: {}",
+                                 path.display(), e);
+                Leaderboards {
+                    boards: [Vec::new(), Vec::new()],
+                    file: DataFile::new(&path, LEADERBOARDS_HEADER, LEADERBOARDS_VERSION)
+                        .read_only()
+                }
+            }
+        };
+    }
+
+    /// Write the leaderboards to disk.
+    pub fn save(&self) -> io::Result<()> {
+        let mut lines = Vec::new();
+        for board in BOARDS.iter() {
+            for entry in self.entries(*board).iter() {
+                lines.push(format!("{}\t{}\t{}\t{}",
+                                   board.key(), entry.score, entry.recorded_at, entry.name));
+            }
+        }
+        return self.file.write_lines(&lines);
+    }
+
+    /// Save, reporting any problem on stderr.
+    pub fn save_or_report(&self) {
+        if let Err(e) = self.save() {
+            let _ = writeln!(&mut io::stderr(), "Unable to save leaderboards to {}: {}",
+                             self.file.path().display(), e);
+        }
+    }
+
+    pub fn entries(&self, board: Board) -> &[Entry] {
+        return &self.boards[board as usize];
+    }
+
+    /// The place, counting from 0, a score would take on a board, or None if it does not make
+    /// the board.
+    pub fn rank(&self, board: Board, score: u64) -> Option<usize> {
+        if score == 0 {
+            return None;
+        }
+        let entries = self.entries(board);
+        let rank = entries.iter().position(|e| score > e.score).unwrap_or(entries.len());
+        return if rank < BOARD_SIZE {Some(rank)} else {None};
+    }
+
+    /// Put a score on a board, pushing the lowest entry off if the board is full. Returns the
+    /// place it took, or None if it does not make the board.
+    pub fn insert(&mut self, board: Board, name: &str, score: u64) -> Option<usize> {
+        let rank = try_opt!(self.rank(board, score));
+        let recorded_at = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let entry = Entry { name: name.to_string(), score: score, recorded_at: recorded_at };
+        let entries = &mut self.boards[board as usize];
+        entries.insert(rank, entry);
+        entries.truncate(BOARD_SIZE);
+        return Some(rank);
+    }
+
+}
+
+fn decode_entry(line: &str) -> Option<(Board, Entry)> {
+    let fields: Vec<&str> = line.splitn(4, '\t').collect();
+    if fields.len() != 4 {
+        return None;
+    }
+    let board = try_opt!(BOARDS.iter().find(|board| board.key() == fields[0]));
+    let entry = Entry {
+        name: fields[3].to_string(),
+        score: try_opt!(fields[1].parse().ok()),
+        recorded_at: try_opt!(fields[2].parse().ok())
+    };
+    return Some((*board, entry));
+}
+
+/// Describe a place on a board, such as 1st or 12th.
+pub fn ordinal(rank: usize) -> String {
+    let place = rank + 1;
+    let suffix = match (place % 10, place % 100) {
+        (_, 11) | (_, 12) | (_, 13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th"
+    };
+    return format!("{}{}", place, suffix);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{decode_entry, ordinal, Board, Entry, Leaderboards, BOARD_SIZE};
+
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    fn entry(name: &str, score: u64, recorded_at: u64) -> Entry {
+        return Entry { name: name.to_string(), score: score, recorded_at: recorded_at };
+    }
+
+    #[test]
+    fn entries_are_decoded_onto_their_board() {
+        assert_eq!(decode_entry("longest-rally\t31\t1500000000\tZoë"),
+                   Some((Board::LongestRally, entry("Zoë", 31, 1500000000))));
+        assert_eq!(decode_entry("longest-point\t12345\t1500000001\tPlayer 2"),
+                   Some((Board::LongestPoint, entry("Player 2", 12345, 1500000001))));
+    }
+
+    #[test]
+    fn names_keep_their_tabs() {
+        assert_eq!(decode_entry("longest-rally\t31\t1500000000\tZo\të"),
+                   Some((Board::LongestRally, entry("Zo\të", 31, 1500000000))));
+    }
+
+    #[test]
+    fn malformed_entries_are_skipped() {
+        assert_eq!(decode_entry("most-aces\t31\t1500000000\tZoë"), None);
+        assert_eq!(decode_entry("longest-rally\t-31\t1500000000\tZoë"), None);
+        assert_eq!(decode_entry("longest-rally\t31\tyesterday\tZoë"), None);
+        assert_eq!(decode_entry("longest-rally\t31\t1500000000"), None);
+        assert_eq!(decode_entry(""), None);
+    }
+
+    #[test]
+    fn boards_survive_a_round_trip() {
+        let path = env::temp_dir().join("pongo-test-leaderboards.txt");
+        let _ = fs::remove_file(&path);
+        let mut leaderboards = Leaderboards::load(&path).unwrap();
+        assert_eq!(leaderboards.insert(Board::LongestRally, "Ada", 12), Some(0));
+        assert_eq!(leaderboards.insert(Board::LongestRally, "Zoë", 31), Some(0));
+        assert_eq!(leaderboards.insert(Board::LongestPoint, "Ada", 8200), Some(0));
+        leaderboards.save().unwrap();
+
+        let loaded = Leaderboards::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.entries(Board::LongestRally),
+                   leaderboards.entries(Board::LongestRally));
+        assert_eq!(loaded.entries(Board::LongestPoint),
+                   leaderboards.entries(Board::LongestPoint));
+    }
+
+    #[test]
+    fn boards_edited_by_hand_are_sorted_and_trimmed() {
+        let path = env::temp_dir().join("pongo-test-leaderboards-edited.txt");
+        {
+            let mut file = File::create(&path).unwrap();
+            writeln!(file, "pongo-leaderboards 1").unwrap();
+            for score in 1..BOARD_SIZE as u64 + 3 {
+                writeln!(file, "longest-rally\t{}\t{}\tPlayer {}", score, score, score).unwrap();
+            }
+            writeln!(file, "longest-rally\t5\t0\tFirst to five").unwrap();
+        }
+        let loaded = Leaderboards::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        let scores: Vec<u64> = loaded.entries(Board::LongestRally).iter()
+            .map(|e| e.score)
+            .collect();
+        assert_eq!(scores, vec![12, 11, 10, 9, 8, 7, 6, 5, 5, 4]);
+        assert_eq!(loaded.entries(Board::LongestRally)[7].name, "Player 5");
+    }
+
+    #[test]
+    fn places_are_written_as_ordinals() {
+        let places: Vec<String> = [0, 1, 2, 3, 10, 11, 12, 20, 21, 100, 110].iter()
+            .map(|&rank| ordinal(rank))
+            .collect();
+        assert_eq!(places, vec!["1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st",
+                                "22nd", "101st", "111th"]);
+    }
+
+}
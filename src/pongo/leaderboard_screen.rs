@@ -0,0 +1,129 @@
+use pongo::layout::{Align, Bounds, Element};
+use pongo::leaderboard::{self, Board, Leaderboards, BOARDS};
+use pongo::profile::MAX_NAME_LEN;
+use pongo::text_entry::{TextEntry, TextEntryStatus};
+use pongo::ui::{ScalableFont, Ui};
+
+use std::rc::Rc;
+use std::thread;
+
+/// Space between the entries of a board.
+const ROW_SPACING: f32 = 6.;
+
+/// Tell the player they set a record and have them type the name it goes on the board under,
+/// arcade style. Starts out with `default_name`. Returns None if the player skips it with
+/// escape or closes the window.
+pub fn enter_name(ui: &mut Ui,
+                  board: Board,
+                  score: u64,
+                  rank: usize,
+                  default_name: &str) -> Option<String> {
+
+    let title_font = Rc::new(ScalableFont::new(&ui.theme.heading_font, 60));
+    let name_font = Rc::new(ScalableFont::new(&ui.theme.title_font, 48));
+    let text_font = Rc::new(ScalableFont::new(&ui.theme.text_font, 22));
+    let title_color = ui.theme.highlight;
+    let name_color = ui.theme.positive;
+    let text_color = ui.theme.text;
+    let prompt_color = ui.theme.heading;
+
+    let record = format!("{} on the {} board with {}",
+                         leaderboard::ordinal(rank), board.title().to_lowercase(),
+                         board.format_score(score));
+    let mut entry = TextEntry::new(default_name, MAX_NAME_LEN);
+    TextEntry::start(ui);
+
+    loop {
+        while let Some(event) = ui.poll_event() {
+            match entry.handle_event(&event) {
+                TextEntryStatus::Editing => {},
+                TextEntryStatus::Done => {
+                    TextEntry::stop(ui);
+                    return Some(entry.text.trim().to_string());
+                },
+                TextEntryStatus::Cancelled => {
+                    TextEntry::stop(ui);
+                    return None;
+                }
+            }
+        }
+
+        let screen = Element::column(Align::Center, 0., vec![
+            Element::label("NEW RECORD!", &title_font, title_color),
+            Element::space(0., 50.),
+            Element::label(&record, &text_font, text_color),
+            Element::space(0., 50.),
+            Element::label("Enter your name", &text_font, text_color),
+            Element::space(0., 20.),
+            Element::label(&entry.display_text(), &name_font, name_color)
+        ]);
+        let prompt = Element::label("ENTER to save, ESCAPE to skip", &text_font, prompt_color);
+
+        let background = ui.theme.background;
+        ui.renderer.set_draw_color(background);
+        ui.renderer.clear();
+        let area = Bounds::new(0., 60., ui.width, 0.);
+        screen.draw_in(ui, area, Align::Center, Align::Start);
+        let bottom = Bounds::new(0., 0., ui.width, ui.height - 50.);
+        prompt.draw_in(ui, bottom, Align::Center, Align::End);
+        ui.renderer.present();
+
+        thread::sleep_ms(50);
+    }
+}
+
+/// Show every board side by side until the player presses a key. The entry at `highlight`,
+/// if any, stands out, for instance because it was just added.
+pub fn show(ui: &mut Ui,
+            leaderboards: &Leaderboards,
+            highlight: Option<(Board, usize)>) {
+
+    let title_font = Rc::new(ScalableFont::new(&ui.theme.heading_font, 39));
+    let text_font = Rc::new(ScalableFont::new(&ui.theme.text_font, 20));
+    let title_color = ui.theme.heading;
+    let heading_color = ui.theme.accent;
+    let text_color = ui.theme.text;
+    let highlight_color = ui.theme.highlight;
+
+    // Each board is a table of ranks, names and scores, in columns so the entries line up.
+    let mut boards = Vec::new();
+    for board in BOARDS.iter() {
+        let entries = leaderboards.entries(*board);
+        let table = if entries.is_empty() {
+            Element::label("No records yet", &text_font, text_color)
+        } else {
+            let (mut ranks, mut names, mut scores) = (Vec::new(), Vec::new(), Vec::new());
+            for (rank, entry) in entries.iter().enumerate() {
+                let highlighted = highlight == Some((*board, rank));
+                let color = if highlighted {highlight_color} else {text_color};
+                ranks.push(Element::label(&format!("{}.", rank + 1), &text_font, color));
+                names.push(Element::label(&entry.name, &text_font, color));
+                scores.push(Element::label(&board.format_score(entry.score), &text_font, color));
+            }
+            Element::row(Align::Start, 16., vec![
+                Element::column(Align::End, ROW_SPACING, ranks),
+                Element::column(Align::Start, ROW_SPACING, names),
+                Element::column(Align::End, ROW_SPACING, scores)
+            ])
+        };
+        boards.push(Element::column(Align::Start, 16., vec![
+            Element::label(board.title(), &text_font, heading_color),
+            table
+        ]));
+    }
+    let screen = Element::column(Align::Center, 40., vec![
+        Element::label("HIGH SCORES", &title_font, title_color),
+        Element::row(Align::Start, 80., boards)
+    ]);
+
+    let background = ui.theme.background;
+    ui.renderer.set_draw_color(background);
+    ui.renderer.clear();
+    let area = Bounds::new(0., 40., ui.width, 0.);
+    screen.draw_in(ui, area, Align::Center, Align::Start);
+    let prompt = Element::label("Press any key to continue", &text_font, title_color);
+    let bottom = Bounds::new(0., 0., ui.width, ui.height - 50.);
+    prompt.draw_in(ui, bottom, Align::Center, Align::End);
+    ui.renderer.present();
+    ui.wait_for_dismissal();
+}
@@ -0,0 +1,752 @@
+extern crate clock_ticks;
+
+use pongo::lan::{DiscoveredHost, Endpoint, HostAnnouncer, HostBrowser, LanPeer, Message};
+use pongo::lan::PEER_TIMEOUT_MS;
+use pongo::layout::{Align, Bounds, Element};
+use pongo::match_rules::MatchRules;
+use pongo::relay_protocol::{CODE_LEN, DEFAULT_RELAY_ADDR, OpenMatch, RelayMessage};
+use pongo::ui::{ScalableFont, Ui};
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+
+use std::env;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::rc::Rc;
+use std::thread;
+
+/// Fonts and colors shared by the lobby screens, taken from the theme.
+struct LobbyStyle {
+    heading_font: Rc<ScalableFont>,
+    text_font: Rc<ScalableFont>,
+    background_color: Color,
+    heading_color: Color,
+    text_color: Color,
+    highlight_color: Color,
+    ready_color: Color
+}
+
+impl LobbyStyle {
+
+    fn new(ui: &Ui) -> LobbyStyle {
+        let theme = &ui.theme;
+        return LobbyStyle {
+            heading_font: Rc::new(ScalableFont::new(&theme.heading_font, 39)),
+            text_font: Rc::new(ScalableFont::new(&theme.text_font, 22)),
+            background_color: theme.background,
+            heading_color: theme.heading,
+            text_color: theme.text,
+            highlight_color: theme.highlight,
+            ready_color: theme.positive
+        };
+    }
+
+    fn label(&self, text: &str, color: Color) -> Element {
+        return Element::label(text, &self.text_font, color);
+    }
+
+    /// The ready state of a player for the ready-check.
+    fn ready_label(&self, name: &str, ready: bool) -> Element {
+        let text = format!("{}: {}", name, if ready {"READY"} else {"not ready"});
+        return self.label(&text, if ready {self.ready_color} else {self.text_color});
+    }
+
+    /// A list of games to pick from, with the selected one highlighted, or `empty` if there are
+    /// none.
+    fn choices(&self, lines: &[String], selected: usize, empty: &str) -> Vec<Element> {
+        if lines.is_empty() {
+            return vec![self.label(empty, self.text_color)];
+        }
+        return lines.iter().enumerate().map(|(i, line)| {
+            self.label(line, if i == selected {self.highlight_color} else {self.text_color})
+        }).collect();
+    }
+
+}
+
+/// Draw a lobby screen: the heading at the top with what the screen holds below it, and the
+/// footer, if any, at the bottom.
+fn draw_screen(ui: &mut Ui, style: &LobbyStyle, heading: &str, body: Vec<Element>,
+               footer: Vec<Element>) {
+    let screen = Element::column(Align::Center, 0., vec![
+        Element::label(heading, &style.heading_font, style.heading_color),
+        Element::space(0., 40.),
+        Element::column(Align::Center, 14., body)
+    ]);
+    let footer = Element::column(Align::Center, 24., footer);
+
+    ui.renderer.set_draw_color(style.background_color);
+    ui.renderer.clear();
+    let area = Bounds::new(0., 80., ui.width, 0.);
+    screen.draw_in(ui, area, Align::Center, Align::Start);
+    let bottom = Bounds::new(0., 0., ui.width, ui.height - 50.);
+    footer.draw_in(ui, bottom, Align::Center, Align::End);
+    ui.renderer.present();
+}
+
+/// Describe match rules received through the relay, which passes them along as they are.
+fn describe_rules(rules: &str) -> String {
+    return match rules.parse::<MatchRules>() {
+        Ok(rules) => rules.describe(),
+        Err(_) => "unknown rules".to_string()
+    };
+}
+
+/// Host a LAN game. The game is announced on the LAN until a player joins and both players
+/// pass the ready-check. Returns the joined player, or None if the host backs out with escape.
+pub fn host(ui: &mut Ui, host_name: &str, rules: MatchRules) -> Option<LanPeer> {
+    let style = LobbyStyle::new(ui);
+    let endpoint = match Endpoint::bind(0) {
+        Ok(endpoint) => endpoint,
+        Err(e) => {
+            show_error(ui, &style, &format!("Unable to host: {}", e));
+            return None;
+        }
+    };
+    let mut announcer = HostAnnouncer::new(host_name, rules);
+    let mut guest: Option<(SocketAddr, String)> = Option::None;
+    let mut guest_last_heard_ms = 0;
+    let mut host_ready = false;
+    let mut guest_ready = false;
+    let mut time_last_ready_sent_ms = 0;
+
+    loop {
+        let now_ms = clock_ticks::precise_time_ms();
+
+        // Keep announcing until the match starts.
+        announcer.announce_if_due(&endpoint);
+
+        while let Some((message, addr)) = endpoint.recv() {
+            let from_guest = guest.as_ref().map_or(false, |&(guest_addr, _)| guest_addr == addr);
+            match message {
+                Message::Join { name } => {
+                    if guest.is_none() || from_guest {
+                        endpoint.send(&Message::Welcome { name: host_name.to_string() }, addr);
+                        endpoint.send(&Message::Ready(host_ready), addr);
+                        guest = Some((addr, name));
+                        guest_last_heard_ms = now_ms;
+                    } else {
+                        endpoint.send(&Message::Full, addr);
+                    }
+                },
+                Message::Ready(ready) if from_guest => {
+                    guest_ready = ready;
+                    guest_last_heard_ms = now_ms;
+                },
+                Message::Leave if from_guest => {
+                    guest = None;
+                    guest_ready = false;
+                },
+                _ => {}
+            }
+        }
+
+        // Drop a guest that has gone quiet. Guests repeat their ready state every second.
+        if guest.is_some() && now_ms - guest_last_heard_ms > PEER_TIMEOUT_MS {
+            guest = None;
+            guest_ready = false;
+        }
+
+        while let Some(event) = ui.poll_event() {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    if let Some((addr, _)) = guest {
+                        endpoint.send(&Message::Leave, addr);
+                    }
+                    return None;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                    host_ready = !host_ready;
+                    time_last_ready_sent_ms = 0;
+                },
+                _ => {}
+            }
+        }
+
+        if let Some((addr, ref name)) = guest {
+
+            // Both players are ready. Start is sent a few times since datagrams can be lost, and
+            // should all of them be, the guest starts on the first state of the match instead.
+            if host_ready && guest_ready {
+                for _ in 0..3 {
+                    endpoint.send(&Message::Start, addr);
+                }
+                return Some(LanPeer::new(endpoint, addr, name.clone()));
+            }
+
+            if now_ms - time_last_ready_sent_ms >= 1000 {
+                endpoint.send(&Message::Ready(host_ready), addr);
+                time_last_ready_sent_ms = now_ms;
+            }
+        }
+
+        // Draw the lobby.
+        let description = format!("{}. Port {}.", rules.describe(), endpoint.local_port());
+        let guest_line = match guest {
+            Some((_, ref name)) => style.ready_label(name, guest_ready),
+            None => style.label("Waiting for a player to join...", style.text_color)
+        };
+        draw_screen(ui, &style, "HOSTING LAN GAME", vec![
+            style.label(&description, style.text_color),
+            Element::space(0., 60.),
+            style.ready_label(host_name, host_ready),
+            guest_line
+        ], vec![style.label("SPACE to toggle ready, ESCAPE to leave", style.highlight_color)]);
+
+        thread::sleep_ms(50);
+    }
+}
+
+/// Where the joining player is in the process of getting into a match.
+enum JoinState {
+    Browsing,
+    Joining(DiscoveredHost, u64),       // Join sent to the host at the given time.
+    Joined(DiscoveredHost, bool, bool)  // Ready state of the guest and host.
+}
+
+/// Browse the LAN for hosted games, join one and pass the ready-check. Returns the host and
+/// the host's match rules, or None if the player backs out with escape.
+pub fn join(ui: &mut Ui, player_name: &str) -> Option<(LanPeer, MatchRules)> {
+    let style = LobbyStyle::new(ui);
+    let mut browser = match HostBrowser::new(false) {
+        Ok(browser) => browser,
+        Err(e) => {
+            show_error(ui, &style, &format!("Unable to search for games: {}", e));
+            return None;
+        }
+    };
+    let endpoint = match Endpoint::bind(0) {
+        Ok(endpoint) => endpoint,
+        Err(e) => {
+            show_error(ui, &style, &format!("Unable to join: {}", e));
+            return None;
+        }
+    };
+    let mut selected = 0;
+    let mut state = JoinState::Browsing;
+    let mut status: Option<String> = Option::None;
+    let mut time_last_ready_sent_ms = 0;
+
+    loop {
+        let now_ms = clock_ticks::precise_time_ms();
+        browser.refresh();
+        if selected >= browser.hosts.len() && selected > 0 {
+            selected = browser.hosts.len().saturating_sub(1);
+        }
+
+        // Handle replies from the host we are joining. Anything from elsewhere is ignored.
+        while let Some((message, addr)) = endpoint.recv() {
+            let host_addr = match state {
+                JoinState::Joining(ref host, _) | JoinState::Joined(ref host, _, _) => {
+                    Some(host.addr)
+                },
+                JoinState::Browsing => None
+            };
+            if host_addr != Some(addr) {
+                continue;
+            }
+            state = match (state, message) {
+                (JoinState::Joining(mut host, _), Message::Welcome { name }) => {
+                    host.host_name = name;
+                    status = None;
+                    JoinState::Joined(host, false, false)
+                },
+                (JoinState::Joining(host, _), Message::Full) => {
+                    status = Some(format!("{}'s game is full.", host.host_name));
+                    JoinState::Browsing
+                },
+                (JoinState::Joined(host, ready, _), Message::Ready(host_ready)) => {
+                    JoinState::Joined(host, ready, host_ready)
+                },
+                // The state of the match only comes once the host has started it, so it starts
+                // the match as well, in case every Start was lost.
+                (JoinState::Joined(host, _, _), Message::Start) |
+                (JoinState::Joined(host, _, _), Message::State(_)) => {
+                    let rules = host.rules;
+                    let peer = LanPeer::new(endpoint, addr, host.host_name);
+                    return Some((peer, rules));
+                },
+                (JoinState::Joined(host, _, _), Message::Leave) => {
+                    status = Some(format!("{} left the game.", host.host_name));
+                    JoinState::Browsing
+                },
+                (state, _) => state
+            };
+        }
+
+        while let Some(event) = ui.poll_event() {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    match state {
+                        JoinState::Joining(ref host, _) | JoinState::Joined(ref host, _, _) => {
+                            endpoint.send(&Message::Leave, host.addr);
+                        },
+                        JoinState::Browsing => {}
+                    }
+                    return None;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                    selected = selected.saturating_sub(1);
+                },
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                    if selected + 1 < browser.hosts.len() {
+                        selected += 1;
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                    if let JoinState::Browsing = state {
+                        if let Some(host) = browser.hosts.get(selected) {
+                            state = JoinState::Joining(host.clone(), 0);
+                        }
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                    state = match state {
+                        JoinState::Joined(host, ready, host_ready) => {
+                            time_last_ready_sent_ms = 0;
+                            JoinState::Joined(host, !ready, host_ready)
+                        },
+                        state => state
+                    };
+                },
+                _ => {}
+            }
+        }
+
+        // Repeat join requests and ready state every second, since datagrams can be lost and
+        // the host drops guests it has not heard from in a while.
+        state = match state {
+            JoinState::Joining(host, time_join_sent_ms) => {
+                if now_ms - time_join_sent_ms >= 1000 {
+                    endpoint.send(&Message::Join { name: player_name.to_string() }, host.addr);
+                    JoinState::Joining(host, now_ms)
+                } else {
+                    JoinState::Joining(host, time_join_sent_ms)
+                }
+            },
+            JoinState::Joined(host, ready, host_ready) => {
+                if now_ms - time_last_ready_sent_ms >= 1000 {
+                    endpoint.send(&Message::Ready(ready), host.addr);
+                    time_last_ready_sent_ms = now_ms;
+                }
+                JoinState::Joined(host, ready, host_ready)
+            },
+            JoinState::Browsing => JoinState::Browsing
+        };
+
+        // Draw the lobby.
+        match state {
+            JoinState::Browsing => {
+                let lines: Vec<String> = browser.hosts.iter().map(|host| {
+                    format!("{}  -  {}  -  {}", host.host_name, host.rules.describe(), host.addr)
+                }).collect();
+                let mut footer = Vec::new();
+                if let Some(ref status) = status {
+                    footer.push(style.label(status, style.heading_color));
+                }
+                footer.push(style.label("ENTER to join, ESCAPE to leave", style.highlight_color));
+                let games = style.choices(&lines, selected, "Searching for games...");
+                draw_screen(ui, &style, "LAN GAMES", games, footer);
+            },
+            JoinState::Joining(ref host, _) => {
+                let line = format!("Contacting {}...", host.host_name);
+                draw_screen(ui, &style, "JOINING", vec![style.label(&line, style.text_color)],
+                            Vec::new());
+            },
+            JoinState::Joined(ref host, ready, host_ready) => {
+                let description = format!("{}.", host.rules.describe());
+                draw_screen(ui, &style, "READY CHECK", vec![
+                    style.label(&description, style.text_color),
+                    Element::space(0., 60.),
+                    style.ready_label(&host.host_name, host_ready),
+                    style.ready_label(player_name, ready)
+                ], vec![style.label("SPACE to toggle ready, ESCAPE to leave",
+                                    style.highlight_color)]);
+            }
+        }
+
+        thread::sleep_ms(50);
+    }
+}
+
+/// Browse the LAN for matches in progress and pick one to watch. Returns the host of the
+/// chosen match, or None if the player backs out with escape.
+pub fn watch(ui: &mut Ui) -> Option<LanPeer> {
+    let style = LobbyStyle::new(ui);
+    let mut browser = match HostBrowser::new(true) {
+        Ok(browser) => browser,
+        Err(e) => {
+            show_error(ui, &style, &format!("Unable to search for matches: {}", e));
+            return None;
+        }
+    };
+    let mut selected = 0;
+
+    loop {
+        browser.refresh();
+        if selected >= browser.hosts.len() && selected > 0 {
+            selected = browser.hosts.len() - 1;
+        }
+
+        while let Some(event) = ui.poll_event() {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    return None;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                    selected = selected.saturating_sub(1);
+                },
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                    if selected + 1 < browser.hosts.len() {
+                        selected += 1;
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                    if let Some(host) = browser.hosts.get(selected) {
+                        let endpoint = match Endpoint::bind(0) {
+                            Ok(endpoint) => endpoint,
+                            Err(e) => {
+                                show_error(ui, &style, &format!("Unable to watch: {}", e));
+                                return None;
+                            }
+                        };
+                        let peer = LanPeer::new(endpoint, host.addr, host.host_name.clone());
+                        peer.send(&Message::Watch);
+                        return Some(peer);
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let lines: Vec<String> = browser.hosts.iter().map(|host| {
+            format!("{}'s match  -  {}", host.host_name, host.rules.describe())
+        }).collect();
+        let matches = style.choices(&lines, selected, "Searching for matches...");
+        draw_screen(ui, &style, "WATCH A MATCH", matches,
+                    vec![style.label("ENTER to watch, ESCAPE to leave", style.highlight_color)]);
+
+        thread::sleep_ms(50);
+    }
+}
+
+/// Where the player is in the process of getting into a match through the relay server.
+enum OnlineState {
+    Registering,
+    Browsing(Vec<OpenMatch>),
+    Hosting(Option<String>),    // The match code, once the relay has handed one out.
+    Joining(String)
+}
+
+/// The relay server to use. Set PONGO_RELAY to reach a relay on another machine.
+fn relay_addr() -> Option<SocketAddr> {
+    let addr = env::var("PONGO_RELAY").unwrap_or(DEFAULT_RELAY_ADDR.to_string());
+    return addr.to_socket_addrs().ok().and_then(|mut addrs| addrs.next());
+}
+
+/// Find an opponent through the relay server: host a match and wait for someone to join it by
+/// its code, or join one from the list of open matches, either by picking it or by typing its
+/// code. Then pass the ready-check. Returns the opponent, the host's match rules and whether
+/// we are the host, or None if the player backs out with escape.
+pub fn online(ui: &mut Ui, player_name: &str, rules: MatchRules)
+              -> Option<(LanPeer, MatchRules, bool)> {
+    let style = LobbyStyle::new(ui);
+    let relay_addr = match relay_addr() {
+        Some(relay_addr) => relay_addr,
+        None => {
+            show_error(ui, &style, "Unable to find the relay server.");
+            return None;
+        }
+    };
+    let endpoint = match Endpoint::bind(0) {
+        Ok(endpoint) => endpoint,
+        Err(e) => {
+            show_error(ui, &style, &format!("Unable to go online: {}", e));
+            return None;
+        }
+    };
+    let mut state = OnlineState::Registering;
+    let mut selected = 0;
+    let mut typed_code = String::new();
+    let mut status: Option<String> = Option::None;
+    let mut time_last_request_ms = 0;
+    let time_started_ms = clock_ticks::precise_time_ms();
+
+    loop {
+        let now_ms = clock_ticks::precise_time_ms();
+
+        // Handle replies from the relay.
+        while let Some((line, addr)) = endpoint.recv_line() {
+            if addr != relay_addr {
+                continue;
+            }
+            let message = match RelayMessage::decode(&line) {
+                Some(message) => message,
+                None => continue
+            };
+            state = match (state, message) {
+                (OnlineState::Registering, RelayMessage::Registered { .. }) => {
+                    time_last_request_ms = 0;
+                    OnlineState::Browsing(Vec::new())
+                },
+                (OnlineState::Browsing(_), RelayMessage::Matches(matches)) => {
+                    OnlineState::Browsing(matches)
+                },
+                (OnlineState::Hosting(_), RelayMessage::Hosting { code }) => {
+                    OnlineState::Hosting(Some(code))
+                },
+                (OnlineState::Hosting(_), RelayMessage::Paired { guest_name }) => {
+                    let mut peer = LanPeer::relayed(endpoint, relay_addr, guest_name);
+                    if ready_check(ui, &style, &mut peer, player_name, true, &rules) {
+                        return Some((peer, rules, true));
+                    }
+                    return None;
+                },
+                (OnlineState::Joining(code),
+                 RelayMessage::Joined { rules: host_rules, host_name, .. }) => {
+                    // A host running another version of the game may have rules we do not
+                    // understand.
+                    let host_rules: MatchRules = match host_rules.parse() {
+                        Ok(host_rules) => host_rules,
+                        Err(_) => {
+                            endpoint.send_line(&RelayMessage::Bye.encode(), relay_addr);
+                            show_error(ui, &style, &format!("Match {} uses rules this game does \
+                                                             not know.", code));
+                            return None;
+                        }
+                    };
+                    let mut peer = LanPeer::relayed(endpoint, relay_addr, host_name);
+                    if ready_check(ui, &style, &mut peer, player_name, false, &host_rules) {
+                        return Some((peer, host_rules, false));
+                    }
+                    return None;
+                },
+                (OnlineState::Joining(code), RelayMessage::NoMatch { .. }) => {
+                    status = Some(format!("There is no open match with code {}.", code));
+                    time_last_request_ms = 0;
+                    OnlineState::Browsing(Vec::new())
+                },
+                (state, _) => state
+            };
+        }
+
+        while let Some(event) = ui.poll_event() {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    endpoint.send_line(&RelayMessage::Bye.encode(), relay_addr);
+                    return None;
+                },
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    state = match (state, keycode) {
+                        (OnlineState::Browsing(matches), Keycode::Up) => {
+                            selected = selected.saturating_sub(1);
+                            OnlineState::Browsing(matches)
+                        },
+                        (OnlineState::Browsing(matches), Keycode::Down) => {
+                            if selected + 1 < matches.len() {
+                                selected += 1;
+                            }
+                            OnlineState::Browsing(matches)
+                        },
+                        (OnlineState::Browsing(matches), Keycode::Return) => {
+                            match matches.get(selected).map(|m| m.code.clone()) {
+                                Some(code) => {
+                                    time_last_request_ms = 0;
+                                    OnlineState::Joining(code)
+                                },
+                                None => OnlineState::Browsing(matches)
+                            }
+                        },
+                        (OnlineState::Browsing(matches), Keycode::Backspace) => {
+                            typed_code.pop();
+                            OnlineState::Browsing(matches)
+                        },
+                        (OnlineState::Browsing(matches), keycode) => {
+                            // Letters make up a match code, so hosting uses F1 instead.
+                            let name = keycode.name();
+                            if keycode == Keycode::F1 {
+                                time_last_request_ms = 0;
+                                OnlineState::Hosting(None)
+                            } else if name.len() == 1 && name.chars().all(|c| c.is_alphabetic()) {
+                                typed_code.push_str(&name.to_uppercase());
+                                if typed_code.len() == CODE_LEN {
+                                    time_last_request_ms = 0;
+                                    let code = typed_code.clone();
+                                    typed_code.clear();
+                                    OnlineState::Joining(code)
+                                } else {
+                                    OnlineState::Browsing(matches)
+                                }
+                            } else {
+                                OnlineState::Browsing(matches)
+                            }
+                        },
+                        (state, _) => state
+                    };
+                },
+                _ => {}
+            }
+        }
+
+        // Requests to the relay are repeated until answered, since datagrams can be lost. The
+        // list of open matches is refreshed every couple of seconds, which also lets the relay
+        // know we are still around.
+        let request = match state {
+            OnlineState::Registering => {
+                if now_ms - time_started_ms > 5000 {
+                    show_error(ui, &style, &format!("No answer from the relay at {}.", relay_addr));
+                    return None;
+                }
+                Some((RelayMessage::Register { name: player_name.to_string() }, 1000))
+            },
+            OnlineState::Browsing(_) => Some((RelayMessage::List, 2000)),
+            OnlineState::Hosting(_) => {
+                Some((RelayMessage::Host { rules: rules.to_string() }, 1000))
+            },
+            OnlineState::Joining(ref code) => {
+                Some((RelayMessage::Join { code: code.clone() }, 1000))
+            }
+        };
+        if let Some((request, interval_ms)) = request {
+            if now_ms - time_last_request_ms >= interval_ms {
+                endpoint.send_line(&request.encode(), relay_addr);
+                time_last_request_ms = now_ms;
+            }
+        }
+
+        // Draw the lobby.
+        match state {
+            OnlineState::Registering => {
+                let line = format!("Contacting {}...", relay_addr);
+                draw_screen(ui, &style, "ONLINE", vec![style.label(&line, style.text_color)],
+                            Vec::new());
+            },
+            OnlineState::Browsing(ref matches) => {
+                let lines: Vec<String> = matches.iter().map(|m| {
+                    format!("{}  -  {}  -  {}", m.code, m.host_name, describe_rules(&m.rules))
+                }).collect();
+                let mut footer = Vec::new();
+                if !typed_code.is_empty() {
+                    let line = format!("Code: {}", typed_code);
+                    footer.push(style.label(&line, style.highlight_color));
+                }
+                if let Some(ref status) = status {
+                    footer.push(style.label(status, style.heading_color));
+                }
+                footer.push(style.label("ENTER to join, type a code to join by code, F1 to host",
+                                        style.highlight_color));
+                let open = style.choices(&lines, selected, "Nobody is hosting right now.");
+                draw_screen(ui, &style, "OPEN MATCHES", open, footer);
+            },
+            OnlineState::Hosting(ref code) => {
+                let line = match *code {
+                    Some(ref code) => format!("Your match code is {}", code),
+                    None => "Asking the relay for a match code...".to_string()
+                };
+                draw_screen(ui, &style, "HOSTING", vec![
+                    style.label(&line, style.text_color),
+                    style.label("Waiting for a player to join...", style.text_color)
+                ], Vec::new());
+            },
+            OnlineState::Joining(ref code) => {
+                let line = format!("Joining match {}...", code);
+                draw_screen(ui, &style, "JOINING", vec![style.label(&line, style.text_color)],
+                            Vec::new());
+            }
+        }
+
+        thread::sleep_ms(50);
+    }
+}
+
+/// Make sure both players are ready before the match starts. The host decides when the match
+/// starts. Returns false if either player backs out or the connection is lost.
+fn ready_check(ui: &mut Ui, style: &LobbyStyle, peer: &mut LanPeer, local_name: &str,
+               is_host: bool, rules: &MatchRules) -> bool {
+    let mut ready = false;
+    let mut peer_ready = false;
+    let mut time_last_ready_sent_ms = 0;
+
+    loop {
+        let now_ms = clock_ticks::precise_time_ms();
+
+        while let Some(message) = peer.recv() {
+            match message {
+                Message::Ready(r) => peer_ready = r,
+                // The state of the match only comes once the host has started it, so it starts
+                // the match as well, in case every Start was lost.
+                Message::Start | Message::State(_) if !is_host => return true,
+                Message::Leave => {
+                    show_error(ui, style, &format!("{} left the game.", peer.name));
+                    return false;
+                },
+                _ => {}
+            }
+        }
+        if peer.timed_out() {
+            show_error(ui, style, &format!("Lost contact with {}.", peer.name));
+            return false;
+        }
+
+        while let Some(event) = ui.poll_event() {
+            match event {
+                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    peer.leave();
+                    return false;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                    ready = !ready;
+                    time_last_ready_sent_ms = 0;
+                },
+                _ => {}
+            }
+        }
+
+        // Both players are ready. Start is sent a few times since datagrams can be lost, and
+        // should all of them be, the guest starts on the first state of the match instead.
+        if is_host && ready && peer_ready {
+            for _ in 0..3 {
+                peer.send(&Message::Start);
+            }
+            return true;
+        }
+
+        // Repeat our ready state every second, which also tells the peer we are still here.
+        if now_ms - time_last_ready_sent_ms >= 1000 {
+            peer.send(&Message::Ready(ready));
+            time_last_ready_sent_ms = now_ms;
+        }
+
+        let description = format!("{}.", rules.describe());
+        draw_screen(ui, style, "READY CHECK", vec![
+            style.label(&description, style.text_color),
+            Element::space(0., 60.),
+            style.ready_label(local_name, ready),
+            style.ready_label(&peer.name, peer_ready)
+        ], vec![style.label("SPACE to toggle ready, ESCAPE to leave", style.highlight_color)]);
+
+        thread::sleep_ms(50);
+    }
+}
+
+/// Show an error message until the player presses a key.
+fn show_error(ui: &mut Ui, style: &LobbyStyle, msg: &str) {
+    let message = Element::column(Align::Center, 28., vec![
+        style.label(msg, style.heading_color),
+        style.label("Press any key to continue", style.text_color)
+    ]);
+    ui.renderer.set_draw_color(style.background_color);
+    ui.renderer.clear();
+    let screen = Bounds::screen(ui);
+    message.draw_in(ui, screen, Align::Center, Align::Center);
+    ui.renderer.present();
+    loop {
+        thread::sleep_ms(50);
+        match ui.poll_event() {
+            Some(Event::Quit {..}) | Some(Event::KeyDown {..}) |
+                Some(Event::MouseButtonDown {..}) => return,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use pongo::checksum::{Checksummable, StateHasher};
+use pongo::match_stats::Side;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// How a match is won. Points make up games, games make up sets and the match goes to whoever
+/// wins most of the sets. With the default of a single game and a single set, the first to the
+/// points per game wins, as in classic Pong.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MatchRules {
+    pub points_per_game: u32,
+    pub win_by_two: bool,               // A game needs a lead of two points, deuce style.
+    pub games_per_set: u32,             // A set is best of this many games.
+    pub sets: u32,                      // The match is best of this many sets.
+    pub time_limit_secs: Option<u32>   // Once up, whoever is ahead wins. If nobody is, the
+                                        // next point does.
+}
+
+impl MatchRules {
+
+    /// A single game to the given number of points.
+    pub fn first_to(points: u32) -> MatchRules {
+        return MatchRules {
+            points_per_game: points,
+            win_by_two: false,
+            games_per_set: 1,
+            sets: 1,
+            time_limit_secs: None
+        };
+    }
+
+    /// Games needed to win a set.
+    pub fn games_to_win_set(&self) -> u32 {
+        return self.games_per_set / 2 + 1;
+    }
+
+    /// Sets needed to win the match.
+    pub fn sets_to_win_match(&self) -> u32 {
+        return self.sets / 2 + 1;
+    }
+
+    pub fn time_limit_ms(&self) -> Option<u64> {
+        return self.time_limit_secs.map(|secs| secs as u64 * 1000);
+    }
+
+    /// A game with this score is over, won by the side with `points`.
+    pub fn game_won(&self, points: u32, opponent_points: u32) -> bool {
+        return points >= self.points_per_game &&
+            (!self.win_by_two || points >= opponent_points + 2);
+    }
+
+    /// Describe the rules for players about to start a match.
+    pub fn describe(&self) -> String {
+        let mut description = format!("First to {} points", self.points_per_game);
+        if self.win_by_two {
+            description.push_str(", win by two");
+        }
+        if self.games_per_set > 1 {
+            description.push_str(&format!(", best of {} games", self.games_per_set));
+        }
+        if self.sets > 1 {
+            description.push_str(&format!(", best of {} sets", self.sets));
+        }
+        if let Some(secs) = self.time_limit_secs {
+            description.push_str(&format!(", {}:{:02} time limit", secs / 60, secs % 60));
+        }
+        return description;
+    }
+
+}
+
+/// The rules as a single word, for the network and replay files: points per game, win by two,
+/// games per set, sets and the time limit in seconds, separated by slashes. A time limit of 0
+/// means there is none.
+impl fmt::Display for MatchRules {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}/{}/{}/{}/{}",
+                      self.points_per_game,
+                      if self.win_by_two {1} else {0},
+                      self.games_per_set,
+                      self.sets,
+                      self.time_limit_secs.unwrap_or(0));
+    }
+
+}
+
+impl FromStr for MatchRules {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MatchRules, String> {
+        let fields: Option<Vec<u32>> = s.split('/').map(|field| field.parse().ok()).collect();
+        let fields = match fields {
+            Some(ref fields) if fields.len() == 5 => fields.clone(),
+            _ => return Err(format!("malformed match rules '{}'", s))
+        };
+        let rules = MatchRules {
+            points_per_game: fields[0],
+            win_by_two: fields[1] != 0,
+            games_per_set: fields[2],
+            sets: fields[3],
+            time_limit_secs: if fields[4] > 0 {Some(fields[4])} else {None}
+        };
+        if rules.points_per_game == 0 || rules.games_per_set == 0 || rules.sets == 0 {
+            return Err(format!("match rules '{}' can never be won", s));
+        }
+        return Ok(rules);
+    }
+
+}
+
+/// Where a match stands. Indexed by `Side`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MatchScore {
+    pub points: [u32; 2],       // In the current game.
+    pub games: [u32; 2],        // In the current set.
+    pub sets: [u32; 2],
+    pub sudden_death: bool,     // Time is up with the score level. The next point wins.
+    pub server: Side,           // Puts the ball in play. The ball comes back off the wall of
+                                // whoever lost the last point, so they serve next.
+    pub winner: Option<Side>
+}
+
+impl MatchScore {
+
+    pub fn new() -> MatchScore {
+        return MatchScore {
+            points: [0, 0],
+            games: [0, 0],
+            sets: [0, 0],
+            sudden_death: false,
+            server: Side::Left,
+            winner: None
+        };
+    }
+
+    /// Award a point, completing games and sets as the rules say. The score the match was won
+    /// with is left standing.
+    pub fn point(&mut self, rules: &MatchRules, scorer: Side) {
+        if self.winner.is_some() {
+            return;
+        }
+        let (s, o) = (scorer as usize, 1 - scorer as usize);
+        self.points[s] += 1;
+        self.server = if scorer == Side::Left {Side::Right} else {Side::Left};
+        if self.sudden_death {
+            self.winner = Some(scorer);
+            return;
+        }
+        if !rules.game_won(self.points[s], self.points[o]) {
+            return;
+        }
+        self.games[s] += 1;
+        if self.games[s] < rules.games_to_win_set() {
+            self.points = [0, 0];
+            return;
+        }
+        self.sets[s] += 1;
+        if self.sets[s] < rules.sets_to_win_match() {
+            self.points = [0, 0];
+            self.games = [0, 0];
+            return;
+        }
+        self.winner = Some(scorer);
+    }
+
+    /// The time limit is up. Whoever is ahead wins, or the match goes to sudden death.
+    pub fn time_up(&mut self) {
+        if self.winner.is_some() || self.sudden_death {
+            return;
+        }
+        match self.leader() {
+            Some(side) => self.winner = Some(side),
+            None => self.sudden_death = true
+        }
+    }
+
+    /// The side ahead on sets, then games, then points.
+    pub fn leader(&self) -> Option<Side> {
+        let left = (self.sets[0], self.games[0], self.points[0]);
+        let right = (self.sets[1], self.games[1], self.points[1]);
+        return if left > right {
+            Some(Side::Left)
+        } else if right > left {
+            Some(Side::Right)
+        } else {
+            None
+        };
+    }
+
+}
+
+impl Checksummable for MatchScore {
+
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        for &value in self.points.iter().chain(self.games.iter()).chain(self.sets.iter()) {
+            hasher.write_u32(value);
+        }
+        hasher.write_bool(self.sudden_death);
+        hasher.write_u32(self.server as u32);
+        hasher.write_u32(match self.winner {
+            None => 0,
+            Some(Side::Left) => 1,
+            Some(Side::Right) => 2
+        });
+    }
+
+    fn dump_state(&self) -> String {
+        return format!("score points={:?} games={:?} sets={:?} sudden_death={} server={:?} \
+                        winner={:?}",
+                       self.points, self.games, self.sets, self.sudden_death, self.server,
+                       self.winner);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pongo::match_stats::Side;
+
+    fn play(score: &mut MatchScore, rules: &MatchRules, scorer: Side, points: u32) {
+        for _ in 0..points {
+            score.point(rules, scorer);
+        }
+    }
+
+    #[test]
+    fn first_to_points_wins() {
+        let rules = MatchRules::first_to(3);
+        let mut score = MatchScore::new();
+        play(&mut score, &rules, Side::Left, 2);
+        play(&mut score, &rules, Side::Right, 2);
+        assert_eq!(score.winner, None);
+        score.point(&rules, Side::Right);
+        assert_eq!(score.winner, Some(Side::Right));
+        assert_eq!(score.points, [2, 3]);
+    }
+
+    #[test]
+    fn win_by_two_plays_on_past_deuce() {
+        let rules = MatchRules { win_by_two: true, ..MatchRules::first_to(11) };
+        let mut score = MatchScore::new();
+        play(&mut score, &rules, Side::Left, 10);
+        play(&mut score, &rules, Side::Right, 10);
+        score.point(&rules, Side::Left);
+        assert_eq!(score.winner, None);
+        score.point(&rules, Side::Right);
+        score.point(&rules, Side::Right);
+        assert_eq!(score.winner, None);
+        score.point(&rules, Side::Right);
+        assert_eq!(score.winner, Some(Side::Right));
+        assert_eq!(score.points, [11, 13]);
+    }
+
+    #[test]
+    fn games_are_won_at_the_points_unless_a_lead_of_two_is_needed() {
+        let rules = MatchRules::first_to(11);
+        assert!(rules.game_won(11, 10) && rules.game_won(12, 3));
+        assert!(!rules.game_won(10, 11) && !rules.game_won(10, 0));
+        let rules = MatchRules { win_by_two: true, ..rules };
+        assert!(rules.game_won(11, 9) && rules.game_won(14, 12));
+        assert!(!rules.game_won(11, 10) && !rules.game_won(13, 12));
+    }
+
+    #[test]
+    fn games_and_sets_reset_the_points() {
+        let rules = MatchRules { games_per_set: 3, sets: 3, ..MatchRules::first_to(2) };
+        let mut score = MatchScore::new();
+        play(&mut score, &rules, Side::Left, 2);
+        assert_eq!((score.points, score.games), ([0, 0], [1, 0]));
+        play(&mut score, &rules, Side::Left, 2);
+        assert_eq!((score.points, score.games, score.sets), ([0, 0], [0, 0], [1, 0]));
+        play(&mut score, &rules, Side::Left, 3);
+        assert_eq!(score.winner, None);
+        play(&mut score, &rules, Side::Left, 1);
+        assert_eq!(score.winner, Some(Side::Left));
+        assert_eq!((score.points, score.games, score.sets), ([2, 0], [2, 0], [2, 0]));
+    }
+
+    #[test]
+    fn no_points_after_the_match_is_won() {
+        let rules = MatchRules::first_to(1);
+        let mut score = MatchScore::new();
+        score.point(&rules, Side::Left);
+        score.point(&rules, Side::Right);
+        assert_eq!(score.points, [1, 0]);
+        assert_eq!(score.winner, Some(Side::Left));
+    }
+
+    #[test]
+    fn time_up_goes_to_the_leader() {
+        let rules = MatchRules { games_per_set: 3, ..MatchRules::first_to(5) };
+        let mut score = MatchScore::new();
+        play(&mut score, &rules, Side::Left, 5);
+        play(&mut score, &rules, Side::Right, 3);
+        score.time_up();
+        assert_eq!(score.winner, Some(Side::Left));
+        assert!(!score.sudden_death);
+    }
+
+    #[test]
+    fn time_up_level_goes_to_sudden_death() {
+        let rules = MatchRules { win_by_two: true, time_limit_secs: Some(60),
+                                 ..MatchRules::first_to(11) };
+        let mut score = MatchScore::new();
+        play(&mut score, &rules, Side::Left, 4);
+        play(&mut score, &rules, Side::Right, 4);
+        score.time_up();
+        assert_eq!(score.winner, None);
+        assert!(score.sudden_death);
+        score.time_up();
+        assert!(score.sudden_death);
+        score.point(&rules, Side::Right);
+        assert_eq!(score.winner, Some(Side::Right));
+    }
+
+    #[test]
+    fn time_limit_is_in_milliseconds() {
+        let rules = MatchRules { time_limit_secs: Some(90), ..MatchRules::first_to(5) };
+        assert_eq!(rules.time_limit_ms(), Some(90000));
+        assert_eq!(MatchRules::first_to(5).time_limit_ms(), None);
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        let rules = MatchRules { points_per_game: 11, win_by_two: true, games_per_set: 5,
+                                 sets: 3, time_limit_secs: Some(300) };
+        assert_eq!(rules.to_string(), "11/1/5/3/300");
+        assert_eq!("11/1/5/3/300".parse::<MatchRules>(), Ok(rules));
+        assert_eq!("7/0/1/1/0".parse::<MatchRules>(), Ok(MatchRules::first_to(7)));
+    }
+
+    #[test]
+    fn rules_that_can_never_be_won_are_rejected() {
+        assert!("0/0/1/1/0".parse::<MatchRules>().is_err());
+        assert!("5/0/0/1/0".parse::<MatchRules>().is_err());
+        assert!("5/0/1/0/0".parse::<MatchRules>().is_err());
+        assert!("5/0/1/1".parse::<MatchRules>().is_err());
+    }
+
+}
@@ -0,0 +1,98 @@
+use super::Resettable;
+
+/// The two sides of the court. The side's value indexes the per player statistics.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+    Left = 0,
+    Right = 1
+}
+
+/// Statistics of a single match, gathered from what happens to the ball as it is played.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchStats {
+    pub points: u32,                    // Number of points played, i.o.w. rallies finished.
+    pub longest_rally: u32,             // Most paddle hits during a single point.
+    pub paddle_hits: [u32; 2],          // Per side.
+    pub wall_bounces: u32,              // Bounces off the top and bottom walls.
+    pub top_ball_speed: f32,            // In pixels per second.
+    pub slow_motions_used: u32,
+    pub points_won_on_serve: [u32; 2],  // Per side. Points the opponent never touched the ball.
+    pub duration_ms: u64,               // Time spent playing the match.
+    pub longest_point_ms: u64,          // Longest time the ball stayed in play.
+    current_rally: u32,                 // Paddle hits since the last point.
+    current_point_ms: u64               // Time since the last point.
+}
+
+impl MatchStats {
+
+    pub fn new() -> MatchStats {
+        return MatchStats {
+            points: 0,
+            longest_rally: 0,
+            paddle_hits: [0, 0],
+            wall_bounces: 0,
+            top_ball_speed: 0.,
+            slow_motions_used: 0,
+            points_won_on_serve: [0, 0],
+            duration_ms: 0,
+            longest_point_ms: 0,
+            current_rally: 0,
+            current_point_ms: 0
+        };
+    }
+
+    pub fn paddle_hit(&mut self, side: Side) {
+        self.paddle_hits[side as usize] += 1;
+        self.current_rally += 1;
+        if self.current_rally > self.longest_rally {
+            self.longest_rally = self.current_rally;
+        }
+    }
+
+    pub fn wall_bounce(&mut self) {
+        self.wall_bounces += 1;
+    }
+
+    /// A point was scored by the given side. The ball comes back into play straight away, so
+    /// this also starts the next rally.
+    pub fn point(&mut self, scorer: Side) {
+        self.points += 1;
+        if self.current_rally == 0 {
+            self.points_won_on_serve[scorer as usize] += 1;
+        }
+        self.current_rally = 0;
+        self.current_point_ms = 0;
+    }
+
+    pub fn ball_speed(&mut self, speed: f32) {
+        if speed > self.top_ball_speed {
+            self.top_ball_speed = speed;
+        }
+    }
+
+    pub fn slow_motion_used(&mut self) {
+        self.slow_motions_used += 1;
+    }
+
+    pub fn tick(&mut self, dt_ms: u32) {
+        self.duration_ms += dt_ms as u64;
+        self.current_point_ms += dt_ms as u64;
+        if self.current_point_ms > self.longest_point_ms {
+            self.longest_point_ms = self.current_point_ms;
+        }
+    }
+
+    /// Paddle hits by both players over the whole match.
+    pub fn total_rally_hits(&self) -> u32 {
+        return self.paddle_hits[0] + self.paddle_hits[1];
+    }
+
+}
+
+impl Resettable for MatchStats {
+
+    fn reset(&mut self) {
+        *self = MatchStats::new();
+    }
+
+}
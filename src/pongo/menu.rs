@@ -0,0 +1,420 @@
+use pongo::layout::{Align, Bounds, Element};
+use pongo::ui::{ScalableFont, Ui};
+
+use sdl2::controller::{Axis, Button};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::Mouse;
+
+use sdl2_gfx::primitives::DrawRenderer;
+
+use std::rc::Rc;
+use std::thread;
+use std::usize;
+
+/// How far the controller stick has to be pushed to move through a menu.
+const STICK_THRESHOLD: i16 = 16000;
+
+/// Width of a slider's track.
+const SLIDER_WIDTH: f32 = 160.;
+
+/// Gap between the label of a setting and its value, either side of the middle of the screen.
+const LABEL_GAP: f32 = 15.;
+
+/// Half the width of the area a menu line responds to the mouse in.
+const LINE_HALF_WIDTH: f32 = 300.;
+
+/// A line of a menu.
+pub enum Widget {
+    Button { label: String },
+    Toggle { label: String, on: bool },
+    Slider { label: String, value: u32, min: u32, max: u32, step: u32 },
+    List { label: String, options: Vec<String>, selected: usize }
+}
+
+impl Widget {
+
+    pub fn button(label: &str) -> Widget {
+        return Widget::Button { label: label.to_string() };
+    }
+
+    pub fn toggle(label: &str, on: bool) -> Widget {
+        return Widget::Toggle { label: label.to_string(), on: on };
+    }
+
+    pub fn slider(label: &str, value: u32, min: u32, max: u32, step: u32) -> Widget {
+        return Widget::Slider {
+            label: label.to_string(),
+            value: value.max(min).min(max),
+            min: min,
+            max: max,
+            step: step
+        };
+    }
+
+    pub fn list(label: &str, options: Vec<String>, selected: usize) -> Widget {
+        let selected = if selected < options.len() {selected} else {0};
+        return Widget::List { label: label.to_string(), options: options, selected: selected };
+    }
+
+    /// Whether a toggle is on. False for anything else.
+    pub fn is_on(&self) -> bool {
+        return match *self {
+            Widget::Toggle { on, .. } => on,
+            _ => false
+        };
+    }
+
+    /// The value of a slider, or the index of the option picked from a list.
+    pub fn value(&self) -> u32 {
+        return match *self {
+            Widget::Slider { value, .. } => value,
+            Widget::List { selected, .. } => selected as u32,
+            _ => 0
+        };
+    }
+
+    /// Step a toggle, slider or list back (-1) or forward (1). Returns whether it changed.
+    fn step(&mut self, direction: i32) -> bool {
+        match *self {
+            Widget::Button {..} => return false,
+            Widget::Toggle { ref mut on, .. } => {
+                *on = !*on;
+                return true;
+            },
+            Widget::Slider { ref mut value, min, max, step, .. } => {
+                let stepped = if direction < 0 {
+                    value.saturating_sub(step).max(min)
+                } else {
+                    (*value + step).min(max)
+                };
+                let changed = stepped != *value;
+                *value = stepped;
+                return changed;
+            },
+            Widget::List { ref options, ref mut selected, .. } => {
+                if options.len() < 2 {
+                    return false;
+                }
+                *selected = if direction < 0 {
+                    (*selected + options.len() - 1) % options.len()
+                } else {
+                    (*selected + 1) % options.len()
+                };
+                return true;
+            }
+        }
+    }
+
+}
+
+/// Where a menu is moved by any of the input devices.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Navigation {
+    Up,
+    Down,
+    Left,
+    Right,
+    Accept,
+    Back,
+    Point(f32, f32),    // The mouse moved here.
+    Click(f32, f32)     // The left mouse button was pressed here.
+}
+
+/// What happened to a menu as the result of an event.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuEvent {
+    Nothing,
+    Activated(usize),   // A button was pressed.
+    Changed(usize),     // A toggle, slider or list took a new value.
+    Back                // The player backed out of the menu.
+}
+
+/// A column of widgets centered on the screen, one of which has the focus. Moved through with
+/// the arrow keys, a controller's d-pad or left stick, or the mouse. Enter, space, the A
+/// button or a click presses a button. Left and right, or a click, change a setting. Escape
+/// or the B button backs out. A menu with more lines than fit scrolls to keep the focus in
+/// view.
+pub struct Menu {
+    pub items: Vec<Widget>,
+    pub focus: usize,
+    pub top: f32,               // Where the first line is drawn.
+    pub line_height: f32,
+    pub max_lines: usize,       // Lines shown at once.
+    scroll: usize,              // The first line shown.
+    stick_direction: [i32; 2]   // Which way the stick was last pushed across and up or down,
+                                // so that holding it over moves once.
+}
+
+impl Menu {
+
+    pub fn new(items: Vec<Widget>, top: f32) -> Menu {
+        return Menu {
+            items: items,
+            focus: 0,
+            top: top,
+            line_height: 38.,
+            max_lines: usize::MAX,
+            scroll: 0,
+            stick_direction: [0, 0]
+        };
+    }
+
+    /// The line at a height on the screen, if there is one there.
+    fn line_at(&self, ui: &Ui, x: f32, y: f32) -> Option<usize> {
+        if (x - ui.width / 2.).abs() > LINE_HALF_WIDTH || y < self.top {
+            return None;
+        }
+        let shown = ((y - self.top) / self.line_height) as usize;
+        let line = self.scroll + shown;
+        return if shown < self.max_lines && line < self.items.len() {Some(line)} else {None};
+    }
+
+    /// Scroll just far enough to show the focused line.
+    fn scroll_to_focus(&mut self) {
+        if self.focus < self.scroll {
+            self.scroll = self.focus;
+        } else if self.focus - self.scroll >= self.max_lines {
+            self.scroll = self.focus + 1 - self.max_lines;
+        }
+    }
+
+    /// Work out how an event moves through the menu, if at all.
+    fn navigation(&mut self, event: &Event) -> Option<Navigation> {
+        return match *event {
+            Event::KeyDown { keycode: Some(keycode), .. } => match keycode {
+                Keycode::Up => Some(Navigation::Up),
+                Keycode::Down => Some(Navigation::Down),
+                Keycode::Left => Some(Navigation::Left),
+                Keycode::Right => Some(Navigation::Right),
+                Keycode::Return | Keycode::Space => Some(Navigation::Accept),
+                Keycode::Escape | Keycode::Backspace => Some(Navigation::Back),
+                _ => None
+            },
+            Event::ControllerButtonDown { button, .. } => match button {
+                Button::DPadUp => Some(Navigation::Up),
+                Button::DPadDown => Some(Navigation::Down),
+                Button::DPadLeft => Some(Navigation::Left),
+                Button::DPadRight => Some(Navigation::Right),
+                Button::A | Button::Start => Some(Navigation::Accept),
+                Button::B | Button::Back => Some(Navigation::Back),
+                _ => None
+            },
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                if axis != Axis::LeftX && axis != Axis::LeftY {
+                    return None;
+                }
+                let direction = if value > STICK_THRESHOLD {
+                    1
+                } else if value < -STICK_THRESHOLD {
+                    -1
+                } else {
+                    0
+                };
+                let navigation = match (axis, direction) {
+                    (_, 0) => None,
+                    (Axis::LeftY, -1) => Some(Navigation::Up),
+                    (Axis::LeftY, _) => Some(Navigation::Down),
+                    (Axis::LeftX, -1) => Some(Navigation::Left),
+                    (_, _) => Some(Navigation::Right)
+                };
+                // Only pushing the stick over moves, not holding it there.
+                let index = if axis == Axis::LeftX {0} else {1};
+                if direction == self.stick_direction[index] {
+                    return None;
+                }
+                self.stick_direction[index] = direction;
+                navigation
+            },
+            Event::MouseMotion { x, y, .. } => Some(Navigation::Point(x as f32, y as f32)),
+            Event::MouseButtonDown { mouse_btn: Mouse::Left, x, y, .. } => {
+                Some(Navigation::Click(x as f32, y as f32))
+            },
+            _ => None
+        };
+    }
+
+    /// Move through the menu or change the focused widget in response to an event.
+    pub fn handle_event(&mut self, ui: &Ui, event: &Event) -> MenuEvent {
+        if self.items.is_empty() {
+            return match self.navigation(event) {
+                Some(Navigation::Back) => MenuEvent::Back,
+                _ => MenuEvent::Nothing
+            };
+        }
+        let count = self.items.len();
+        let focus = self.focus;
+        match self.navigation(event) {
+            Some(Navigation::Up) => {
+                self.focus = (focus + count - 1) % count;
+                self.scroll_to_focus();
+            },
+            Some(Navigation::Down) => {
+                self.focus = (focus + 1) % count;
+                self.scroll_to_focus();
+            },
+            Some(Navigation::Left) => {
+                if self.items[focus].step(-1) {
+                    return MenuEvent::Changed(focus);
+                }
+            },
+            Some(Navigation::Right) => {
+                if self.items[focus].step(1) {
+                    return MenuEvent::Changed(focus);
+                }
+            },
+            Some(Navigation::Accept) => return self.accept(focus),
+            Some(Navigation::Back) => return MenuEvent::Back,
+            Some(Navigation::Point(x, y)) => {
+                if let Some(line) = self.line_at(ui, x, y) {
+                    self.focus = line;
+                }
+            },
+            Some(Navigation::Click(x, y)) => {
+                if let Some(line) = self.line_at(ui, x, y) {
+                    self.focus = line;
+                    return self.click(ui, line, x);
+                }
+            },
+            None => {}
+        }
+        return MenuEvent::Nothing;
+    }
+
+    /// Press a button, or step a toggle or list forward.
+    fn accept(&mut self, line: usize) -> MenuEvent {
+        if let Widget::Button {..} = self.items[line] {
+            return MenuEvent::Activated(line);
+        }
+        return if self.items[line].step(1) {MenuEvent::Changed(line)} else {MenuEvent::Nothing};
+    }
+
+    /// A click on a slider's track moves it to where it was clicked. Anywhere else, a click
+    /// does what accepting would.
+    fn click(&mut self, ui: &Ui, line: usize, x: f32) -> MenuEvent {
+        let track_left = ui.width / 2. + LABEL_GAP;
+        if let Widget::Slider { ref mut value, min, max, step, .. } = self.items[line] {
+            if x < track_left || x > track_left + SLIDER_WIDTH {
+                return MenuEvent::Nothing;
+            }
+            let fraction = (x - track_left) / SLIDER_WIDTH;
+            let steps = ((max - min) as f32 * fraction / step as f32).round() as u32;
+            let clicked = (min + steps * step).min(max);
+            if clicked == *value {
+                return MenuEvent::Nothing;
+            }
+            *value = clicked;
+            return MenuEvent::Changed(line);
+        }
+        return self.accept(line);
+    }
+
+    /// Draw the lines in view. Buttons are centered. Settings have their label to the left of
+    /// the middle of the screen and their value to the right, all in the colors of the theme.
+    pub fn draw(&self, ui: &mut Ui, font: &Rc<ScalableFont>) {
+        let text_color = ui.theme.text;
+        let focus_color = ui.theme.highlight;
+        let mut y = self.top;
+        for (i, item) in self.items.iter().enumerate().skip(self.scroll).take(self.max_lines) {
+            let color = if i == self.focus {focus_color} else {text_color};
+            let label = match *item {
+                Widget::Button { ref label } => {
+                    let mut button = Element::button(i, label, font, text_color, focus_color);
+                    button.set_focus(Some(self.focus));
+                    let area = Bounds::new(0., y, ui.width, self.line_height);
+                    button.draw_in(ui, area, Align::Center, Align::Start);
+                    y += self.line_height;
+                    continue;
+                },
+                Widget::Toggle { ref label, .. } | Widget::Slider { ref label, .. } |
+                    Widget::List { ref label, .. } => label
+            };
+            let label_area = Bounds::new(0., y, ui.width / 2. - LABEL_GAP, self.line_height);
+            let label_bounds = Element::label(label, font, color)
+                .draw_in(ui, label_area, Align::End, Align::Start);
+            let label_height = label_bounds.height;
+
+            let value_x = ui.width / 2. + LABEL_GAP;
+            match *item {
+                Widget::Toggle { on, .. } => {
+                    ui.draw_text(font, if on {"on"} else {"off"}, color, value_x, y);
+                },
+                Widget::Slider { value, min, max, .. } => {
+                    let middle = (y + label_height / 2.) as i16;
+                    let left = value_x as i16;
+                    let right = (value_x + SLIDER_WIDTH) as i16;
+                    let _ = ui.renderer.box_(left, middle - 2, right, middle + 2, text_color);
+                    let fraction = if max > min {
+                        (value - min) as f32 / (max - min) as f32
+                    } else {
+                        0.
+                    };
+                    let knob_x = (value_x + SLIDER_WIDTH * fraction) as i16;
+                    let _ = ui.renderer.filled_circle(knob_x, middle, 7, color);
+                    ui.draw_text(font, &value.to_string(), color,
+                                 value_x + SLIDER_WIDTH + LABEL_GAP, y);
+                },
+                Widget::List { ref options, selected, .. } => {
+                    if let Some(option) = options.get(selected) {
+                        let text = if i == self.focus {
+                            format!("< {} >", option)
+                        } else {
+                            option.clone()
+                        };
+                        ui.draw_text(font, &text, color, value_x, y);
+                    }
+                },
+                Widget::Button {..} => {}
+            }
+            y += self.line_height;
+        }
+    }
+
+}
+
+/// Let the player pick one of a list of options under a heading, with a way back at the end.
+/// Returns the index of the option picked, or None if the player backed out or the window was
+/// closed, even on an earlier screen.
+pub fn choose(ui: &mut Ui, heading: &str, options: &[String], empty_msg: &str) -> Option<usize> {
+    let heading_font = Rc::new(ScalableFont::new(&ui.theme.heading_font, 39));
+    let text_font = Rc::new(ScalableFont::new(&ui.theme.text_font, 22));
+
+    let mut items: Vec<Widget> = options.iter().map(|option| Widget::button(option)).collect();
+    items.push(Widget::button("Back"));
+    let mut menu = Menu::new(items, 140.);
+    menu.line_height = 34.;
+    menu.max_lines = 12;
+
+    loop {
+        if ui.quit_requested {
+            return None;
+        }
+        while let Some(event) = ui.poll_event() {
+            if let Event::Quit {..} = event {
+                return None;
+            }
+            match menu.handle_event(ui, &event) {
+                MenuEvent::Activated(line) if line < options.len() => return Some(line),
+                MenuEvent::Activated(..) | MenuEvent::Back => return None,
+                _ => {}
+            }
+        }
+
+        let (background, heading_color, text_color) =
+            (ui.theme.background, ui.theme.heading, ui.theme.text);
+        ui.renderer.set_draw_color(background);
+        ui.renderer.clear();
+        let heading_area = Bounds::new(0., 60., ui.width, 0.);
+        Element::label(heading, &heading_font, heading_color)
+            .draw_in(ui, heading_area, Align::Center, Align::Start);
+        if options.is_empty() {
+            let empty_area = Bounds::new(0., 100., ui.width, 0.);
+            Element::label(empty_msg, &text_font, text_color)
+                .draw_in(ui, empty_area, Align::Center, Align::Start);
+        }
+        menu.draw(ui, &text_font);
+        ui.renderer.present();
+
+        thread::sleep_ms(50);
+    }
+}
@@ -3,10 +3,46 @@ pub trait Resettable {
     fn reset(&mut self);
 }
 
+/// Unwrap an option or return None from the enclosing function. The option counterpart of
+/// `try!`, used when parsing lines received over the network or read from disk.
+macro_rules! try_opt {
+    ($e:expr) => (match $e { Some(value) => value, None => return None })
+}
+
+pub mod ai_level;
 pub mod ball;
+pub mod checksum;
+pub mod console;
+pub mod data_dir;
+pub mod debug_overlay;
+pub mod frame_pacer;
 pub mod game;
+pub mod history;
+pub mod instant_replay;
+pub mod lan;
+pub mod layout;
+pub mod leaderboard;
+pub mod leaderboard_screen;
+pub mod lobby;
+pub mod match_rules;
+pub mod match_stats;
+pub mod menu;
 pub mod net;
 pub mod paddle;
-pub mod score_card;
+pub mod palette;
+pub mod profile;
+pub mod profile_menu;
+pub mod profiler;
+pub mod relay_protocol;
+pub mod replay;
+pub mod scoreboard;
+pub mod settings;
+pub mod settings_menu;
+pub mod snapshot;
+pub mod spectator;
+pub mod summary;
+pub mod text_entry;
+pub mod text_renderer;
+pub mod theme;
 pub mod ui;
-
+pub mod window_mode;
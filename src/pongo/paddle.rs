@@ -1,8 +1,14 @@
+use pongo::checksum::{Checksummable, StateHasher};
 use pongo::ui::{Drawable, Ui};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use super::Resettable;
 
+/// Height of each stripe across a striped paddle, and of the gap below it.
+const STRIPE_HEIGHT: f32 = 6.;
+const STRIPE_GAP: f32 = 3.;
+
+#[derive(Clone)]
 pub struct Paddle {
     pub color: Color,   
     pub initial_x: f32,         // The initial x location. Stored so that we can reset the paddle.
@@ -11,8 +17,10 @@ pub struct Paddle {
     pub y: f32,                 // y pixel coordinate of top left corner
     pub width: f32,     
     pub height: f32,    
-    pub speed: f32,             // Speed in pixels per second. Never changes. 
-    pub speed_multiplier: f32   // Used to adjust the speed.
+    pub speed: f32,             // Speed in pixels per second. Never changes during a match.
+    pub speed_multiplier: f32,  // Used to adjust the speed.
+    pub striped: bool           // Drawn in stripes when shape cues are on, so it can be told
+                                // from the other paddle by more than its color.
 }
 
 impl Paddle {
@@ -33,7 +41,8 @@ impl Paddle {
             width: width, 
             height: height, 
             speed: speed,
-            speed_multiplier: 1.0
+            speed_multiplier: 1.0,
+            striped: false
         };
 
         paddle.reset();
@@ -58,12 +67,48 @@ impl Resettable for Paddle {
 
 impl Drawable for Paddle {
 
+    /// The paddle can be drawn wider than it is, to make it easier to see. It grows away from
+    /// the court, so its face stays where the ball bounces off it. Its length, which decides
+    /// what it hits, is always drawn as it is.
     fn draw(&self, ui: &mut Ui) {
+        let width = self.width * ui.object_scale as f32 / 100.;
+        let faces_right = self.x + self.width / 2. < ui.width / 2.;
+        let x = if faces_right {self.x + self.width - width} else {self.x};
         ui.renderer.set_draw_color(self.color);
-        ui.renderer.fill_rect(Rect::new_unwrap(self.x as i32, 
-                                    self.y as i32, 
-                                    self.width as u32, 
-                                    self.height as u32));
+        if !(self.striped && ui.shape_cues) {
+            ui.renderer.fill_rect(Rect::new_unwrap(x as i32, 
+                                        self.y as i32, 
+                                        width as u32, 
+                                        self.height as u32));
+            return;
+        }
+
+        // Stripes from end to end, the last one cut short where the paddle ends.
+        let mut y = 0.;
+        while y < self.height {
+            let height = STRIPE_HEIGHT.min(self.height - y);
+            ui.renderer.fill_rect(Rect::new_unwrap(x as i32, 
+                                        (self.y + y) as i32, 
+                                        width as u32, 
+                                        height.max(1.) as u32));
+            y += STRIPE_HEIGHT + STRIPE_GAP;
+        }
+    }
+
+}
+
+impl Checksummable for Paddle {
+
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_f32(self.x);
+        hasher.write_f32(self.y);
+        hasher.write_f32(self.speed);
+        hasher.write_f32(self.speed_multiplier);
+    }
+
+    fn dump_state(&self) -> String {
+        return format!("paddle x={} y={} speed={} speed_multiplier={}",
+                       self.x, self.y, self.speed, self.speed_multiplier);
     }
 
 }
@@ -0,0 +1,81 @@
+use pongo::theme::Theme;
+
+use sdl2::pixels::Color;
+
+use std::str::FromStr;
+
+/// The colors the players and the HUD are drawn in. A theme's own colors may be hard to tell
+/// apart for a player who sees colors differently, so palettes chosen to stay distinct for
+/// each kind of color blindness can take their place.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorPalette {
+    Theme = 0,          // The colors of the theme in use.
+    Deuteranopia = 1,   // Green weak. Red and green look alike.
+    Protanopia = 2,     // Red weak. Red and green look alike, and red looks dark.
+    Tritanopia = 3      // Blue weak. Blue and green, and yellow and pink, look alike.
+}
+
+/// Every palette. A palette's value indexes this array.
+pub const COLOR_PALETTES: [ColorPalette; 4] = [ColorPalette::Theme, ColorPalette::Deuteranopia,
+                                               ColorPalette::Protanopia, ColorPalette::Tritanopia];
+
+/// What a palette tells apart by color.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PaletteColors {
+    pub left: Color,        // The left paddle and its side of the scoreboard.
+    pub right: Color,       // The right paddle and its side of the scoreboard.
+    pub available: Color,   // Slow motions and rewinds still to use.
+    pub used: Color         // Slow motions and rewinds used up.
+}
+
+impl ColorPalette {
+
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            ColorPalette::Theme => "theme",
+            ColorPalette::Deuteranopia => "deuteranopia",
+            ColorPalette::Protanopia => "protanopia",
+            ColorPalette::Tritanopia => "tritanopia"
+        };
+    }
+
+    /// The colors to draw with. The palettes for color blindness keep the players apart by
+    /// lightness as well as hue, and what is used up is always the theme's muted color.
+    pub fn colors(&self, theme: &Theme) -> PaletteColors {
+        let (left, right, available) = match *self {
+            ColorPalette::Theme => {
+                return PaletteColors {
+                    left: theme.left_paddle,
+                    right: theme.right_paddle,
+                    available: theme.positive,
+                    used: theme.muted
+                };
+            },
+            ColorPalette::Deuteranopia => (Color::RGB(0x00, 0x72, 0xb2),
+                                           Color::RGB(0xe6, 0x9f, 0x00),
+                                           Color::RGB(0xf0, 0xe4, 0x42)),
+            ColorPalette::Protanopia => (Color::RGB(0x56, 0xb4, 0xe9),
+                                         Color::RGB(0xf0, 0xe4, 0x42),
+                                         Color::RGB(0xff, 0xff, 0xff)),
+            ColorPalette::Tritanopia => (Color::RGB(0xe8, 0x46, 0x7c),
+                                         Color::RGB(0x1f, 0xb5, 0xac),
+                                         Color::RGB(0xff, 0xff, 0xff))
+        };
+        return PaletteColors { left: left, right: right, available: available, used: theme.muted };
+    }
+
+}
+
+impl FromStr for ColorPalette {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorPalette, String> {
+        return match COLOR_PALETTES.iter().find(|palette| palette.name() == s) {
+            Some(palette) => Ok(*palette),
+            None => Err(format!("unknown color palette '{}', expected theme, deuteranopia, \
+                                 protanopia or tritanopia", s))
+        };
+    }
+
+}
@@ -0,0 +1,320 @@
+use pongo::ai_level::{AiLevel, AI_LEVELS};
+use pongo::data_dir::{self, DataFile};
+use pongo::lan;
+
+use sdl2::pixels::Color;
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// First line of the profiles file. The number is bumped whenever the layout changes, so that
+/// an older game does not misread, and then overwrite, a newer file.
+const PROFILES_HEADER: &'static str = "pongo-profiles";
+const PROFILES_VERSION: u32 = 1;
+
+/// Longest name a profile may have.
+pub const MAX_NAME_LEN: usize = 16;
+
+/// Colors a player can pick for their paddle. The first is the default.
+pub const PADDLE_COLORS: [(u8, u8, u8); 5] = [
+    (0x03, 0x91, 0xcf),     // Blue
+    (0xf6, 0x77, 0x34),     // Orange
+    (0xfc, 0xef, 0x6d),     // Yellow
+    (0x6f, 0xc3, 0x2d),     // Green
+    (0xf0, 0x3b, 0x32)      // Red
+];
+
+/// How the player moves their paddle.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Controls {
+    Mouse,      // The paddle follows the mouse. Left click slows time, right click rewinds.
+    Keyboard,   // The arrow keys move the paddle. Space slows time, backspace rewinds.
+    Controller  // The d-pad or left stick moves the paddle. A slows time, B rewinds.
+}
+
+/// Every way of controlling a paddle.
+pub const CONTROLS: [Controls; 3] = [Controls::Mouse, Controls::Keyboard, Controls::Controller];
+
+impl Controls {
+
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            Controls::Mouse => "mouse",
+            Controls::Keyboard => "keyboard",
+            Controls::Controller => "controller"
+        };
+    }
+
+    pub fn next(&self) -> Controls {
+        return match *self {
+            Controls::Mouse => Controls::Keyboard,
+            Controls::Keyboard => Controls::Controller,
+            Controls::Controller => Controls::Mouse
+        };
+    }
+
+}
+
+impl FromStr for Controls {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Controls, String> {
+        return match s {
+            "mouse" => Ok(Controls::Mouse),
+            "keyboard" => Ok(Controls::Keyboard),
+            "controller" => Ok(Controls::Controller),
+            _ => Err(format!("unknown controls '{}'", s))
+        };
+    }
+
+}
+
+/// Statistics kept over every match a player has played.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LifetimeStats {
+    pub matches: u32,
+    pub wins_vs_ai: [u32; 3],   // Wins against the computer, per AI level.
+    pub best_rally: u32         // Most paddle hits during a single point.
+}
+
+/// A local player, with their preferences and lifetime statistics.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub controls: Controls,
+    pub paddle_color: (u8, u8, u8),
+    pub stats: LifetimeStats
+}
+
+impl Profile {
+
+    pub fn new(name: &str) -> Profile {
+        return Profile {
+            name: name.to_string(),
+            controls: Controls::Mouse,
+            paddle_color: PADDLE_COLORS[0],
+            stats: LifetimeStats { matches: 0, wins_vs_ai: [0, 0, 0], best_rally: 0 }
+        };
+    }
+
+    pub fn color(&self) -> Color {
+        let (r, g, b) = self.paddle_color;
+        return Color::RGB(r, g, b);
+    }
+
+    /// Pick the next of the paddle colors.
+    pub fn next_color(&mut self) {
+        let i = PADDLE_COLORS.iter().position(|c| *c == self.paddle_color).unwrap_or(0);
+        self.paddle_color = PADDLE_COLORS[(i + 1) % PADDLE_COLORS.len()];
+    }
+
+    /// Add a finished match to the lifetime statistics. `ai_level` is set when the opponent
+    /// was the computer.
+    pub fn record_match(&mut self, ai_level: Option<AiLevel>, won: bool, longest_rally: u32) {
+        self.stats.matches += 1;
+        if let Some(ai_level) = ai_level {
+            if won {
+                self.stats.wins_vs_ai[ai_level as usize] += 1;
+            }
+        }
+        self.stats.best_rally = self.stats.best_rally.max(longest_rally);
+    }
+
+    /// Encode as a line of tab separated fields.
+    fn encode(&self) -> String {
+        let (r, g, b) = self.paddle_color;
+        return format!("profile\t{}\t{}\t{:02x}{:02x}{:02x}\t{}\t{}\t{}\t{}\t{}",
+                       self.name.replace("\t", " "),
+                       self.controls.name(),
+                       r, g, b,
+                       self.stats.matches,
+                       self.stats.wins_vs_ai[0],
+                       self.stats.wins_vs_ai[1],
+                       self.stats.wins_vs_ai[2],
+                       self.stats.best_rally);
+    }
+
+    fn decode(line: &str) -> Option<Profile> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 9 || fields[0] != "profile" || fields[3].len() != 6 ||
+           !fields[3].chars().all(|c| c.is_digit(16)) {
+            return Option::None;
+        }
+        let color = fields[3];
+        let channel = |i: usize| u8::from_str_radix(&color[i..i + 2], 16).ok();
+        return Some(Profile {
+            name: fields[1].to_string(),
+            controls: try_opt!(fields[2].parse().ok()),
+            paddle_color: (try_opt!(channel(0)), try_opt!(channel(2)), try_opt!(channel(4))),
+            stats: LifetimeStats {
+                matches: try_opt!(fields[4].parse().ok()),
+                wins_vs_ai: [try_opt!(fields[5].parse().ok()),
+                             try_opt!(fields[6].parse().ok()),
+                             try_opt!(fields[7].parse().ok())],
+                best_rally: try_opt!(fields[8].parse().ok())
+            }
+        });
+    }
+
+}
+
+/// The local player profiles, saved in the data directory. There is always at least one
+/// profile, and one of them is selected to play with.
+pub struct ProfileStore {
+    pub profiles: Vec<Profile>,
+    pub selected: usize,
+    file: DataFile
+}
+
+impl ProfileStore {
+
+    /// The profiles file in the data directory.
+    pub fn default_path() -> PathBuf {
+        return data_dir::data_dir().join("profiles.txt");
+    }
+
+    /// Read the profiles. If there are none yet, a profile named after the user is created.
+    pub fn load(path: &Path) -> io::Result<ProfileStore> {
+        let mut store = ProfileStore {
+            profiles: Vec::new(),
+            selected: 0,
+            file: DataFile::new(path, PROFILES_HEADER, PROFILES_VERSION)
+        };
+        let lines = try!(store.file.read_lines()).unwrap_or(Vec::new());
+        for line in lines {
+            if line.starts_with("selected\t") {
+                store.selected = line["selected\t".len()..].parse().unwrap_or(0);
+            } else if let Some(profile) = Profile::decode(&line) {
+                store.profiles.push(profile);
+            }
+        }
+        if store.profiles.is_empty() {
+            store.profiles.push(Profile::new(&lan::local_player_name()));
+        }
+        if store.selected >= store.profiles.len() {
+            store.selected = 0;
+        }
+        return Ok(store);
+    }
+
+    /// Read the profiles from the data directory. Problems are reported on stderr, and the game
+    /// carries on with a default profile that is not saved.
+    pub fn load_or_default() -> ProfileStore {
+        let path = ProfileStore::default_path();
+        return match ProfileStore::load(&path) {
+            Ok(store) => store,
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(), "Unable to load profiles from {}: {}",
+                                 path.display(), e);
+                ProfileStore {
+                    profiles: vec![Profile::new(&lan::local_player_name())],
+                    selected: 0,
+                    file: DataFile::new(&path, PROFILES_HEADER, PROFILES_VERSION).read_only()
+                }
+            }
+        };
+    }
+
+    /// Write the profiles to disk.
+    pub fn save(&self) -> io::Result<()> {
+        let mut lines = vec![format!("selected\t{}", self.selected)];
+        lines.extend(self.profiles.iter().map(|profile| profile.encode()));
+        return self.file.write_lines(&lines);
+    }
+
+    /// Save, reporting any problem on stderr.
+    pub fn save_or_report(&self) {
+        if let Err(e) = self.save() {
+            let _ = writeln!(&mut io::stderr(), "Unable to save profiles to {}: {}",
+                             self.file.path().display(), e);
+        }
+    }
+
+    pub fn selected(&self) -> &Profile {
+        return &self.profiles[self.selected];
+    }
+
+    pub fn selected_mut(&mut self) -> &mut Profile {
+        return &mut self.profiles[self.selected];
+    }
+
+    /// Add a profile and select it. Returns false if the name is empty or already taken.
+    pub fn add(&mut self, name: &str) -> bool {
+        let name = name.trim();
+        if name.is_empty() || self.profiles.iter().any(|p| p.name == name) {
+            return false;
+        }
+        self.profiles.push(Profile::new(name));
+        self.selected = self.profiles.len() - 1;
+        return true;
+    }
+
+    /// Remove the selected profile, unless it is the last one.
+    pub fn remove_selected(&mut self) {
+        if self.profiles.len() > 1 {
+            self.profiles.remove(self.selected);
+            if self.selected >= self.profiles.len() {
+                self.selected = self.profiles.len() - 1;
+            }
+        }
+    }
+
+}
+
+/// The names of the AI levels along with the wins against each, for display.
+pub fn wins_vs_ai_summary(stats: &LifetimeStats) -> String {
+    let wins: Vec<String> = AI_LEVELS.iter()
+        .map(|level| format!("{} {}", level.name(), stats.wins_vs_ai[*level as usize]))
+        .collect();
+    return wins.join("   ");
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Controls, LifetimeStats, Profile, PADDLE_COLORS};
+
+    fn profile() -> Profile {
+        return Profile {
+            name: "Zoë".to_string(),
+            controls: Controls::Controller,
+            paddle_color: PADDLE_COLORS[2],
+            stats: LifetimeStats { matches: 42, wins_vs_ai: [10, 7, 1], best_rally: 31 }
+        };
+    }
+
+    #[test]
+    fn profile_survives_a_round_trip() {
+        assert_eq!(Profile::decode(&profile().encode()), Some(profile()));
+        assert_eq!(Profile::decode(&Profile::new("Ada").encode()), Some(Profile::new("Ada")));
+    }
+
+    #[test]
+    fn tabs_in_names_do_not_break_the_line() {
+        let mut profile = profile();
+        profile.name = "Zo\të".to_string();
+        assert_eq!(Profile::decode(&profile.encode()).unwrap().name, "Zo ë");
+    }
+
+    #[test]
+    fn malformed_colors_are_rejected() {
+        let line = profile().encode();
+        for color in ["fcef6", "fcef6d0", "fcefxx", "+cef6d", "fcéf6", "fc€f"].iter() {
+            let line = line.replace("fcef6d", color);
+            assert_eq!(Profile::decode(&line), None);
+        }
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        let line = profile().encode();
+        assert_eq!(Profile::decode(&line.replace("controller", "joystick")), None);
+        assert_eq!(Profile::decode(&line.replace("\t42\t", "\t-1\t")), None);
+        assert_eq!(Profile::decode(&line.replace("profile\t", "player\t")), None);
+        assert_eq!(Profile::decode(&format!("{}\t0", line)), None);
+        assert_eq!(Profile::decode(""), None);
+    }
+
+}
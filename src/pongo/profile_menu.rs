@@ -0,0 +1,128 @@
+use pongo::layout::{Align, Bounds, Element};
+use pongo::profile::{self, ProfileStore, MAX_NAME_LEN};
+use pongo::text_entry::{TextEntry, TextEntryStatus};
+use pongo::ui::{ScalableFont, Ui};
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use std::rc::Rc;
+use std::thread;
+
+/// Let the player pick the profile to play with, create new profiles and change the controls and
+/// paddle color of the selected one. Every change is saved straight away. Returns when the
+/// player presses enter or escape.
+pub fn choose(ui: &mut Ui, store: &mut ProfileStore) {
+    let heading_font = Rc::new(ScalableFont::new(&ui.theme.heading_font, 39));
+    let text_font = Rc::new(ScalableFont::new(&ui.theme.text_font, 22));
+    let heading_color = ui.theme.heading;
+    let text_color = ui.theme.text;
+    let highlight_color = ui.theme.highlight;
+
+    let mut new_profile: Option<TextEntry> = None;     // Set while a new profile is being named.
+
+    loop {
+        while let Some(event) = ui.poll_event() {
+            if let Event::Quit {..} = event {
+                TextEntry::stop(ui);
+                return;
+            }
+
+            if let Some(mut entry) = new_profile.take() {
+                match entry.handle_event(&event) {
+                    TextEntryStatus::Editing => new_profile = Some(entry),
+                    TextEntryStatus::Done => {
+                        TextEntry::stop(ui);
+                        if store.add(&entry.text) {
+                            store.save_or_report();
+                        }
+                    },
+                    TextEntryStatus::Cancelled => TextEntry::stop(ui)
+                }
+                continue;
+            }
+
+            let keycode = match event {
+                Event::KeyDown { keycode: Some(keycode), .. } => keycode,
+                _ => continue
+            };
+            match keycode {
+                Keycode::Escape | Keycode::Return => {
+                    store.save_or_report();
+                    return;
+                },
+                Keycode::Up => {
+                    store.selected = store.selected.saturating_sub(1);
+                },
+                Keycode::Down => {
+                    if store.selected + 1 < store.profiles.len() {
+                        store.selected += 1;
+                    }
+                },
+                Keycode::N => {
+                    new_profile = Some(TextEntry::new("", MAX_NAME_LEN));
+                    TextEntry::start(ui);
+                },
+                Keycode::C => {
+                    let controls = store.selected().controls.next();
+                    store.selected_mut().controls = controls;
+                    store.save_or_report();
+                },
+                Keycode::Left | Keycode::Right => {
+                    store.selected_mut().next_color();
+                    store.save_or_report();
+                },
+                Keycode::Delete => {
+                    store.remove_selected();
+                    store.save_or_report();
+                },
+                _ => {}
+            }
+        }
+
+        let mut profiles = Vec::new();
+        for (i, profile) in store.profiles.iter().enumerate() {
+            let line = format!("{}  -  {} controls", profile.name, profile.controls.name());
+            let color = if i == store.selected {profile.color()} else {text_color};
+            profiles.push(Element::label(&line, &text_font, color));
+        }
+
+        let selected = store.selected();
+        let stats_line = format!("Matches: {}   Best rally: {} hits",
+                                 selected.stats.matches, selected.stats.best_rally);
+        let wins_line = format!("Wins against the computer: {}",
+                                profile::wins_vs_ai_summary(&selected.stats));
+        let screen = Element::column(Align::Center, 0., vec![
+            Element::label("PROFILES", &heading_font, heading_color),
+            Element::space(0., 30.),
+            Element::column(Align::Center, 8., profiles),
+            Element::space(0., 40.),
+            Element::column(Align::Center, 12., vec![
+                Element::label(&stats_line, &text_font, text_color),
+                Element::label(&wins_line, &text_font, text_color)
+            ])
+        ]);
+
+        let (first_prompt, second_prompt) = match new_profile {
+            Some(ref entry) => (format!("New profile: {}", entry.display_text()),
+                                "ENTER to create, ESCAPE to cancel"),
+            None => ("N new   C controls   LEFT/RIGHT color   DELETE remove".to_string(),
+                     "ENTER to play with the selected profile")
+        };
+        let prompts = Element::column(Align::Center, 16., vec![
+            Element::label(&first_prompt, &text_font, highlight_color),
+            Element::label(second_prompt, &text_font, highlight_color)
+        ]);
+
+        let background = ui.theme.background;
+        ui.renderer.set_draw_color(background);
+        ui.renderer.clear();
+        let area = Bounds::new(0., 60., ui.width, 0.);
+        screen.draw_in(ui, area, Align::Center, Align::Start);
+        let bottom = Bounds::new(0., 0., ui.width, ui.height - 50.);
+        prompts.draw_in(ui, bottom, Align::Center, Align::End);
+        ui.renderer.present();
+
+        thread::sleep_ms(50);
+    }
+}
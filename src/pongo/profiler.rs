@@ -0,0 +1,245 @@
+extern crate clock_ticks;
+
+use pongo::ui::{ScalableFont, Ui};
+
+use sdl2::pixels::Color;
+
+use sdl2_gfx::primitives::DrawRenderer;
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Frames kept for the graph and the averages.
+const HISTORY_FRAMES: usize = 120;
+
+/// Size of the frame-time graph in pixels.
+const GRAPH_HEIGHT: f32 = 40.;
+
+/// How opaque the shade behind the graph and its text is.
+const SHADE_ALPHA: u8 = 0xc0;
+
+/// The parts of a frame that are timed separately. A phase's value indexes `PHASES`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Phase {
+    Update = 0,     // Input, simulation, networking and everything else outside the other two.
+    Draw = 1,
+    Audio = 2
+}
+
+pub const PHASES: [Phase; 3] = [Phase::Update, Phase::Draw, Phase::Audio];
+
+impl Phase {
+
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            Phase::Update => "update",
+            Phase::Draw => "draw",
+            Phase::Audio => "audio"
+        };
+    }
+
+}
+
+/// How long a frame took, from the start of one frame to the start of the next, and how much
+/// of that went to each phase. What is left over was spent waiting for the next frame.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct FrameTimes {
+    frame_ms: f32,
+    phase_ms: [f32; 3]
+}
+
+/// Measures frame times and phase times, shows them on screen and optionally logs them to a
+/// CSV file. Phases nest: time spent in a phase started inside another is only counted once,
+/// for the inner phase.
+pub struct Profiler {
+    pub visible: bool,
+    target_ms: f32,                     // Frame time at the frame rate the game is capped to.
+    history: VecDeque<FrameTimes>,
+    frame_start_ns: Option<u64>,
+    current: [f32; 3],                  // Phase times of the frame in progress.
+    phases: Vec<Phase>,                 // Phases started and not yet ended, innermost last.
+    phase_mark_ns: u64,                 // When the innermost phase was last charged.
+    log: Option<BufWriter<File>>,
+    frames_logged: u64
+}
+
+impl Profiler {
+
+    pub fn new(target_fps: u32) -> Profiler {
+        return Profiler {
+            visible: false,
+            target_ms: 1000. / target_fps as f32,
+            history: VecDeque::new(),
+            frame_start_ns: None,
+            current: [0.; 3],
+            phases: Vec::new(),
+            phase_mark_ns: 0,
+            log: None,
+            frames_logged: 0
+        };
+    }
+
+    /// Log the time of every frame to a CSV file from now on.
+    pub fn log_to(&mut self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                try!(fs::create_dir_all(dir));
+            }
+        }
+        let mut log = BufWriter::new(try!(File::create(path)));
+        try!(writeln!(log, "frame,frame_ms,update_ms,draw_ms,audio_ms"));
+        self.log = Some(log);
+        self.frames_logged = 0;
+        return Ok(());
+    }
+
+    /// Forget the frame in progress, for instance after play was held up by a menu, so the
+    /// wait does not show up as a slow frame.
+    pub fn restart(&mut self) {
+        self.frame_start_ns = None;
+        self.phases.clear();
+    }
+
+    /// Finish the previous frame, if any, and start timing the next.
+    pub fn begin_frame(&mut self) {
+        let now_ns = clock_ticks::precise_time_ns();
+        if let Some(frame_start_ns) = self.frame_start_ns {
+            let frame = FrameTimes {
+                frame_ms: ns_to_ms(now_ns - frame_start_ns),
+                phase_ms: self.current
+            };
+            self.log_frame(&frame);
+            self.history.push_back(frame);
+            while self.history.len() > HISTORY_FRAMES {
+                self.history.pop_front();
+            }
+        }
+        self.frame_start_ns = Some(now_ns);
+        self.current = [0.; 3];
+        self.phases.clear();
+    }
+
+    pub fn begin_phase(&mut self, phase: Phase) {
+        self.charge_innermost();
+        self.phases.push(phase);
+    }
+
+    pub fn end_phase(&mut self) {
+        self.charge_innermost();
+        self.phases.pop();
+    }
+
+    /// Charge the time since the last mark to the innermost phase.
+    fn charge_innermost(&mut self) {
+        let now_ns = clock_ticks::precise_time_ns();
+        if let Some(phase) = self.phases.last() {
+            self.current[*phase as usize] += ns_to_ms(now_ns - self.phase_mark_ns);
+        }
+        self.phase_mark_ns = now_ns;
+    }
+
+    fn log_frame(&mut self, frame: &FrameTimes) {
+        let mut failed = false;
+        if let Some(ref mut log) = self.log {
+            self.frames_logged += 1;
+            let result = writeln!(log, "{},{:.3},{:.3},{:.3},{:.3}",
+                                  self.frames_logged, frame.frame_ms,
+                                  frame.phase_ms[0], frame.phase_ms[1], frame.phase_ms[2]);
+            if let Err(e) = result {
+                let _ = writeln!(&mut io::stderr(), "Unable to log frame times: {}", e);
+                failed = true;
+            }
+        }
+        if failed {
+            self.log = None;
+        }
+    }
+
+    /// Frames per second over the frames in the history.
+    pub fn fps(&self) -> f32 {
+        let total_ms = self.history.iter().fold(0., |sum, frame| sum + frame.frame_ms);
+        return if total_ms > 0. {self.history.len() as f32 * 1000. / total_ms} else {0.};
+    }
+
+    fn average_ms(&self, phase: Option<Phase>) -> f32 {
+        if self.history.is_empty() {
+            return 0.;
+        }
+        let total_ms = self.history.iter().fold(0., |sum, frame| {
+            sum + match phase {
+                Some(phase) => frame.phase_ms[phase as usize],
+                None => frame.frame_ms
+            }
+        });
+        return total_ms / self.history.len() as f32;
+    }
+
+    /// Variance of the frame times in the history, in square milliseconds. The lower it is,
+    /// the more evenly frames are paced.
+    pub fn frame_time_variance(&self) -> f32 {
+        if self.history.is_empty() {
+            return 0.;
+        }
+        let mean_ms = self.average_ms(None);
+        let total = self.history.iter().fold(0., |sum, frame| {
+            sum + (frame.frame_ms - mean_ms) * (frame.frame_ms - mean_ms)
+        });
+        return total / self.history.len() as f32;
+    }
+
+    /// Draw the frame rate, how evenly frames are paced, the average time of each phase and a
+    /// graph of recent frame times in the top right corner. The graph shows the target frame
+    /// time as a line halfway up.
+    pub fn draw(&self, ui: &mut Ui, font: &ScalableFont, pacing: &str) {
+        let width = HISTORY_FRAMES as f32 + 110.;     // Room for the text beside the graph.
+        let left = ui.width - width - 10.;
+        let (_, line_height) = ui.text_size(font, "0");
+        let line_height = line_height + 2.;
+        let height = line_height * (PHASES.len() + 2) as f32 + GRAPH_HEIGHT + 20.;
+        let (shade_red, shade_green, shade_blue) = ui.theme.shade.rgb();
+        let shade_color = Color::RGBA(shade_red, shade_green, shade_blue, SHADE_ALPHA);
+        let _ = ui.renderer.box_(left as i16, 10, (left + width) as i16, (10. + height) as i16,
+                                 shade_color);
+
+        let text_color = ui.theme.text;
+        let (slow_color, on_time_color, target_color) =
+            (ui.theme.heading, ui.theme.positive, ui.theme.highlight);
+        let mut y = 15.;
+        let summary = format!("{:.0} FPS  {:.2} ms", self.fps(), self.average_ms(None));
+        ui.draw_text(font, &summary, text_color, left + 10., y);
+        y += line_height;
+        let variance = self.frame_time_variance();
+        let pacing = format!("{} jitter {:.2} ms var {:.2}", pacing, variance.sqrt(), variance);
+        ui.draw_text(font, &pacing, text_color, left + 10., y);
+        for phase in PHASES.iter() {
+            y += line_height;
+            let line = format!("{} {:.2} ms", phase.name(), self.average_ms(Some(*phase)));
+            ui.draw_text(font, &line, text_color, left + 10., y);
+        }
+
+        // Frames slower than the target stand out in the heading color.
+        let bottom = (y + line_height + 5. + GRAPH_HEIGHT) as i16;
+        let scale = GRAPH_HEIGHT / (2. * self.target_ms);
+        let mut x = (left + 10.) as i16;
+        for frame in self.history.iter() {
+            let bar = (frame.frame_ms * scale).min(GRAPH_HEIGHT) as i16;
+            let color = if frame.frame_ms > self.target_ms * 1.1 {
+                slow_color
+            } else {
+                on_time_color
+            };
+            let _ = ui.renderer.vline(x, bottom - bar, bottom, color);
+            x += 1;
+        }
+        let target_y = bottom - (GRAPH_HEIGHT / 2.) as i16;
+        let _ = ui.renderer.hline((left + 10.) as i16, (left + 10.) as i16 + HISTORY_FRAMES as i16,
+                                  target_y, target_color);
+    }
+
+}
+
+fn ns_to_ms(ns: u64) -> f32 {
+    return ns as f32 / 1_000_000.;
+}
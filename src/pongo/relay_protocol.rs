@@ -0,0 +1,204 @@
+//! Messages exchanged between game clients and the `pongo-relay` lobby and relay server. The
+//! server binary includes this file directly, so it must not depend on the rest of the game.
+
+/// Prefix on every datagram, shared with the LAN protocol.
+pub const PROTOCOL: &'static str = "PONGO4";
+
+/// Address the client looks for the relay on when none is configured. Matches the default the
+/// server binds to, so running both on one machine needs no setup.
+pub const DEFAULT_RELAY_ADDR: &'static str = "127.0.0.1:47900";
+
+/// Number of letters in a match code.
+pub const CODE_LEN: usize = 4;
+
+/// A match waiting for a second player.
+#[derive(Clone, PartialEq, Debug)]
+pub struct OpenMatch {
+    pub code: String,
+    pub rules: String,      // The host's match rules, passed along as they are.
+    pub host_name: String
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RelayMessage {
+
+    // Client to server.
+    Register { name: String },
+    Host { rules: String },
+    List,
+    Join { code: String },
+    Relay(String),          // A complete game message to pass on to the other player.
+    Bye,
+
+    // Server to client.
+    Registered { id: u32 },
+    Hosting { code: String },
+    Matches(Vec<OpenMatch>),
+    Joined { code: String, rules: String, host_name: String },
+    Paired { guest_name: String },
+    NoMatch { code: String }
+}
+
+impl RelayMessage {
+
+    pub fn encode(&self) -> String {
+        let body = match *self {
+            RelayMessage::Register { ref name } => format!("REGISTER {}", name),
+            RelayMessage::Host { ref rules } => format!("HOST {}", rules),
+            RelayMessage::List => "LIST".to_string(),
+            RelayMessage::Join { ref code } => format!("JOIN_CODE {}", code),
+            RelayMessage::Relay(ref payload) => format!("RELAY {}", payload),
+            RelayMessage::Bye => "BYE".to_string(),
+            RelayMessage::Registered { id } => format!("REGISTERED {}", id),
+            RelayMessage::Hosting { ref code } => format!("HOSTING {}", code),
+            RelayMessage::Matches(ref matches) => {
+                // Host names may contain spaces, so entries are separated by tabs.
+                let entries: Vec<String> = matches.iter()
+                    .map(|m| format!("{} {} {}", m.code, m.rules, m.host_name))
+                    .collect();
+                format!("MATCHES {}", entries.join("\t"))
+            },
+            RelayMessage::Joined { ref code, ref rules, ref host_name } =>
+                format!("JOINED {} {} {}", code, rules, host_name),
+            RelayMessage::Paired { ref guest_name } => format!("PAIRED {}", guest_name),
+            RelayMessage::NoMatch { ref code } => format!("NOMATCH {}", code)
+        };
+        return format!("{} {}", PROTOCOL, body);
+    }
+
+    /// Decode a datagram. Returns None for anything that is not a relay message, including
+    /// ordinary game messages, which clients handle themselves.
+    pub fn decode(line: &str) -> Option<RelayMessage> {
+        let line = line.trim_right_matches(|c| c == '\r' || c == '\n');
+        let mut parts = line.splitn(3, ' ');
+        if parts.next() != Some(PROTOCOL) {
+            return None;
+        }
+        let kind = match parts.next() {
+            Some(kind) => kind,
+            None => return None
+        };
+        let rest = parts.next().unwrap_or("");
+        return match kind {
+            "REGISTER" => Some(RelayMessage::Register { name: rest.to_string() }),
+            "HOST" if !rest.is_empty() && !rest.contains(' ') => {
+                Some(RelayMessage::Host { rules: rest.to_string() })
+            },
+            "LIST" => Some(RelayMessage::List),
+            "JOIN_CODE" => Some(RelayMessage::Join { code: rest.to_uppercase() }),
+            "RELAY" => Some(RelayMessage::Relay(rest.to_string())),
+            "BYE" => Some(RelayMessage::Bye),
+            "REGISTERED" => rest.parse().ok().map(|id| RelayMessage::Registered { id: id }),
+            "HOSTING" => Some(RelayMessage::Hosting { code: rest.to_string() }),
+            "MATCHES" => {
+                let matches = rest.split('\t')
+                    .filter(|entry| !entry.is_empty())
+                    .filter_map(decode_open_match)
+                    .collect();
+                Some(RelayMessage::Matches(matches))
+            },
+            "JOINED" => decode_open_match(rest).map(|m| {
+                RelayMessage::Joined {
+                    code: m.code,
+                    rules: m.rules,
+                    host_name: m.host_name
+                }
+            }),
+            "PAIRED" => Some(RelayMessage::Paired { guest_name: rest.to_string() }),
+            "NOMATCH" => Some(RelayMessage::NoMatch { code: rest.to_string() }),
+            _ => None
+        };
+    }
+
+}
+
+/// Decode "<code> <rules> <host name>".
+fn decode_open_match(entry: &str) -> Option<OpenMatch> {
+    let mut fields = entry.splitn(3, ' ');
+    let code = match fields.next() {
+        Some(code) => code.to_string(),
+        None => return None
+    };
+    let rules = match fields.next() {
+        Some(rules) if !rules.is_empty() => rules.to_string(),
+        _ => return None
+    };
+    let host_name = fields.next().unwrap_or("").to_string();
+    return Some(OpenMatch { code: code, rules: rules, host_name: host_name });
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn round_trip(message: RelayMessage) {
+        assert_eq!(RelayMessage::decode(&message.encode()), Some(message));
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        round_trip(RelayMessage::Register { name: "Ada Lovelace".to_string() });
+        round_trip(RelayMessage::Host { rules: "points:11".to_string() });
+        round_trip(RelayMessage::List);
+        round_trip(RelayMessage::Join { code: "ABCD".to_string() });
+        round_trip(RelayMessage::Relay(format!("{} START", PROTOCOL)));
+        round_trip(RelayMessage::Bye);
+        round_trip(RelayMessage::Registered { id: 7 });
+        round_trip(RelayMessage::Hosting { code: "WXYZ".to_string() });
+        round_trip(RelayMessage::Joined {
+            code: "WXYZ".to_string(),
+            rules: "points:11".to_string(),
+            host_name: "Grace Hopper".to_string()
+        });
+        round_trip(RelayMessage::Paired { guest_name: "Alan".to_string() });
+        round_trip(RelayMessage::NoMatch { code: "QQQQ".to_string() });
+    }
+
+    #[test]
+    fn match_list_keeps_host_names_with_spaces() {
+        let matches = vec![
+            OpenMatch {
+                code: "ABCD".to_string(),
+                rules: "points:11".to_string(),
+                host_name: "Ada Lovelace".to_string()
+            },
+            OpenMatch {
+                code: "EFGH".to_string(),
+                rules: "games:3".to_string(),
+                host_name: String::new()
+            }
+        ];
+        round_trip(RelayMessage::Matches(matches));
+        round_trip(RelayMessage::Matches(Vec::new()));
+    }
+
+    #[test]
+    fn join_code_is_upper_cased() {
+        let line = format!("{} JOIN_CODE abcd", PROTOCOL);
+        assert_eq!(RelayMessage::decode(&line),
+                   Some(RelayMessage::Join { code: "ABCD".to_string() }));
+    }
+
+    #[test]
+    fn line_endings_are_ignored() {
+        let line = format!("{} LIST\r\n", PROTOCOL);
+        assert_eq!(RelayMessage::decode(&line), Some(RelayMessage::List));
+    }
+
+    #[test]
+    fn host_rules_must_be_a_single_word() {
+        assert_eq!(RelayMessage::decode(&format!("{} HOST", PROTOCOL)), None);
+        assert_eq!(RelayMessage::decode(&format!("{} HOST a b", PROTOCOL)), None);
+    }
+
+    #[test]
+    fn other_messages_are_not_relay_messages() {
+        assert_eq!(RelayMessage::decode("PONGO0 LIST"), None);
+        assert_eq!(RelayMessage::decode(&format!("{} START", PROTOCOL)), None);
+        assert_eq!(RelayMessage::decode(&format!("{} REGISTERED x", PROTOCOL)), None);
+        assert_eq!(RelayMessage::decode(&format!("{} JOINED ABCD", PROTOCOL)), None);
+        assert_eq!(RelayMessage::decode(""), None);
+    }
+
+}
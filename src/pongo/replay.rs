@@ -0,0 +1,490 @@
+use pongo::ai_level::{AiLevel, AI_LEVELS};
+use pongo::match_rules::MatchRules;
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// First bytes of every replay file.
+const MAGIC: &'static [u8; 4] = b"PGRP";
+
+/// Bumped whenever the file layout or the simulation changes in a way that would make older
+/// replays play back differently.
+pub const REPLAY_VERSION: u8 = 5;
+
+/// Directory replays are written to when no file name is given.
+pub const REPLAY_DIR: &'static str = "replays";
+
+// Flags that start every tick record, saying which optional fields follow.
+const HAS_LPADDLE_Y: u8 = 0x01;
+const SLOW_MOTION: u8 = 0x02;
+const HAS_RPADDLE_Y: u8 = 0x04;
+const HAS_CHECKSUM: u8 = 0x08;
+const REWIND: u8 = 0x10;
+const END_OF_REPLAY: u8 = 0xff;
+
+/// Everything the player and the network fed into the simulation during one tick. Together
+/// with the seed and config in the replay header, this is enough to repeat a match exactly.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TickInput {
+    pub dt_ms: u32,                 // Time step of the tick.
+    pub lpaddle_y: Option<f32>,     // Where the player moved the left paddle, if it moved.
+    pub slow_motion: bool,          // The player asked for a slow motion turn.
+    pub rpaddle_y: Option<f32>,     // Where a LAN guest moved the right paddle, if it moved.
+    pub rewind: bool                // The player asked to rewind time.
+}
+
+impl TickInput {
+
+    pub fn new(dt_ms: u32) -> TickInput {
+        return TickInput {
+            dt_ms: dt_ms,
+            lpaddle_y: None,
+            slow_motion: false,
+            rpaddle_y: None,
+            rewind: false
+        };
+    }
+
+}
+
+/// The seed and settings a match was played with.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplayHeader {
+    pub seed: [u32; 4],
+    pub width: f32,
+    pub height: f32,
+    pub fps: u32,
+    pub rules: MatchRules,
+    pub slow_motions: u32,
+    pub left_name: String,
+    pub right_name: String,
+    pub right_is_guest: bool,   // A person, at a LAN machine or this one, rather than the
+                                // computer played on the right.
+    pub ai_level: AiLevel       // How well the computer played, if it played.
+}
+
+/// A recorded tick. A checksum of the resulting state is stored every so often, so that
+/// playback can tell when it no longer matches the original match.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TickRecord {
+    pub input: TickInput,
+    pub checksum: Option<u64>
+}
+
+/// Writes a replay to disk as the match is played, so that a crash still leaves everything up
+/// to that point behind.
+pub struct ReplayRecorder {
+    writer: BufWriter<File>,
+    pub path: PathBuf
+}
+
+impl ReplayRecorder {
+
+    pub fn create(path: &Path, header: &ReplayHeader) -> io::Result<ReplayRecorder> {
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                try!(fs::create_dir_all(dir));
+            }
+        }
+        let mut writer = BufWriter::new(try!(File::create(path)));
+        try!(writer.write_all(MAGIC));
+        try!(write_u8(&mut writer, REPLAY_VERSION));
+        for &word in header.seed.iter() {
+            try!(write_u32(&mut writer, word));
+        }
+        try!(write_f32(&mut writer, header.width));
+        try!(write_f32(&mut writer, header.height));
+        try!(write_varint(&mut writer, header.fps as u64));
+        try!(write_string(&mut writer, &header.rules.to_string()));
+        try!(write_varint(&mut writer, header.slow_motions as u64));
+        try!(write_string(&mut writer, &header.left_name));
+        try!(write_string(&mut writer, &header.right_name));
+        try!(write_u8(&mut writer, if header.right_is_guest {1} else {0}));
+        try!(write_u8(&mut writer, header.ai_level as u8));
+        return Ok(ReplayRecorder { writer: writer, path: path.to_path_buf() });
+    }
+
+    /// Create a replay in the replay directory, named after the time the match started.
+    pub fn create_timestamped(header: &ReplayHeader) -> io::Result<ReplayRecorder> {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let path = Path::new(REPLAY_DIR).join(format!("match-{}.pgr", secs));
+        return ReplayRecorder::create(&path, header);
+    }
+
+    pub fn record(&mut self, record: &TickRecord) -> io::Result<()> {
+        let input = &record.input;
+        let mut flags = 0;
+        if input.lpaddle_y.is_some() { flags |= HAS_LPADDLE_Y; }
+        if input.slow_motion { flags |= SLOW_MOTION; }
+        if input.rpaddle_y.is_some() { flags |= HAS_RPADDLE_Y; }
+        if record.checksum.is_some() { flags |= HAS_CHECKSUM; }
+        if input.rewind { flags |= REWIND; }
+        try!(write_u8(&mut self.writer, flags));
+        try!(write_varint(&mut self.writer, input.dt_ms as u64));
+        if let Some(y) = input.lpaddle_y {
+            try!(write_f32(&mut self.writer, y));
+        }
+        if let Some(y) = input.rpaddle_y {
+            try!(write_f32(&mut self.writer, y));
+        }
+        if let Some(checksum) = record.checksum {
+            try!(write_varint(&mut self.writer, checksum));
+        }
+        return Ok(());
+    }
+
+    /// Mark the end of the match and flush everything to disk.
+    pub fn finish(mut self) -> io::Result<()> {
+        try!(write_u8(&mut self.writer, END_OF_REPLAY));
+        return self.writer.flush();
+    }
+
+}
+
+/// A replay read back from disk.
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub ticks: Vec<TickRecord>
+}
+
+impl Replay {
+
+    /// Read a replay. A file that ends early, for instance because the game crashed while it
+    /// was being recorded, yields the ticks up to that point.
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let mut reader = BufReader::new(try!(File::open(path)));
+        let mut magic = [0; 4];
+        try!(reader.read_exact(&mut magic));
+        if &magic != MAGIC {
+            return Err(invalid_data("not a pongo replay"));
+        }
+        let version = try!(read_u8(&mut reader));
+        if version != REPLAY_VERSION {
+            return Err(invalid_data(&format!("replay version {} is not supported", version)));
+        }
+        let mut seed = [0; 4];
+        for word in seed.iter_mut() {
+            *word = try!(read_u32(&mut reader));
+        }
+        let header = ReplayHeader {
+            seed: seed,
+            width: try!(read_f32(&mut reader)),
+            height: try!(read_f32(&mut reader)),
+            fps: try!(read_varint(&mut reader)) as u32,
+            rules: match try!(read_string(&mut reader)).parse() {
+                Ok(rules) => rules,
+                Err(e) => return Err(invalid_data(&e))
+            },
+            slow_motions: try!(read_varint(&mut reader)) as u32,
+            left_name: try!(read_string(&mut reader)),
+            right_name: try!(read_string(&mut reader)),
+            right_is_guest: try!(read_u8(&mut reader)) != 0,
+            ai_level: match AI_LEVELS.get(try!(read_u8(&mut reader)) as usize) {
+                Some(level) => *level,
+                None => return Err(invalid_data("unknown AI level"))
+            }
+        };
+
+        let mut ticks = Vec::new();
+        loop {
+            match read_tick(&mut reader) {
+                Ok(Some(record)) => ticks.push(record),
+                Ok(None) => break,
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e)
+            }
+        }
+        return Ok(Replay { header: header, ticks: ticks });
+    }
+
+}
+
+/// Where the given match of the session is recorded when the player named a file. The first
+/// match keeps the name and later ones are numbered before the extension, so that match.pgr is
+/// followed by match-2.pgr, match-3.pgr and so on.
+pub fn numbered_path(path: &Path, number: u32) -> PathBuf {
+    if number <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, number)
+    };
+    return path.with_file_name(name);
+}
+
+/// The replays in the replay directory, newest first. A missing directory holds none.
+pub fn list_replays() -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(REPLAY_DIR) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e)
+    };
+    let mut replays: Vec<(SystemTime, PathBuf)> = Vec::new();
+    for entry in entries {
+        let entry = try!(entry);
+        let path = entry.path();
+        if path.extension().map_or(false, |extension| extension == "pgr") {
+            let modified = try!(entry.metadata()).modified().unwrap_or(UNIX_EPOCH);
+            replays.push((modified, path));
+        }
+    }
+    replays.sort_by(|a, b| b.cmp(a));
+    return Ok(replays.into_iter().map(|(_, path)| path).collect());
+}
+
+/// Read one tick record, or None at the end of the replay.
+fn read_tick<R: Read>(reader: &mut R) -> io::Result<Option<TickRecord>> {
+    let flags = try!(read_u8(reader));
+    if flags == END_OF_REPLAY {
+        return Ok(None);
+    }
+    let mut input = TickInput::new(try!(read_varint(reader)) as u32);
+    if flags & HAS_LPADDLE_Y != 0 {
+        input.lpaddle_y = Some(try!(read_f32(reader)));
+    }
+    input.slow_motion = flags & SLOW_MOTION != 0;
+    input.rewind = flags & REWIND != 0;
+    if flags & HAS_RPADDLE_Y != 0 {
+        input.rpaddle_y = Some(try!(read_f32(reader)));
+    }
+    let checksum = if flags & HAS_CHECKSUM != 0 {Some(try!(read_varint(reader)))} else {None};
+    return Ok(Some(TickRecord { input: input, checksum: checksum }));
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    return io::Error::new(ErrorKind::InvalidData, msg.to_string());
+}
+
+// All numbers are little endian. Counts and time steps are stored as variable length integers
+// since they are nearly always small.
+
+fn write_u8<W: Write>(writer: &mut W, value: u8) -> io::Result<()> {
+    return writer.write_all(&[value]);
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    let bytes = [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8];
+    return writer.write_all(&bytes);
+}
+
+fn write_f32<W: Write>(writer: &mut W, value: f32) -> io::Result<()> {
+    return write_u32(writer, unsafe { ::std::mem::transmute::<f32, u32>(value) });
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return write_u8(writer, byte);
+        }
+        try!(write_u8(writer, byte | 0x80));
+    }
+}
+
+fn write_string<W: Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    try!(write_varint(writer, value.len() as u64));
+    return writer.write_all(value.as_bytes());
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    try!(reader.read_exact(&mut buf));
+    return Ok(buf[0]);
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    try!(reader.read_exact(&mut buf));
+    return Ok(buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24);
+}
+
+fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    let bits = try!(read_u32(reader));
+    return Ok(unsafe { ::std::mem::transmute::<u32, f32>(bits) });
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = try!(read_u8(reader));
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(invalid_data("malformed number"));
+        }
+    }
+}
+
+/// Read a string written by `write_string`. A corrupt length cannot make us allocate more than
+/// the file actually holds.
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = try!(read_varint(reader));
+    let mut buf = Vec::new();
+    try!(reader.by_ref().take(len).read_to_end(&mut buf));
+    if buf.len() as u64 != len {
+        return Err(io::Error::new(ErrorKind::UnexpectedEof, "text cut short"));
+    }
+    return String::from_utf8(buf).map_err(|_| invalid_data("malformed text"));
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{numbered_path, read_string, read_varint, write_string, write_varint};
+    use super::{Replay, ReplayHeader, ReplayRecorder, TickInput, TickRecord};
+
+    use pongo::ai_level::AiLevel;
+
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Cursor, ErrorKind, Read, Write};
+    use std::path::{Path, PathBuf};
+
+    fn header() -> ReplayHeader {
+        return ReplayHeader {
+            seed: [1, 2, 3, 0xdeadbeef],
+            width: 800.,
+            height: 600.,
+            fps: 60,
+            rules: "11/1/3/1/90".parse().unwrap(),
+            slow_motions: 2,
+            left_name: "Zoë".to_string(),
+            right_name: "Computer".to_string(),
+            right_is_guest: false,
+            ai_level: AiLevel::Hard
+        };
+    }
+
+    /// A tick of every kind: idle, moving either paddle, slowing down, rewinding and the ones
+    /// that carry a checksum.
+    fn ticks() -> Vec<TickRecord> {
+        let mut ticks = Vec::new();
+        for i in 0..120 {
+            let mut input = TickInput::new(16 + i % 2);
+            if i % 3 == 0 { input.lpaddle_y = Some(i as f32 * 2.5); }
+            if i % 5 == 0 { input.rpaddle_y = Some(600. - i as f32); }
+            input.slow_motion = i == 40;
+            input.rewind = i == 90;
+            let checksum = if i % 60 == 59 {Some(!0 - i as u64)} else {None};
+            ticks.push(TickRecord { input: input, checksum: checksum });
+        }
+        return ticks;
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        return env::temp_dir().join(format!("pongo-test-{}.pgr", name));
+    }
+
+    fn record(path: &Path, ticks: &[TickRecord]) {
+        let mut recorder = ReplayRecorder::create(path, &header()).unwrap();
+        for tick in ticks.iter() {
+            recorder.record(tick).unwrap();
+        }
+        recorder.finish().unwrap();
+    }
+
+    fn truncate(path: &Path, len: usize) {
+        let mut bytes = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+        File::create(path).unwrap().write_all(&bytes[..len]).unwrap();
+    }
+
+    #[test]
+    fn replay_survives_a_round_trip() {
+        let path = temp_path("round-trip");
+        record(&path, &ticks());
+        let replay = Replay::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(replay.header, header());
+        assert_eq!(replay.ticks, ticks());
+    }
+
+    #[test]
+    fn truncated_replay_keeps_the_ticks_before_the_cut() {
+        let path = temp_path("truncated");
+        record(&path, &ticks());
+
+        // Losing the end marker and half of the last tick loses that tick alone.
+        let len = fs::metadata(&path).unwrap().len() as usize;
+        truncate(&path, len - 3);
+        let replay = Replay::load(&path).unwrap();
+        assert_eq!(replay.header, header());
+        assert_eq!(replay.ticks, &ticks()[..119]);
+
+        // Without a whole header there is nothing to play.
+        truncate(&path, 20);
+        let err = Replay::load(&path).err().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn other_files_are_not_replays() {
+        let path = temp_path("not-a-replay");
+        File::create(&path).unwrap().write_all(b"PGRQ not a replay").unwrap();
+        let err = Replay::load(&path).err().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn later_matches_are_numbered_beside_the_first() {
+        let path = Path::new("replays/final.pgr");
+        assert_eq!(numbered_path(path, 1), PathBuf::from("replays/final.pgr"));
+        assert_eq!(numbered_path(path, 2), PathBuf::from("replays/final-2.pgr"));
+        assert_eq!(numbered_path(Path::new("final"), 3), PathBuf::from("final-3"));
+    }
+
+    fn varint_round_trip(value: u64) -> u64 {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, value).unwrap();
+        return read_varint(&mut Cursor::new(bytes)).unwrap();
+    }
+
+    #[test]
+    fn varints_survive_a_round_trip() {
+        for &value in [0, 1, 127, 128, 300, 16383, 16384, 0xffffffff, !0].iter() {
+            assert_eq!(varint_round_trip(value), value);
+        }
+    }
+
+    #[test]
+    fn small_varints_take_one_byte() {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 127).unwrap();
+        assert_eq!(bytes, vec![0x7f]);
+    }
+
+    #[test]
+    fn overlong_varint_is_rejected() {
+        let bytes = vec![0xff; 11];
+        let err = read_varint(&mut Cursor::new(bytes)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn strings_survive_a_round_trip() {
+        let mut bytes = Vec::new();
+        write_string(&mut bytes, "Zoë").unwrap();
+        assert_eq!(read_string(&mut Cursor::new(bytes)).unwrap(), "Zoë");
+    }
+
+    #[test]
+    fn string_longer_than_the_file_is_rejected() {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 1 << 40).unwrap();
+        bytes.extend(b"short".iter().cloned());
+        let err = read_string(&mut Cursor::new(bytes)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+}
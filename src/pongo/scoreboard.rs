@@ -0,0 +1,128 @@
+use pongo::match_rules::{MatchRules, MatchScore};
+use pongo::match_stats::Side;
+use pongo::text_renderer::TextStyle;
+use pongo::ui::{Drawable, ScalableFont, Ui};
+use sdl2::pixels::Color;
+use sdl2_gfx::primitives::DrawRenderer;
+
+/// Gap between the net and the points on either side of it.
+const POINTS_GAP: f32 = 30.;
+
+/// How far right and down the shadow under the points falls, and how opaque it is.
+const SHADOW_OFFSET: f32 = 2.;
+const SHADOW_ALPHA: u8 = 0x80;
+
+/// Radius of the dot marking whose serve it is.
+const SERVE_DOT_RADIUS: i16 = 5;
+
+/// Shows where a match stands at the top of the court: the points of the current game on
+/// either side of the net, the games and sets won below them when the rules have more than
+/// one, a dot beside the player who serves and the match clock. Text is drawn at the natural
+/// size of its font, growing outwards from the net, so scores of any width fit. Fonts come
+/// from the theme as it is drawn.
+pub struct Scoreboard {
+    pub colors: [Color; 2],         // Indexed by `Side`.
+    pub center_x: f32,              // Where the net runs.
+    pub y: f32,                     // Top of the points.
+    pub points_size: u16,
+    pub detail_size: u16,           // For games, sets and the clock.
+    pub rules: MatchRules,
+    pub score: MatchScore,
+    pub clock_ms: Option<u64>       // Time left on the clock, if the match has one.
+}
+
+impl Scoreboard {
+
+    pub fn new(left_color: Color,
+               right_color: Color,
+               center_x: f32,
+               y: f32,
+               points_size: u16,
+               detail_size: u16) -> Scoreboard {
+        return Scoreboard {
+            colors: [left_color, right_color],
+            center_x: center_x,
+            y: y,
+            points_size: points_size,
+            detail_size: detail_size,
+            rules: MatchRules::first_to(5),
+            score: MatchScore::new(),
+            clock_ms: None
+        };
+    }
+
+    /// Games and sets won by one side, or an empty string if the rules have neither.
+    fn tally(&self, side: Side) -> String {
+        let mut parts = Vec::new();
+        if self.rules.games_per_set > 1 {
+            parts.push(format!("Games {}", self.score.games[side as usize]));
+        }
+        if self.rules.sets > 1 {
+            parts.push(format!("Sets {}", self.score.sets[side as usize]));
+        }
+        return parts.join("  ");
+    }
+
+    /// The x coordinate text of the given width starts at to sit on one side of the net.
+    fn text_x(&self, side: Side, width: f32) -> f32 {
+        return match side {
+            Side::Left => self.center_x - POINTS_GAP - width,
+            Side::Right => self.center_x + POINTS_GAP
+        };
+    }
+
+}
+
+impl Drawable for Scoreboard {
+
+    fn draw(&self, ui: &mut Ui) {
+        let points_font = ScalableFont::new(&ui.theme.points_font, self.points_size);
+        let detail_font = ScalableFont::new(&ui.theme.detail_font, self.detail_size);
+        let (shade_red, shade_green, shade_blue) = ui.theme.shade.rgb();
+        let shadow_color = Color::RGBA(shade_red, shade_green, shade_blue, SHADOW_ALPHA);
+        let (_, points_height) = ui.text_size(&points_font, "0");
+        let details_y = self.y + points_height;
+        for &side in [Side::Left, Side::Right].iter() {
+            let color = self.colors[side as usize];
+
+            let points = self.score.points[side as usize].to_string();
+            let (width, _) = ui.text_size(&points_font, &points);
+            let x = self.text_x(side, width);
+            let style = TextStyle::plain(color)
+                .shadowed(shadow_color, SHADOW_OFFSET, SHADOW_OFFSET);
+            ui.draw_styled_text(&points_font, &points, &style, x, self.y);
+
+            if self.score.server == side {
+                let dot_x = match side {
+                    Side::Left => x - 3. * SERVE_DOT_RADIUS as f32,
+                    Side::Right => x + width + 3. * SERVE_DOT_RADIUS as f32
+                };
+                let dot_y = self.y + points_height / 2.;
+                let _ = ui.renderer.filled_circle(dot_x as i16, dot_y as i16, SERVE_DOT_RADIUS,
+                                                  color);
+            }
+
+            let tally = self.tally(side);
+            if !tally.is_empty() {
+                let (width, _) = ui.text_size(&detail_font, &tally);
+                let x = self.text_x(side, width);
+                ui.draw_text(&detail_font, &tally, color, x, details_y);
+            }
+        }
+
+        // The clock goes over the net, below the points. Once time is up with the score
+        // level, a warning that the next point wins takes its place.
+        let clock = if self.score.sudden_death {
+            Some(("SUDDEN DEATH".to_string(), ui.theme.heading))
+        } else {
+            let text_color = ui.theme.text;
+            self.clock_ms.map(|ms| (format!("{}:{:02}", ms / 60000, ms / 1000 % 60), text_color))
+        };
+        if let Some((text, color)) = clock {
+            let (width, _) = ui.text_size(&detail_font, &text);
+            let x = self.center_x - width / 2.;
+            ui.draw_text(&detail_font, &text, color, x, details_y);
+        }
+    }
+
+}
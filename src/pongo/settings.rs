@@ -0,0 +1,280 @@
+use pongo::data_dir::{self, DataFile};
+use pongo::frame_pacer::FramePacing;
+use pongo::match_rules::MatchRules;
+use pongo::palette::ColorPalette;
+use pongo::window_mode::{Resolution, WindowMode};
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// First line of the settings file, followed by the version of its layout.
+const SETTINGS_HEADER: &'static str = "pongo-settings";
+const SETTINGS_VERSION: u32 = 1;
+
+/// Smallest and largest percent of their size the ball and paddles can be drawn at.
+pub const MIN_OBJECT_SCALE: u32 = 100;
+pub const MAX_OBJECT_SCALE: u32 = 200;
+
+/// Preferences that apply whoever is playing, saved in the data directory. Each is written on
+/// its own line as a key and a value separated by a tab. Unknown keys and values that cannot
+/// be read are skipped, so a setting left out keeps its default.
+pub struct Settings {
+    pub frame_pacing: FramePacing,
+    pub window_mode: WindowMode,
+    pub display: u32,               // Index of the display to show the game on.
+    pub resolution: Resolution,     // Size of the window, or of the display in fullscreen.
+    pub volume: u32,                // Percent of full volume sounds and music play at.
+    pub title_music: bool,          // Music plays on the title screen.
+    pub theme: String,              // Name of the theme the game is drawn with.
+    pub palette: ColorPalette,      // Colors the players and the HUD are told apart by.
+    pub shape_cues: bool,           // Patterns and shapes tell things apart as well as color.
+    pub object_scale: u32,          // Percent of their size the ball and paddles are drawn at.
+    pub reduced_motion: bool,       // Skip the instant replays and the playback of rewinds.
+    pub rules: MatchRules,          // How matches hosted or played here are won.
+    pub allow_spectators: bool,     // Others on the LAN may watch the matches simulated here.
+    file: DataFile
+}
+
+impl Settings {
+
+    /// The settings file in the data directory.
+    pub fn default_path() -> PathBuf {
+        return data_dir::data_dir().join("settings.txt");
+    }
+
+    fn defaults(path: &Path) -> Settings {
+        return Settings {
+            frame_pacing: FramePacing::Limited,
+            window_mode: WindowMode::Windowed,
+            display: 0,
+            resolution: Resolution::new(800, 600),
+            volume: 100,
+            title_music: true,
+            theme: "classic".to_string(),
+            palette: ColorPalette::Theme,
+            shape_cues: false,
+            object_scale: 100,
+            reduced_motion: false,
+            rules: MatchRules::first_to(5),
+            allow_spectators: false,
+            file: DataFile::new(path, SETTINGS_HEADER, SETTINGS_VERSION)
+        };
+    }
+
+    /// Read the settings. A missing file means nothing has been changed yet.
+    pub fn load(path: &Path) -> io::Result<Settings> {
+        let mut settings = Settings::defaults(path);
+        let lines = try!(settings.file.read_lines()).unwrap_or(Vec::new());
+        for line in lines.iter() {
+            let mut fields = line.splitn(2, '\t');
+            let (key, value) = match (fields.next(), fields.next()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => continue
+            };
+            match key {
+                "frame_pacing" => {
+                    if let Ok(pacing) = value.parse() {
+                        settings.frame_pacing = pacing;
+                    }
+                },
+                "window_mode" => {
+                    if let Ok(mode) = value.parse() {
+                        settings.window_mode = mode;
+                    }
+                },
+                "display" => {
+                    if let Ok(display) = value.parse() {
+                        settings.display = display;
+                    }
+                },
+                "resolution" => {
+                    if let Ok(resolution) = value.parse() {
+                        settings.resolution = resolution;
+                    }
+                },
+                "volume" => {
+                    if let Ok(volume) = value.parse::<u32>() {
+                        settings.volume = volume.min(100);
+                    }
+                },
+                "title_music" => settings.title_music = value == "on",
+                "theme" => {
+                    if !value.is_empty() {
+                        settings.theme = value.to_string();
+                    }
+                },
+                "palette" => {
+                    if let Ok(palette) = value.parse() {
+                        settings.palette = palette;
+                    }
+                },
+                "shape_cues" => settings.shape_cues = value == "on",
+                "object_scale" => {
+                    if let Ok(scale) = value.parse::<u32>() {
+                        settings.object_scale = scale.max(MIN_OBJECT_SCALE).min(MAX_OBJECT_SCALE);
+                    }
+                },
+                "reduced_motion" => settings.reduced_motion = value == "on",
+                "rules" => {
+                    if let Ok(rules) = value.parse() {
+                        settings.rules = rules;
+                    }
+                },
+                "spectators" => settings.allow_spectators = value == "on",
+                _ => {}
+            }
+        }
+        return Ok(settings);
+    }
+
+    /// Read the settings from the data directory. Problems are reported on stderr, and the
+    /// game carries on with default settings that are not saved.
+    pub fn load_or_default() -> Settings {
+        let path = Settings::default_path();
+        return match Settings::load(&path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(), "Unable to load settings from {}: {}",
+                                 path.display(), e);
+                let mut settings = Settings::defaults(&path);
+                settings.file = DataFile::new(&path, SETTINGS_HEADER, SETTINGS_VERSION).read_only();
+                settings
+            }
+        };
+    }
+
+    /// Write the settings to disk, replacing the file in one go.
+    pub fn save(&self) -> io::Result<()> {
+        let on_off = |on: bool| if on {"on"} else {"off"};
+        let lines = vec![
+            format!("frame_pacing\t{}", self.frame_pacing.name()),
+            format!("window_mode\t{}", self.window_mode.name()),
+            format!("display\t{}", self.display),
+            format!("resolution\t{}", self.resolution),
+            format!("volume\t{}", self.volume),
+            format!("title_music\t{}", on_off(self.title_music)),
+            format!("theme\t{}", self.theme),
+            format!("palette\t{}", self.palette.name()),
+            format!("shape_cues\t{}", on_off(self.shape_cues)),
+            format!("object_scale\t{}", self.object_scale),
+            format!("reduced_motion\t{}", on_off(self.reduced_motion)),
+            format!("rules\t{}", self.rules),
+            format!("spectators\t{}", on_off(self.allow_spectators))
+        ];
+        return self.file.write_lines(&lines);
+    }
+
+    /// Save, reporting any problem on stderr.
+    pub fn save_or_report(&self) {
+        if let Err(e) = self.save() {
+            let _ = writeln!(&mut io::stderr(), "Unable to save settings to {}: {}",
+                             self.file.path().display(), e);
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Settings, MAX_OBJECT_SCALE};
+
+    use pongo::frame_pacer::FramePacing;
+    use pongo::match_rules::MatchRules;
+    use pongo::palette::ColorPalette;
+    use pongo::window_mode::{Resolution, WindowMode};
+
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        return env::temp_dir().join(format!("pongo-test-{}.txt", name));
+    }
+
+    #[test]
+    fn a_missing_file_holds_the_defaults() {
+        let path = temp_path("settings-missing");
+        let _ = fs::remove_file(&path);
+        let settings = Settings::load(&path).unwrap();
+        assert_eq!(settings.frame_pacing, FramePacing::Limited);
+        assert_eq!(settings.resolution, Resolution::new(800, 600));
+        assert_eq!(settings.volume, 100);
+        assert_eq!(settings.theme, "classic");
+        assert_eq!(settings.rules, MatchRules::first_to(5));
+        assert!(settings.title_music && !settings.allow_spectators);
+    }
+
+    #[test]
+    fn settings_survive_a_round_trip() {
+        let path = temp_path("settings-round-trip");
+        let mut settings = Settings::load(&path).unwrap();
+        settings.frame_pacing = FramePacing::VSync;
+        settings.window_mode = WindowMode::Borderless;
+        settings.display = 1;
+        settings.resolution = Resolution::new(1920, 1080);
+        settings.volume = 35;
+        settings.title_music = false;
+        settings.theme = "high-contrast".to_string();
+        settings.palette = ColorPalette::Tritanopia;
+        settings.shape_cues = true;
+        settings.object_scale = 150;
+        settings.reduced_motion = true;
+        settings.rules = "11/1/3/3/300".parse().unwrap();
+        settings.allow_spectators = true;
+        settings.save().unwrap();
+
+        let loaded = Settings::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.frame_pacing, settings.frame_pacing);
+        assert_eq!(loaded.window_mode, settings.window_mode);
+        assert_eq!(loaded.display, settings.display);
+        assert_eq!(loaded.resolution, settings.resolution);
+        assert_eq!(loaded.volume, settings.volume);
+        assert_eq!(loaded.title_music, settings.title_music);
+        assert_eq!(loaded.theme, settings.theme);
+        assert_eq!(loaded.palette, settings.palette);
+        assert_eq!(loaded.shape_cues, settings.shape_cues);
+        assert_eq!(loaded.object_scale, settings.object_scale);
+        assert_eq!(loaded.reduced_motion, settings.reduced_motion);
+        assert_eq!(loaded.rules, settings.rules);
+        assert_eq!(loaded.allow_spectators, settings.allow_spectators);
+    }
+
+    #[test]
+    fn bad_values_keep_their_defaults_and_numbers_are_clamped() {
+        let path = temp_path("settings-bad-values");
+        {
+            let mut file = File::create(&path).unwrap();
+            writeln!(file, "pongo-settings 1").unwrap();
+            writeln!(file, "frame_pacing\tturbo").unwrap();
+            writeln!(file, "resolution\t1280 by 720").unwrap();
+            writeln!(file, "rules\t0/0/1/1/0").unwrap();
+            writeln!(file, "theme\t").unwrap();
+            writeln!(file, "volume\t250").unwrap();
+            writeln!(file, "object_scale\t1000").unwrap();
+            writeln!(file, "sparkles\ton").unwrap();
+            writeln!(file, "reduced_motion").unwrap();
+        }
+        let settings = Settings::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(settings.frame_pacing, FramePacing::Limited);
+        assert_eq!(settings.resolution, Resolution::new(800, 600));
+        assert_eq!(settings.rules, MatchRules::first_to(5));
+        assert_eq!(settings.theme, "classic");
+        assert_eq!(settings.volume, 100);
+        assert_eq!(settings.object_scale, MAX_OBJECT_SCALE);
+        assert!(!settings.reduced_motion);
+    }
+
+    #[test]
+    fn files_of_other_versions_are_not_read() {
+        let path = temp_path("settings-other-version");
+        File::create(&path).unwrap().write_all(b"pongo-settings 2\nvolume\t10\n").unwrap();
+        let result = Settings::load(&path);
+        let _ = fs::remove_file(&path);
+        assert!(result.is_err());
+    }
+
+}
@@ -0,0 +1,293 @@
+use pongo::ai_level::{AiLevel, AI_LEVELS};
+use pongo::frame_pacer::FRAME_PACINGS;
+use pongo::layout::{Align, Bounds, Element};
+use pongo::match_rules::MatchRules;
+use pongo::menu::{self, Menu, MenuEvent, Widget};
+use pongo::palette::COLOR_PALETTES;
+use pongo::profile::{ProfileStore, CONTROLS};
+use pongo::settings::{Settings, MAX_OBJECT_SCALE, MIN_OBJECT_SCALE};
+use pongo::theme::Theme;
+use pongo::ui::{ScalableFont, Ui};
+use pongo::window_mode::{Resolution, WINDOW_MODES};
+
+use sdl2::event::Event;
+
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::thread;
+
+/// Pages of settings, in the order they are listed.
+const PAGES: &'static [&'static str] = &["Video", "Audio", "Controls", "Gameplay",
+                                         "Accessibility"];
+
+/// Time limits a match can be played to, in seconds. None plays without one.
+const TIME_LIMITS: [Option<u32>; 6] = [None, Some(60), Some(120), Some(180), Some(300), Some(600)];
+
+/// Let the player change the settings, a page at a time. Every change applies and is saved
+/// straight away. Returns when the player backs out or closes the window.
+pub fn show(ui: &mut Ui, settings: &mut Settings, profiles: &mut ProfileStore,
+            ai_level: &mut AiLevel, rules: &mut MatchRules) {
+    let pages: Vec<String> = PAGES.iter().map(|page| page.to_string()).collect();
+    loop {
+        match menu::choose(ui, "Settings", &pages, "") {
+            Some(0) => show_video(ui, settings),
+            Some(1) => show_audio(ui, settings),
+            Some(2) => show_controls(ui, profiles),
+            Some(3) => show_gameplay(ui, settings, ai_level, rules),
+            Some(4) => show_accessibility(ui, settings),
+            _ => return
+        }
+    }
+}
+
+/// Window mode, display, resolution, frame pacing and theme.
+fn show_video(ui: &mut Ui, settings: &mut Settings) {
+    let mode_names = WINDOW_MODES.iter().map(|mode| mode.name().to_string()).collect();
+    let display_names = ui.display_names().unwrap_or(Vec::new());
+    let display = settings.display as usize;
+    let (resolution_names, resolution) = resolution_options(ui, settings);
+    let pacing_names = FRAME_PACINGS.iter().map(|pacing| pacing.name().to_string()).collect();
+    let theme_names = theme_names(settings);
+    let theme = theme_names.iter().position(|name| *name == settings.theme).unwrap_or(0);
+    let items = vec![
+        Widget::list("Window", mode_names, settings.window_mode as usize),
+        Widget::list("Display", display_names, display),
+        Widget::list("Resolution", resolution_names, resolution),
+        Widget::list("Frame pacing", pacing_names, settings.frame_pacing as usize),
+        Widget::list("Theme", theme_names.clone(), theme)
+    ];
+    run_page(ui, "Video", items, "VSync is turned on or off at the next start",
+             |ui, menu, line| {
+        match line {
+            0 => settings.window_mode = WINDOW_MODES[menu.items[0].value() as usize],
+            1 => {
+                // Each display has its own resolutions.
+                settings.display = menu.items[1].value();
+                let (names, resolution) = resolution_options(ui, settings);
+                menu.items[2] = Widget::list("Resolution", names, resolution);
+            },
+            2 => {
+                let (names, _) = resolution_options(ui, settings);
+                if let Some(resolution) = names.get(menu.items[2].value() as usize) {
+                    if let Ok(resolution) = resolution.parse() {
+                        settings.resolution = resolution;
+                    }
+                }
+            },
+            3 => {
+                settings.frame_pacing = FRAME_PACINGS[menu.items[3].value() as usize];
+                settings.save_or_report();
+                return;
+            },
+            _ => {
+                // Takes effect from the next frame drawn, this page included.
+                let name = &theme_names[menu.items[4].value() as usize];
+                match ui.set_theme(name) {
+                    Ok(()) => {
+                        settings.theme = name.clone();
+                        settings.save_or_report();
+                    },
+                    Err(e) => {
+                        let _ = writeln!(&mut io::stderr(), "Unable to load theme {}: {}",
+                                         name, e);
+                    }
+                }
+                return;
+            }
+        }
+        settings.save_or_report();
+        let (mode, display, resolution) =
+            (settings.window_mode, settings.display, settings.resolution);
+        if let Err(e) = ui.set_window_mode(mode, display, resolution) {
+            let _ = writeln!(&mut io::stderr(), "Unable to change the window: {}", e);
+        }
+    });
+}
+
+/// The resolutions of the chosen display, and which of them is chosen. One not supported by
+/// the display, such as a window size set by hand, is listed first.
+fn resolution_options(ui: &Ui, settings: &Settings) -> (Vec<String>, usize) {
+    let mut resolutions: Vec<Resolution> = ui.resolutions(settings.display)
+        .unwrap_or(Vec::new());
+    if !resolutions.contains(&settings.resolution) {
+        resolutions.insert(0, settings.resolution);
+    }
+    let selected = resolutions.iter()
+        .position(|resolution| *resolution == settings.resolution)
+        .unwrap_or(0);
+    return (resolutions.iter().map(|resolution| resolution.to_string()).collect(), selected);
+}
+
+/// The themes in the theme directory, and the one in the settings if its file has gone.
+fn theme_names(settings: &Settings) -> Vec<String> {
+    let mut names = Theme::names();
+    if !names.contains(&settings.theme) {
+        names.push(settings.theme.clone());
+    }
+    return names;
+}
+
+/// Volume and title music.
+fn show_audio(ui: &mut Ui, settings: &mut Settings) {
+    let items = vec![
+        Widget::slider("Volume", settings.volume, 0, 100, 10),
+        Widget::toggle("Title music", settings.title_music)
+    ];
+    run_page(ui, "Audio", items, "", |ui, menu, line| {
+        match line {
+            0 => {
+                settings.volume = menu.items[0].value();
+                ui.set_volume(settings.volume);
+            },
+            _ => settings.title_music = menu.items[1].is_on()
+        }
+        settings.save_or_report();
+    });
+}
+
+/// Who is playing and how they move their paddle.
+fn show_controls(ui: &mut Ui, profiles: &mut ProfileStore) {
+    let profile_names = profiles.profiles.iter().map(|profile| profile.name.clone()).collect();
+    let items = vec![
+        Widget::list("Profile", profile_names, profiles.selected),
+        Widget::list("Controls", control_names(), controls_index(profiles))
+    ];
+    run_page(ui, "Controls", items,
+             "In local versus, the second player uses W and S or a controller",
+             |_, menu, line| {
+        match line {
+            0 => {
+                profiles.selected = menu.items[0].value() as usize;
+                menu.items[1] = Widget::list("Controls", control_names(),
+                                             controls_index(profiles));
+            },
+            _ => profiles.selected_mut().controls = CONTROLS[menu.items[1].value() as usize]
+        }
+        profiles.save_or_report();
+    });
+}
+
+fn control_names() -> Vec<String> {
+    return CONTROLS.iter().map(|controls| controls.name().to_string()).collect();
+}
+
+fn controls_index(profiles: &ProfileStore) -> usize {
+    let controls = profiles.selected().controls;
+    return CONTROLS.iter().position(|c| *c == controls).unwrap_or(0);
+}
+
+/// The rules matches are played to, how well the computer plays and whether others may watch.
+fn show_gameplay(ui: &mut Ui, settings: &mut Settings, ai_level: &mut AiLevel,
+                 rules: &mut MatchRules) {
+    let mut time_limits: Vec<Option<u32>> = TIME_LIMITS.to_vec();
+    if !time_limits.contains(&rules.time_limit_secs) {
+        time_limits.push(rules.time_limit_secs);
+    }
+    let time_limit_names = time_limits.iter().map(|&limit| describe_time_limit(limit)).collect();
+    let time_limit = time_limits.iter().position(|&limit| limit == rules.time_limit_secs)
+        .unwrap_or(0);
+    let level_names = AI_LEVELS.iter().map(|level| level.name().to_string()).collect();
+    let items = vec![
+        Widget::slider("Points per game", rules.points_per_game, 1, 21, 1),
+        Widget::toggle("Win by two", rules.win_by_two),
+        Widget::slider("Games per set", rules.games_per_set, 1, 9, 2),
+        Widget::slider("Sets", rules.sets, 1, 7, 2),
+        Widget::list("Time limit", time_limit_names, time_limit),
+        Widget::list("Computer level", level_names, *ai_level as usize),
+        Widget::toggle("Allow spectators", settings.allow_spectators)
+    ];
+    run_page(ui, "Gameplay", items, "Spectators can watch from anywhere on the LAN",
+             |_, menu, line| {
+        let value = menu.items[line].value();
+        match line {
+            0 => rules.points_per_game = value,
+            1 => rules.win_by_two = menu.items[1].is_on(),
+            2 => rules.games_per_set = value,
+            3 => rules.sets = value,
+            4 => rules.time_limit_secs = time_limits[value as usize],
+            5 => {
+                *ai_level = AI_LEVELS[value as usize];
+                return;
+            },
+            _ => settings.allow_spectators = menu.items[6].is_on()
+        }
+        settings.rules = *rules;
+        settings.save_or_report();
+    });
+}
+
+/// Colors, shapes, sizes and motion, to make the game easier to see and to follow.
+fn show_accessibility(ui: &mut Ui, settings: &mut Settings) {
+    let palette_names = COLOR_PALETTES.iter().map(|palette| palette.name().to_string()).collect();
+    let items = vec![
+        Widget::list("Color palette", palette_names, settings.palette as usize),
+        Widget::toggle("Shape cues", settings.shape_cues),
+        Widget::slider("Ball and paddle size", settings.object_scale, MIN_OBJECT_SCALE,
+                       MAX_OBJECT_SCALE, 25),
+        Widget::toggle("Reduced motion", settings.reduced_motion)
+    ];
+    run_page(ui, "Accessibility", items,
+             "Sizes change how the ball and paddles look, not how they play",
+             |_, menu, line| {
+        match line {
+            0 => settings.palette = COLOR_PALETTES[menu.items[0].value() as usize],
+            1 => settings.shape_cues = menu.items[1].is_on(),
+            2 => settings.object_scale = menu.items[2].value(),
+            _ => settings.reduced_motion = menu.items[3].is_on()
+        }
+        settings.save_or_report();
+    });
+}
+
+fn describe_time_limit(limit: Option<u32>) -> String {
+    return match limit {
+        None => "off".to_string(),
+        Some(secs) if secs % 60 == 0 => format!("{} min", secs / 60),
+        Some(secs) => format!("{} s", secs)
+    };
+}
+
+/// Show a page of settings under a heading, with a way back at the end and an optional note
+/// at the bottom of the screen. Calls `changed` with the line of each setting that changes.
+/// Closing the window backs out, and `show` follows, since `menu::choose` returns straight
+/// away once a quit has been asked for.
+fn run_page<F>(ui: &mut Ui, heading: &str, mut items: Vec<Widget>,
+               note: &str, mut changed: F) where F: FnMut(&mut Ui, &mut Menu, usize) {
+    items.push(Widget::button("Back"));
+    let mut menu = Menu::new(items, 140.);
+
+    loop {
+        while let Some(event) = ui.poll_event() {
+            if let Event::Quit {..} = event {
+                return;
+            }
+            match menu.handle_event(ui, &event) {
+                MenuEvent::Changed(line) => changed(ui, &mut menu, line),
+                MenuEvent::Activated(..) | MenuEvent::Back => return,
+                MenuEvent::Nothing => {}
+            }
+        }
+
+        // The theme can change on this very page, so its fonts and colors are looked up as
+        // each frame is drawn.
+        let heading_font = Rc::new(ScalableFont::new(&ui.theme.heading_font, 39));
+        let text_font = Rc::new(ScalableFont::new(&ui.theme.text_font, 22));
+        let note_font = Rc::new(ScalableFont::new(&ui.theme.text_font, 18));
+        let (background, heading_color, text_color) =
+            (ui.theme.background, ui.theme.heading, ui.theme.text);
+        ui.renderer.set_draw_color(background);
+        ui.renderer.clear();
+        let heading_area = Bounds::new(0., 60., ui.width, 0.);
+        Element::label(heading, &heading_font, heading_color)
+            .draw_in(ui, heading_area, Align::Center, Align::Start);
+        menu.draw(ui, &text_font);
+        if !note.is_empty() {
+            let note_area = Bounds::new(0., ui.height - 60., ui.width, 0.);
+            Element::label(note, &note_font, text_color)
+                .draw_in(ui, note_area, Align::Center, Align::Start);
+        }
+        ui.renderer.present();
+
+        thread::sleep_ms(50);
+    }
+}
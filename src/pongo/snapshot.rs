@@ -0,0 +1,171 @@
+use pongo::match_rules::MatchScore;
+use pongo::match_stats::Side;
+
+use std::str::FromStr;
+
+/// Everything needed to put the simulation back into the state it was in at a given tick, or
+/// to draw that state on another machine.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub ball_x: f32,
+    pub ball_y: f32,
+    pub ball_vx: f32,
+    pub ball_vy: f32,
+    pub ball_speed_multiplier: f32,
+    pub lpaddle_y: f32,
+    pub rpaddle_y: f32,
+    pub rpaddle_speed_multiplier: f32,
+    pub score: MatchScore,
+    pub clock_ms: u64,              // Simulated time since the match began.
+    pub slow_motions_remaining: u32,
+    pub slow_motion_active: bool,
+    pub rewinds_remaining: u32
+}
+
+impl Snapshot {
+
+    /// Encode as a single line of space separated fields. Floats are written in their shortest
+    /// round trip representation, so decoding yields exactly the same values.
+    pub fn encode(&self) -> String {
+        let score = &self.score;
+        let winner = match score.winner {
+            None => 0,
+            Some(Side::Left) => 1,
+            Some(Side::Right) => 2
+        };
+        return format!("{} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} \
+                        {} {} {} {} {} {} {} {} {} {} {} {} {}",
+                       self.tick,
+                       self.ball_x,
+                       self.ball_y,
+                       self.ball_vx,
+                       self.ball_vy,
+                       self.ball_speed_multiplier,
+                       self.lpaddle_y,
+                       self.rpaddle_y,
+                       self.rpaddle_speed_multiplier,
+                       score.points[0],
+                       score.points[1],
+                       score.games[0],
+                       score.games[1],
+                       score.sets[0],
+                       score.sets[1],
+                       if score.sudden_death {1} else {0},
+                       score.server as u8,
+                       winner,
+                       self.clock_ms,
+                       self.slow_motions_remaining,
+                       if self.slow_motion_active {1} else {0},
+                       self.rewinds_remaining);
+    }
+
+    /// Decode a line produced by `encode`. Returns None if the line is malformed.
+    pub fn decode(line: &str) -> Option<Snapshot> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 22 {
+            return Option::None;
+        }
+        let score = MatchScore {
+            points: [try_opt!(parse(fields[9])), try_opt!(parse(fields[10]))],
+            games: [try_opt!(parse(fields[11])), try_opt!(parse(fields[12]))],
+            sets: [try_opt!(parse(fields[13])), try_opt!(parse(fields[14]))],
+            sudden_death: try_opt!(parse::<u8>(fields[15])) != 0,
+            server: if try_opt!(parse::<u8>(fields[16])) == 0 {Side::Left} else {Side::Right},
+            winner: match try_opt!(parse::<u8>(fields[17])) {
+                0 => None,
+                1 => Some(Side::Left),
+                2 => Some(Side::Right),
+                _ => return None
+            }
+        };
+        return Some(Snapshot {
+            tick: try_opt!(parse(fields[0])),
+            ball_x: try_opt!(parse(fields[1])),
+            ball_y: try_opt!(parse(fields[2])),
+            ball_vx: try_opt!(parse(fields[3])),
+            ball_vy: try_opt!(parse(fields[4])),
+            ball_speed_multiplier: try_opt!(parse(fields[5])),
+            lpaddle_y: try_opt!(parse(fields[6])),
+            rpaddle_y: try_opt!(parse(fields[7])),
+            rpaddle_speed_multiplier: try_opt!(parse(fields[8])),
+            score: score,
+            clock_ms: try_opt!(parse(fields[18])),
+            slow_motions_remaining: try_opt!(parse(fields[19])),
+            slow_motion_active: try_opt!(parse::<u8>(fields[20])) != 0,
+            rewinds_remaining: try_opt!(parse(fields[21]))
+        });
+    }
+
+}
+
+fn parse<T: FromStr>(field: &str) -> Option<T> {
+    return field.parse::<T>().ok();
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pongo::match_rules::MatchScore;
+    use pongo::match_stats::Side;
+
+    fn snapshot() -> Snapshot {
+        let mut score = MatchScore::new();
+        score.points = [3, 7];
+        score.games = [1, 0];
+        score.sets = [0, 2];
+        score.sudden_death = true;
+        score.server = Side::Right;
+        score.winner = Some(Side::Right);
+        return Snapshot {
+            tick: 123456,
+            ball_x: 400.33334,
+            ball_y: -0.000001,
+            ball_vx: -499.99997,
+            ball_vy: 1e-7,
+            ball_speed_multiplier: 1.1,
+            lpaddle_y: 270.,
+            rpaddle_y: 0.1,
+            rpaddle_speed_multiplier: 0.5,
+            score: score,
+            clock_ms: 61000,
+            slow_motions_remaining: 0,
+            slow_motion_active: true,
+            rewinds_remaining: 2
+        };
+    }
+
+    #[test]
+    fn snapshot_survives_a_round_trip_exactly() {
+        let snapshot = snapshot();
+        assert_eq!(Snapshot::decode(&snapshot.encode()), Some(snapshot));
+
+        let mut start = snapshot;
+        start.score = MatchScore::new();
+        assert_eq!(Snapshot::decode(&start.encode()), Some(start));
+    }
+
+    #[test]
+    fn truncated_or_padded_lines_are_rejected() {
+        let line = snapshot().encode();
+        let fields: Vec<&str> = line.split(' ').collect();
+        assert_eq!(Snapshot::decode(&fields[..21].join(" ")), None);
+        assert_eq!(Snapshot::decode(&format!("{} 0", line)), None);
+        assert_eq!(Snapshot::decode(""), None);
+    }
+
+    #[test]
+    fn garbage_fields_are_rejected() {
+        let line = snapshot().encode();
+        for i in 0..22 {
+            let mut fields: Vec<&str> = line.split(' ').collect();
+            fields[i] = "x";
+            assert_eq!(Snapshot::decode(&fields.join(" ")), None);
+        }
+        let mut fields: Vec<&str> = line.split(' ').collect();
+        fields[17] = "3";
+        assert_eq!(Snapshot::decode(&fields.join(" ")), None);
+    }
+
+}
@@ -0,0 +1,212 @@
+extern crate clock_ticks;
+
+use pongo::lan::{Endpoint, HostAnnouncer, Message, PEER_TIMEOUT_MS};
+use pongo::match_rules::MatchRules;
+use pongo::snapshot::Snapshot;
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+
+/// Number of snapshots a spectator holds back before drawing them. Snapshots arrive at
+/// uneven intervals, so a small reserve lets playback advance one tick per frame regardless.
+const DELAY_TICKS: usize = 4;
+
+/// Lets spectators on the LAN watch a match that is being simulated on this machine. The match
+/// is announced so spectators can find it, and every tick is sent to everyone watching.
+pub struct SpectatorServer {
+    endpoint: Endpoint,
+    announcer: HostAnnouncer,
+    spectators: Vec<(SocketAddr, u64)>,     // Address and time last heard from.
+    time_count_last_sent_ms: u64
+}
+
+impl SpectatorServer {
+
+    pub fn new(host_name: &str, rules: MatchRules) -> io::Result<SpectatorServer> {
+        let endpoint = try!(Endpoint::bind(0));
+        let mut announcer = HostAnnouncer::new(host_name, rules);
+        announcer.in_progress = true;
+        return Ok(SpectatorServer {
+            endpoint: endpoint,
+            announcer: announcer,
+            spectators: Vec::new(),
+            time_count_last_sent_ms: 0
+        });
+    }
+
+    /// Announce the match, take in spectators who have started or stopped watching and drop
+    /// those that have gone quiet. Spectators repeat their request to watch every second.
+    pub fn update(&mut self) {
+        let now_ms = clock_ticks::precise_time_ms();
+        self.announcer.announce_if_due(&self.endpoint);
+        while let Some((message, addr)) = self.endpoint.recv() {
+            match message {
+                Message::Watch => {
+                    match self.spectators.iter().position(|&(a, _)| a == addr) {
+                        Some(i) => self.spectators[i].1 = now_ms,
+                        None => self.spectators.push((addr, now_ms))
+                    }
+                },
+                Message::Leave => self.spectators.retain(|&(a, _)| a != addr),
+                _ => {}
+            }
+        }
+        self.spectators.retain(|&(_, last_heard_ms)| now_ms - last_heard_ms <= PEER_TIMEOUT_MS);
+
+        // Spectators show how many are watching along with the players.
+        if now_ms - self.time_count_last_sent_ms >= 1000 {
+            let count = Message::Spectators(self.count());
+            for &(addr, _) in self.spectators.iter() {
+                self.endpoint.send(&count, addr);
+            }
+            self.time_count_last_sent_ms = now_ms;
+        }
+    }
+
+    /// Send the state of a tick to everyone watching.
+    pub fn send_snapshot(&self, snapshot: &Snapshot) {
+        let message = Message::State(*snapshot);
+        for &(addr, _) in self.spectators.iter() {
+            self.endpoint.send(&message, addr);
+        }
+    }
+
+    /// Tell everyone watching that the match is over.
+    pub fn close(&self) {
+        for &(addr, _) in self.spectators.iter() {
+            self.endpoint.send(&Message::Leave, addr);
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        return self.spectators.len() as u32;
+    }
+
+}
+
+/// Smooths out the arrival of snapshots on the spectator's side. Snapshots are held back by a
+/// few ticks, then played out one per frame in tick order.
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+    last_played_tick: Option<u64>
+}
+
+impl SnapshotBuffer {
+
+    pub fn new() -> SnapshotBuffer {
+        return SnapshotBuffer { snapshots: VecDeque::new(), last_played_tick: None };
+    }
+
+    /// Add a received snapshot. Duplicates and snapshots for ticks already played are dropped.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.last_played_tick.map_or(false, |tick| snapshot.tick <= tick) {
+            return;
+        }
+        let i = self.snapshots.iter().position(|s| s.tick >= snapshot.tick)
+            .unwrap_or(self.snapshots.len());
+        if self.snapshots.get(i).map_or(false, |s| s.tick == snapshot.tick) {
+            return;
+        }
+        self.snapshots.insert(i, snapshot);
+    }
+
+    /// The next snapshot to draw, or None to keep drawing the previous one. Playback waits
+    /// until the reserve has built up, and catches up if it falls too far behind.
+    pub fn next(&mut self) -> Option<Snapshot> {
+        if self.snapshots.len() <= DELAY_TICKS {
+            return None;
+        }
+        while self.snapshots.len() > DELAY_TICKS * 2 {
+            self.snapshots.pop_front();
+        }
+        let snapshot = self.snapshots.pop_front();
+        if let Some(ref snapshot) = snapshot {
+            self.last_played_tick = Some(snapshot.tick);
+        }
+        return snapshot;
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pongo::match_rules::MatchScore;
+    use pongo::snapshot::Snapshot;
+
+    fn snapshot(tick: u64) -> Snapshot {
+        return Snapshot {
+            tick: tick,
+            ball_x: tick as f32,
+            ball_y: 0.,
+            ball_vx: 0.,
+            ball_vy: 0.,
+            ball_speed_multiplier: 1.,
+            lpaddle_y: 0.,
+            rpaddle_y: 0.,
+            rpaddle_speed_multiplier: 1.,
+            score: MatchScore::new(),
+            clock_ms: 0,
+            slow_motions_remaining: 3,
+            slow_motion_active: false,
+            rewinds_remaining: 2
+        };
+    }
+
+    fn played(buffer: &mut SnapshotBuffer) -> Vec<u64> {
+        let mut ticks = Vec::new();
+        while let Some(snapshot) = buffer.next() {
+            ticks.push(snapshot.tick);
+        }
+        return ticks;
+    }
+
+    #[test]
+    fn playback_waits_for_the_reserve() {
+        let mut buffer = SnapshotBuffer::new();
+        for tick in 0..DELAY_TICKS as u64 {
+            buffer.push(snapshot(tick));
+            assert_eq!(buffer.next(), None);
+        }
+        buffer.push(snapshot(DELAY_TICKS as u64));
+        assert_eq!(buffer.next().map(|s| s.tick), Some(0));
+        assert_eq!(buffer.next(), None);
+    }
+
+    #[test]
+    fn snapshots_play_in_tick_order() {
+        let mut buffer = SnapshotBuffer::new();
+        for &tick in [3, 1, 4, 0, 2, 5].iter() {
+            buffer.push(snapshot(tick));
+        }
+        assert_eq!(played(&mut buffer), vec![0, 1]);
+        buffer.push(snapshot(6));
+        assert_eq!(played(&mut buffer), vec![2]);
+    }
+
+    #[test]
+    fn duplicates_and_late_snapshots_are_dropped() {
+        let mut buffer = SnapshotBuffer::new();
+        for tick in 0..DELAY_TICKS as u64 + 2 {
+            buffer.push(snapshot(tick));
+            buffer.push(snapshot(tick));
+        }
+        assert_eq!(played(&mut buffer), vec![0, 1]);
+        buffer.push(snapshot(1));
+        buffer.push(snapshot(0));
+        assert_eq!(played(&mut buffer), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn playback_catches_up_when_far_behind() {
+        let mut buffer = SnapshotBuffer::new();
+        for tick in 0..DELAY_TICKS as u64 * 3 {
+            buffer.push(snapshot(tick));
+        }
+        let first = buffer.next().map(|s| s.tick);
+        assert_eq!(first, Some(DELAY_TICKS as u64));
+    }
+
+}
@@ -0,0 +1,68 @@
+use pongo::layout::{Align, Bounds, Element};
+use pongo::match_stats::{MatchStats, Side};
+use pongo::ui::{ScalableFont, Ui};
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use std::rc::Rc;
+
+/// Show the outcome of a match along with its statistics until the player presses a key.
+/// Returns true if the player asked for a rematch, which is only offered when `can_rematch` is
+/// set.
+pub fn show(ui: &mut Ui,
+            title: &str,
+            stats: &MatchStats,
+            names: [&str; 2],
+            can_rematch: bool) -> bool {
+
+    let title_font = Rc::new(ScalableFont::new(&ui.theme.heading_font, 60));
+    let text_font = Rc::new(ScalableFont::new(&ui.theme.text_font, 22));
+    let title_color = ui.theme.highlight;
+    let text_color = ui.theme.text;
+    let prompt_color = ui.theme.heading;
+
+    let (left, right) = (Side::Left as usize, Side::Right as usize);
+    let lines = [
+        format!("Rallies: {}   Longest rally: {} hits   Total hits: {}",
+                stats.points, stats.longest_rally, stats.total_rally_hits()),
+        format!("Paddle hits: {} {}   {} {}",
+                names[left], stats.paddle_hits[left], names[right], stats.paddle_hits[right]),
+        format!("Wall bounces: {}", stats.wall_bounces),
+        format!("Top ball speed: {:.0} pixels per second", stats.top_ball_speed),
+        format!("Slow motions used: {}", stats.slow_motions_used),
+        format!("Points won on serve: {} {}   {} {}",
+                names[left], stats.points_won_on_serve[left],
+                names[right], stats.points_won_on_serve[right]),
+        format!("Match duration: {}:{:02}",
+                stats.duration_ms / 60000, stats.duration_ms / 1000 % 60)
+    ];
+
+    let prompt = if can_rematch {
+        "R for a rematch, any other key to continue"
+    } else {
+        "Press any key to continue"
+    };
+    let stats_lines = lines.iter()
+        .map(|line| Element::label(line, &text_font, text_color))
+        .collect();
+    let screen = Element::column(Align::Center, 0., vec![
+        Element::label(title, &title_font, title_color),
+        Element::space(0., 40.),
+        Element::column(Align::Center, 14., stats_lines),
+        Element::space(0., 40.),
+        Element::label(prompt, &text_font, prompt_color)
+    ]);
+
+    let background = ui.theme.background;
+    ui.renderer.set_draw_color(background);
+    ui.renderer.clear();
+    let area = Bounds::new(0., 60., ui.width, 0.);
+    screen.draw_in(ui, area, Align::Center, Align::Start);
+    ui.renderer.present();
+
+    return match ui.wait_for_dismissal() {
+        Event::KeyDown { keycode: Some(Keycode::R), .. } => can_rematch,
+        _ => false
+    };
+}
@@ -0,0 +1,78 @@
+extern crate clock_ticks;
+
+use pongo::ui::Ui;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+/// Where the player is with a text entry after an event.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextEntryStatus {
+    Editing,
+    Done,       // Enter was pressed on a non-empty text.
+    Cancelled   // Escape was pressed, or the window closed.
+}
+
+/// A single line of text typed by the player. Characters arrive through SDL text input events,
+/// so the keyboard layout and shift state are respected. Backspace deletes, enter finishes and
+/// escape cancels. Text input events are only sent between `start` and `stop`.
+pub struct TextEntry {
+    pub text: String,
+    max_chars: usize
+}
+
+impl TextEntry {
+
+    pub fn new(text: &str, max_chars: usize) -> TextEntry {
+        return TextEntry {
+            text: text.chars().take(max_chars).collect(),
+            max_chars: max_chars
+        };
+    }
+
+    /// Start receiving text input events.
+    pub fn start(ui: &Ui) {
+        ui.sdl_ctx.video().unwrap().text_input().start();
+    }
+
+    /// Stop receiving text input events, so that keys typed outside the entry are not turned
+    /// into text.
+    pub fn stop(ui: &Ui) {
+        ui.sdl_ctx.video().unwrap().text_input().stop();
+    }
+
+    pub fn handle_event(&mut self, event: &Event) -> TextEntryStatus {
+        match *event {
+            Event::TextInput { ref text, .. } => {
+                // Control characters, tabs included, would break the files names are kept in.
+                for c in text.chars().filter(|c| !c.is_control()) {
+                    if self.text.chars().count() < self.max_chars {
+                        self.text.push(c);
+                    }
+                }
+            },
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                self.text.pop();
+            },
+            Event::KeyDown { keycode: Some(Keycode::Return), .. } |
+                Event::KeyDown { keycode: Some(Keycode::KpEnter), .. } => {
+                if !self.text.trim().is_empty() {
+                    return TextEntryStatus::Done;
+                }
+            },
+            Event::KeyDown { keycode: Some(Keycode::Escape), .. } | Event::Quit {..} => {
+                return TextEntryStatus::Cancelled;
+            },
+            _ => {}
+        }
+        return TextEntryStatus::Editing;
+    }
+
+    /// The text followed by a blinking cursor, ready to be drawn. Never empty, since an empty
+    /// string cannot be rendered.
+    pub fn display_text(&self) -> String {
+        let cursor_visible = clock_ticks::precise_time_ms() / 500 % 2 == 0;
+        return format!("{}{}", self.text, if cursor_visible {"_"} else {" "});
+    }
+
+}
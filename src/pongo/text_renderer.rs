@@ -0,0 +1,356 @@
+use pongo::theme::Theme;
+use pongo::ui::ScalableFont;
+
+use sdl2::SdlResult;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Renderer, Texture};
+use sdl2::surface::Surface;
+use sdl2_ttf::{self, Font};
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Width of every atlas, in pixels. An atlas starts this tall and doubles in height whenever
+/// its glyphs no longer fit.
+const ATLAS_WIDTH: u32 = 1024;
+const INITIAL_ATLAS_HEIGHT: u32 = 256;
+
+/// Characters put in an atlas as soon as it is made, which covers nearly all the game's text.
+/// Anything else is added the first time it is drawn.
+const FIRST_PRELOADED: u8 = b' ';
+const LAST_PRELOADED: u8 = b'~';
+
+/// Space left between glyphs in an atlas, so that scaling one never picks up its neighbours.
+const GLYPH_PADDING: u32 = 1;
+
+/// Pieces of text an atlas keeps the layout of. Text that changes every frame, such as the
+/// frame rate, would otherwise fill it without end, so it starts over once this many are kept.
+const MAX_LAYOUTS: usize = 256;
+
+/// How text is drawn. The outline is drawn around each glyph and the shadow behind the whole
+/// text, offset from it. Sizes are in the same units as the font's, so they scale with it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextStyle {
+    pub color: Color,
+    pub outline: Option<(Color, u16)>,      // Color and width of the outline.
+    pub shadow: Option<(Color, f32, f32)>   // Color of the shadow and how far right and down it
+                                            // falls.
+}
+
+impl TextStyle {
+
+    pub fn plain(color: Color) -> TextStyle {
+        return TextStyle { color: color, outline: None, shadow: None };
+    }
+
+    pub fn outlined(mut self, color: Color, width: u16) -> TextStyle {
+        self.outline = Some((color, width));
+        return self;
+    }
+
+    pub fn shadowed(mut self, color: Color, x: f32, y: f32) -> TextStyle {
+        self.shadow = Some((color, x, y));
+        return self;
+    }
+
+}
+
+/// Where a glyph sits in its atlas.
+#[derive(Clone, Copy)]
+struct Glyph {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    advance: i32    // How far along the next glyph starts.
+}
+
+/// Where the glyphs of a piece of text go and how large it is, in pixels.
+#[derive(Clone)]
+struct TextLayout {
+    pens: Vec<i32>,     // Where each character starts, from the start of the text.
+    width: i32,
+    height: i32
+}
+
+/// Every glyph drawn so far in one font at one pixel size, rasterised once in white onto a
+/// surface that is uploaded as a texture. Text is drawn by copying glyphs from the texture,
+/// tinted to the color wanted.
+struct Atlas {
+    font: Font,
+    outline: i32,                   // Width in pixels of the outline the font is rasterised
+                                    // with, or 0.
+    surface: Surface<'static>,
+    texture: Option<Texture>,       // None until first drawn and after a glyph is added.
+    glyphs: HashMap<char, Glyph>,
+    layouts: HashMap<String, TextLayout>,
+    shelf_x: u32,                   // Glyphs are packed in rows, left to right. Where the next
+    shelf_y: u32,                   // one goes in the current row, and how tall the row is.
+    shelf_height: u32
+}
+
+impl Atlas {
+
+    fn new(path: &Path, pixel_size: i32, outline: i32) -> SdlResult<Atlas> {
+        let mut font = try!(Font::from_file(path, pixel_size));
+        font.set_outline(outline);
+        let mut atlas = Atlas {
+            font: font,
+            outline: outline,
+            surface: try!(Surface::new(ATLAS_WIDTH, INITIAL_ATLAS_HEIGHT,
+                                       PixelFormatEnum::RGBA8888)),
+            texture: None,
+            glyphs: HashMap::new(),
+            layouts: HashMap::new(),
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0
+        };
+        for byte in FIRST_PRELOADED..LAST_PRELOADED + 1 {
+            atlas.glyph(byte as char);
+        }
+        return Ok(atlas);
+    }
+
+    /// The glyph for a character, rasterising it first if this is the first time it is used.
+    /// A glyph that does not fit in the atlas is left blank, but keeps its advance so the rest
+    /// of the text stays where it was.
+    fn glyph(&mut self, ch: char) -> Glyph {
+        if let Some(glyph) = self.glyphs.get(&ch) {
+            return *glyph;
+        }
+        return match self.rasterise(ch) {
+            Ok(glyph) => glyph,
+            Err(_) => {
+                let glyph = self.blank_glyph(ch);
+                self.glyphs.insert(ch, glyph);
+                glyph
+            }
+        };
+    }
+
+    fn blank_glyph(&self, ch: char) -> Glyph {
+        let advance = self.font.metrics_of_char(ch).map_or(0, |metrics| metrics.advance);
+        return Glyph { x: 0, y: 0, width: 0, height: 0, advance: advance };
+    }
+
+    fn rasterise(&mut self, ch: char) -> SdlResult<Glyph> {
+        let mut glyph = self.blank_glyph(ch);
+        let white = Color::RGB(0xff, 0xff, 0xff);
+        if let Ok(mut surface) = self.font.render(ch, sdl2_ttf::blended(white)) {
+            let (width, height) = surface.size();
+            if self.shelf_x + width + GLYPH_PADDING > ATLAS_WIDTH {
+                self.shelf_x = 0;
+                self.shelf_y += self.shelf_height + GLYPH_PADDING;
+                self.shelf_height = 0;
+            }
+            while self.shelf_y + height > self.surface.height() {
+                try!(self.grow());
+            }
+
+            // Copied as it is, rather than blended over the empty atlas.
+            try!(surface.set_blend_mode(BlendMode::None));
+            let target = Rect::new_unwrap(self.shelf_x as i32, self.shelf_y as i32,
+                                          width.max(1), height.max(1));
+            try!(surface.blit(None, &mut self.surface, Some(target)));
+            glyph.x = self.shelf_x as i32;
+            glyph.y = self.shelf_y as i32;
+            glyph.width = width;
+            glyph.height = height;
+            self.shelf_x += width + GLYPH_PADDING;
+            self.shelf_height = self.shelf_height.max(height);
+            self.texture = None;
+        }
+        self.glyphs.insert(ch, glyph);
+        return Ok(glyph);
+    }
+
+    /// Double the height of the atlas, keeping the glyphs already in it.
+    fn grow(&mut self) -> SdlResult<()> {
+        let height = self.surface.height() * 2;
+        let mut surface = try!(Surface::new(ATLAS_WIDTH, height, PixelFormatEnum::RGBA8888));
+        try!(self.surface.set_blend_mode(BlendMode::None));
+        try!(self.surface.blit(None, &mut surface, None));
+        self.surface = surface;
+        return Ok(());
+    }
+
+    /// The texture holding the atlas, uploaded again if glyphs were added since it was last.
+    fn texture(&mut self, renderer: &Renderer) -> SdlResult<&mut Texture> {
+        if self.texture.is_none() {
+            let mut texture = try!(renderer.create_texture_from_surface(&self.surface));
+            texture.set_blend_mode(BlendMode::Blend);
+            self.texture = Some(texture);
+        }
+        return Ok(self.texture.as_mut().unwrap());
+    }
+
+    /// Where the glyphs of text go and how large it is. The font measures the text up to each
+    /// character, so kerning counts, and the advances of the glyphs are used where it cannot.
+    /// That takes a measurement per character, so it is done once for each piece of text and
+    /// kept.
+    fn layout(&mut self, text: &str) -> TextLayout {
+        if let Some(layout) = self.layouts.get(text) {
+            return layout.clone();
+        }
+
+        let measurable = measurable(text);
+        let mut pens = Vec::new();
+        let mut advanced = 0;
+        for (i, ch) in text.char_indices() {
+            let measured = if measurable && i > 0 {self.font.size(&text[..i]).ok()} else {None};
+            let pen = match measured {
+                Some((width, _)) => width as i32,
+                None => advanced
+            };
+            pens.push(pen);
+            advanced = pen + self.glyph(ch).advance;
+        }
+        let measured = if measurable {self.font.size(text).ok()} else {None};
+        let (width, height) = match measured {
+            Some((width, height)) => (width as i32, height as i32),
+            None => (advanced, self.font.height())
+        };
+
+        let layout = TextLayout { pens: pens, width: width, height: height };
+        if self.layouts.len() >= MAX_LAYOUTS {
+            self.layouts.clear();
+        }
+        self.layouts.insert(text.to_string(), layout.clone());
+        return layout;
+    }
+
+    /// Copy the glyphs of text from the atlas, all tinted one color, each starting at its pen
+    /// position. Positions are in pixels and glyphs are copied at the size they were
+    /// rasterised at. Glyphs of an outlined font are larger by the outline all round, so they
+    /// start up and to the left of the plain ones. Each glyph is a copy of its own, since the
+    /// renderer has no way to hand it several at once.
+    fn draw(&mut self, renderer: &mut Renderer, text: &str, pens: &[i32], color: Color, x: i32,
+            y: i32) {
+        let mut copies = Vec::new();
+        for (ch, &pen) in text.chars().zip(pens.iter()) {
+            let glyph = self.glyph(ch);
+            if glyph.width > 0 && glyph.height > 0 {
+                let source = Rect::new_unwrap(glyph.x, glyph.y, glyph.width, glyph.height);
+                let target = Rect::new_unwrap(x + pen - self.outline, y - self.outline,
+                                              glyph.width, glyph.height);
+                copies.push((source, target));
+            }
+        }
+
+        let texture = match self.texture(renderer) {
+            Ok(texture) => texture,
+            Err(_) => return
+        };
+        let (red, green, blue) = color.rgb();
+        texture.set_color_mod(red, green, blue);
+        texture.set_alpha_mod(match color { Color::RGBA(_, _, _, alpha) => alpha, _ => 0xff });
+        for &(source, target) in copies.iter() {
+            renderer.copy(texture, Some(source), Some(target));
+        }
+    }
+
+}
+
+/// Whether the font can measure text. The binding hands text to the font as Latin-1 in a C
+/// string, so only ASCII without nul characters is measured that way.
+fn measurable(text: &str) -> bool {
+    return text.bytes().all(|byte| byte > 0 && byte < 0x80);
+}
+
+/// Draws text from atlases of glyphs, one for each font, pixel size and outline in use, so
+/// text is rasterised and laid out once rather than every time it is drawn. Each pass over a
+/// piece of text copies its glyphs from a single texture with a single tint.
+pub struct TextRenderer {
+    scale: f32,         // Pixels per logical unit, which text is drawn in.
+    atlases: HashMap<(PathBuf, i32, i32), Option<Atlas>>    // By font file, pixel size and
+                                                            // outline. None if neither the
+                                                            // font nor its fallback loads.
+}
+
+impl TextRenderer {
+
+    pub fn new() -> TextRenderer {
+        return TextRenderer { scale: 1., atlases: HashMap::new() };
+    }
+
+    /// Draw text at a new number of pixels per logical unit. The atlases were rasterised at
+    /// pixel sizes for the old scale, so they are dropped rather than kept for text that will
+    /// not be drawn at those sizes again.
+    pub fn set_scale(&mut self, scale: f32) {
+        if scale != self.scale {
+            self.scale = scale;
+            self.atlases.clear();
+        }
+    }
+
+    /// The atlas for a font at the current scale, made the first time the font is used. A font
+    /// that cannot be loaded is reported once and the classic theme's text font takes its
+    /// place. None if that cannot be loaded either.
+    fn atlas(&mut self, font: &ScalableFont, outline: i32) -> Option<&mut Atlas> {
+        let pixel_size = font.pixel_size(self.scale);
+        let key = (font.path().to_path_buf(), pixel_size, outline);
+        if !self.atlases.contains_key(&key) {
+            let atlas = match Atlas::new(font.path(), pixel_size, outline) {
+                Ok(atlas) => Some(atlas),
+                Err(e) => {
+                    let fallback = Theme::classic().text_font;
+                    let _ = writeln!(&mut io::stderr(), "Unable to load font from {}: {}",
+                                     font.path().display(), e);
+                    Atlas::new(&fallback, pixel_size, outline).ok()
+                }
+            };
+            self.atlases.insert(key.clone(), atlas);
+        }
+        return self.atlases.get_mut(&key).and_then(|atlas| atlas.as_mut());
+    }
+
+    /// The logical width and height of text in a font.
+    pub fn size(&mut self, font: &ScalableFont, text: &str) -> (f32, f32) {
+        let scale = self.scale;
+        return match self.atlas(font, 0) {
+            Some(atlas) => {
+                let layout = atlas.layout(text);
+                (layout.width as f32 / scale, layout.height as f32 / scale)
+            },
+            None => (0., 0.)
+        };
+    }
+
+    /// Draw text with its top left corner at x, y, in logical units. The shadow goes down
+    /// first, then the outline and then the text itself, all with their glyphs where the plain
+    /// font puts them. The renderer's logical scaling is turned off while the glyphs are
+    /// copied, so they land on whole pixels at the size they were rasterised at rather than
+    /// being scaled down and up again.
+    pub fn draw(&mut self, renderer: &mut Renderer, font: &ScalableFont, text: &str,
+                style: &TextStyle, x: f32, y: f32) {
+        let scale = self.scale;
+        let pens = match self.atlas(font, 0) {
+            Some(atlas) => atlas.layout(text).pens,
+            None => return
+        };
+        let (scale_x, scale_y) = renderer.scale();
+        let pixel_x = |x: f32| (x * scale_x).round() as i32;
+        let pixel_y = |y: f32| (y * scale_y).round() as i32;
+        renderer.set_scale(1., 1.);
+        if let Some((color, offset_x, offset_y)) = style.shadow {
+            if let Some(atlas) = self.atlas(font, 0) {
+                atlas.draw(renderer, text, &pens, color, pixel_x(x + offset_x),
+                           pixel_y(y + offset_y));
+            }
+        }
+        if let Some((color, width)) = style.outline {
+            let outline = ((width as f32 * scale).round() as i32).max(1);
+            if let Some(atlas) = self.atlas(font, outline) {
+                atlas.draw(renderer, text, &pens, color, pixel_x(x), pixel_y(y));
+            }
+        }
+        if let Some(atlas) = self.atlas(font, 0) {
+            atlas.draw(renderer, text, &pens, style.color, pixel_x(x), pixel_y(y));
+        }
+        renderer.set_scale(scale_x, scale_y);
+    }
+
+}
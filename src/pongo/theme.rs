@@ -0,0 +1,279 @@
+use sdl2::pixels::Color;
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// First line of a theme file, followed by the version of its layout.
+const THEME_HEADER: &'static str = "pongo-theme";
+const THEME_VERSION: u32 = 1;
+
+/// Directory the themes are read from, one file per theme named after it.
+const THEME_DIR: &'static str = "assets/themes";
+
+/// Number of letters in the title, each drawn in a color of its own.
+pub const TITLE_LETTERS: usize = 5;
+
+/// The colors and fonts the game is drawn with. A theme file holds a key and a value on each
+/// line, separated by a tab, after a header line. Colors are written as #rrggbb, or #rrggbbaa
+/// with an alpha, and fonts as the path of a font file. Anything a theme leaves out is taken
+/// from the classic theme.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Theme {
+    pub name: String,
+    pub background: Color,
+    pub net: Color,
+    pub ball: Color,
+    pub left_paddle: Color,         // Until a profile with a paddle color of its own plays.
+    pub right_paddle: Color,        // Also the right side of the scoreboard.
+    pub text: Color,
+    pub heading: Color,             // Headings, prompts and banners.
+    pub highlight: Color,           // Titles, whatever has the focus and new records.
+    pub accent: Color,              // Subheadings, such as the names of the high score boards.
+    pub positive: Color,            // Things available or ready, such as slow motions left.
+    pub muted: Color,               // Things used up or unavailable.
+    pub shade: Color,               // Outlines and shadows that set text off what is behind.
+    pub title: [Color; TITLE_LETTERS],
+    pub title_font: PathBuf,        // The title on the welcome screen and names being entered.
+    pub heading_font: PathBuf,      // Headings, menus, banners and messages.
+    pub text_font: PathBuf,         // Instructions, lists and statistics.
+    pub detail_font: PathBuf,       // The HUD, credits and the scoreboard's details.
+    pub points_font: PathBuf        // The scoreboard's points.
+}
+
+impl Theme {
+
+    /// The theme the game was first drawn with.
+    pub fn classic() -> Theme {
+        return Theme {
+            name: "classic".to_string(),
+            background: Color::RGB(0x25, 0x25, 0x25),
+            net: Color::RGB(0xff, 0xff, 0xff),
+            ball: Color::RGB(0xff, 0xcc, 0x00),
+            left_paddle: Color::RGB(0x03, 0x91, 0xcf),
+            right_paddle: Color::RGB(0xeb, 0x4e, 0x3d),
+            text: Color::RGB(0xff, 0xff, 0xff),
+            heading: Color::RGB(0xec, 0x42, 0x35),
+            highlight: Color::RGB(0xfc, 0xef, 0x6d),
+            accent: Color::RGB(0xf6, 0x77, 0x34),
+            positive: Color::RGB(0x6f, 0xc3, 0x2d),
+            muted: Color::RGB(0x69, 0x69, 0x69),
+            shade: Color::RGB(0x00, 0x00, 0x00),
+            title: [Color::RGB(0x03, 0x91, 0xcf), Color::RGB(0xf6, 0x77, 0x34),
+                    Color::RGB(0xfc, 0xef, 0x6d), Color::RGB(0x6f, 0xc3, 0x2d),
+                    Color::RGB(0xf0, 0x3b, 0x32)],
+            title_font: PathBuf::from("assets/fonts/djb_pokey_dots.ttf"),
+            heading_font: PathBuf::from("assets/fonts/kghappysolid.ttf"),
+            text_font: PathBuf::from("assets/fonts/coffee_time.ttf"),
+            detail_font: PathBuf::from("assets/fonts/kg_cold_coffee.ttf"),
+            points_font: PathBuf::from("assets/fonts/pixel.ttf")
+        };
+    }
+
+    /// The names of the themes in the theme directory, classic first and the rest in order.
+    /// Classic is always there, since it is built in.
+    pub fn names() -> Vec<String> {
+        let mut names = Vec::new();
+        if let Ok(entries) = fs::read_dir(THEME_DIR) {
+            for entry in entries {
+                let path = match entry {
+                    Ok(entry) => entry.path(),
+                    Err(_) => continue
+                };
+                if !path.extension().map_or(false, |extension| extension == "txt") {
+                    continue;
+                }
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    if name != "classic" {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names.insert(0, "classic".to_string());
+        return names;
+    }
+
+    /// The file a theme is read from.
+    pub fn path(name: &str) -> PathBuf {
+        return Path::new(THEME_DIR).join(format!("{}.txt", name));
+    }
+
+    /// Read a theme from its file.
+    pub fn load(name: &str) -> io::Result<Theme> {
+        let file = try!(File::open(Theme::path(name)));
+        return Theme::read(name, BufReader::new(file));
+    }
+
+    /// Read a theme written in the layout of a theme file. Unknown keys and values that cannot
+    /// be read are skipped.
+    fn read<R: BufRead>(name: &str, reader: R) -> io::Result<Theme> {
+        let mut lines = reader.lines();
+        let header = match lines.next() {
+            Some(line) => try!(line),
+            None => String::new()
+        };
+        if header != format!("{} {}", THEME_HEADER, THEME_VERSION) {
+            return Err(io::Error::new(ErrorKind::InvalidData,
+                                      format!("unsupported theme file '{}'", header)));
+        }
+
+        let mut theme = Theme::classic();
+        theme.name = name.to_string();
+        for line in lines {
+            let line = try!(line);
+            let mut fields = line.splitn(2, '\t');
+            let (key, value) = match (fields.next(), fields.next()) {
+                (Some(key), Some(value)) => (key, value.trim()),
+                _ => continue
+            };
+            if key.ends_with("_font") {
+                let font = PathBuf::from(value);
+                match key {
+                    "title_font" => theme.title_font = font,
+                    "heading_font" => theme.heading_font = font,
+                    "text_font" => theme.text_font = font,
+                    "detail_font" => theme.detail_font = font,
+                    "points_font" => theme.points_font = font,
+                    _ => {}
+                }
+                continue;
+            }
+            if key == "title" {
+                let colors: Vec<Color> = value.split_whitespace().filter_map(parse_color).collect();
+                if colors.len() == TITLE_LETTERS {
+                    for (letter, color) in colors.into_iter().enumerate() {
+                        theme.title[letter] = color;
+                    }
+                }
+                continue;
+            }
+            let color = match parse_color(value) {
+                Some(color) => color,
+                None => continue
+            };
+            match key {
+                "background" => theme.background = color,
+                "net" => theme.net = color,
+                "ball" => theme.ball = color,
+                "left_paddle" => theme.left_paddle = color,
+                "right_paddle" => theme.right_paddle = color,
+                "text" => theme.text = color,
+                "heading" => theme.heading = color,
+                "highlight" => theme.highlight = color,
+                "accent" => theme.accent = color,
+                "positive" => theme.positive = color,
+                "muted" => theme.muted = color,
+                "shade" => theme.shade = color,
+                _ => {}
+            }
+        }
+        return Ok(theme);
+    }
+
+    /// Read a theme. Problems are reported on stderr, and the game carries on with the classic
+    /// theme.
+    pub fn load_or_default(name: &str) -> Theme {
+        return match Theme::load(name) {
+            Ok(theme) => theme,
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(), "Unable to load theme from {}: {}",
+                                 Theme::path(name).display(), e);
+                Theme::classic()
+            }
+        };
+    }
+
+}
+
+/// Read a color written as #rrggbb or #rrggbbaa.
+fn parse_color(s: &str) -> Option<Color> {
+    if !s.starts_with('#') || (s.len() != 7 && s.len() != 9) ||
+       !s[1..].chars().all(|c| c.is_digit(16)) {
+        return None;
+    }
+    let mut channels = Vec::new();
+    for i in 0..(s.len() - 1) / 2 {
+        channels.push(try_opt!(u8::from_str_radix(&s[1 + i * 2..3 + i * 2], 16).ok()));
+    }
+    return Some(match channels.len() {
+        3 => Color::RGB(channels[0], channels[1], channels[2]),
+        _ => Color::RGBA(channels[0], channels[1], channels[2], channels[3])
+    });
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{parse_color, Theme};
+
+    use sdl2::pixels::Color;
+
+    use std::io::{Cursor, ErrorKind};
+    use std::path::PathBuf;
+
+    fn read(text: &str) -> Theme {
+        return Theme::read("test", Cursor::new(text.as_bytes())).unwrap();
+    }
+
+    #[test]
+    fn colors_are_read_with_or_without_alpha() {
+        assert_eq!(parse_color("#0391cf"), Some(Color::RGB(0x03, 0x91, 0xcf)));
+        assert_eq!(parse_color("#0391CF80"), Some(Color::RGBA(0x03, 0x91, 0xcf, 0x80)));
+    }
+
+    #[test]
+    fn malformed_colors_are_rejected() {
+        let colors = ["0391cf", "#0391c", "#0391cf8", "#0391cg", "#+391cf", "#03é1c", ""];
+        for s in colors.iter() {
+            assert_eq!(parse_color(s), None);
+        }
+    }
+
+    #[test]
+    fn keys_left_out_come_from_the_classic_theme() {
+        let theme = read("pongo-theme 1\n\
+                          ball\t#00ff00\n\
+                          text_font\tassets/fonts/pixel.ttf\n\
+                          title\t#010101 #020202 #030303 #040404 #05050580\n");
+        let mut expected = Theme::classic();
+        expected.name = "test".to_string();
+        expected.ball = Color::RGB(0, 0xff, 0);
+        expected.text_font = PathBuf::from("assets/fonts/pixel.ttf");
+        expected.title = [Color::RGB(1, 1, 1), Color::RGB(2, 2, 2), Color::RGB(3, 3, 3),
+                          Color::RGB(4, 4, 4), Color::RGBA(5, 5, 5, 0x80)];
+        assert_eq!(theme, expected);
+    }
+
+    #[test]
+    fn unknown_keys_and_bad_values_are_skipped() {
+        let theme = read("pongo-theme 1\n\
+                          sparkles\t#ffffff\n\
+                          ball\tyellow\n\
+                          net\n\
+                          title\t#010101 #020202\n\
+                          muted\t #123456 \n");
+        let mut expected = Theme::classic();
+        expected.name = "test".to_string();
+        expected.muted = Color::RGB(0x12, 0x34, 0x56);
+        assert_eq!(theme, expected);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        for text in ["pongo-theme 2\nball\t#00ff00\n", "ball\t#00ff00\n", ""].iter() {
+            let err = Theme::read("test", Cursor::new(text.as_bytes())).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn shipped_themes_load() {
+        assert_eq!(Theme::load("classic").unwrap(), Theme::classic());
+        for name in Theme::names().iter() {
+            assert_eq!(&Theme::load(name).unwrap().name, name);
+        }
+    }
+
+}
@@ -1,44 +1,364 @@
-use sdl2::{AudioSubsystem, Sdl};
-use sdl2::event::Event;
+extern crate clock_ticks;
+
+use pongo::settings::Settings;
+use pongo::text_renderer::{TextRenderer, TextStyle};
+use pongo::theme::Theme;
+use pongo::window_mode::{Resolution, WindowMode};
+
+use sdl2::{AudioSubsystem, GameControllerSubsystem, Sdl, SdlResult};
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::{Event, WindowEventId};
+use sdl2::keyboard::{Keycode, LALTMOD, RALTMOD, Scancode};
+use sdl2::pixels::Color;
 use sdl2::render::Renderer;
-use sdl2_mixer::Music; 
+use sdl2::video::{FullscreenType, WindowPos};
+use sdl2_mixer::{self, Music}; 
 use sdl2_ttf::Sdl2TtfContext; 
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::thread;
+
+/// How far a controller's stick has to be pushed before it counts.
+const CONTROLLER_DEAD_ZONE: f32 = 8000.;
+
+/// Milliseconds a screen waiting for any key ignores keys and clicks after it appears.
+const DISMISS_DELAY_MS: u64 = 500;
+
+/// A font whose size is given in logical units. It is rasterised at the pixel size the window
+/// is actually scaled to, so text stays crisp at any window size, and again when that changes.
+pub struct ScalableFont {
+    path: PathBuf,
+    size: u16       // Point size at a scale of one.
+}
+
+impl ScalableFont {
+
+    pub fn new(path: &Path, size: u16) -> ScalableFont {
+        return ScalableFont { path: path.to_path_buf(), size: size };
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    /// The size in pixels the font is rasterised at for the given scale.
+    pub fn pixel_size(&self, scale: f32) -> i32 {
+        return ((self.size as f32 * scale).round() as i32).max(1);
+    }
+
+}
 
 
 /// Interface for interacting with the user. For example, obtaining user input, drawing to the
 /// screen and playing audio.
+///
+/// Everything is drawn in a fixed logical space of `width` by `height`, whatever the size of
+/// the window. The renderer scales it up or down to fit and letterboxes the rest, and mouse
+/// positions arrive in the same logical space. The settings stay with the game, which passes
+/// on the values that decide how the window is shown and how things are drawn.
 pub struct Ui {
+    pub width: f32,
+    pub height: f32,
+    pub theme: Theme,               // The colors and fonts everything is drawn with.
+    pub window_mode: WindowMode,    // How the window is shown. Alt+Enter changes it.
+    display: u32,                   // Index of the display the window is shown on.
+    resolution: Resolution,         // Size of the window, or of the display in fullscreen.
+    last_fullscreen: WindowMode,    // What Alt+Enter goes back to from a window.
+    pub object_scale: u32,          // Percent of their size the ball and paddles are drawn at.
+    pub shape_cues: bool,           // Patterns and shapes tell things apart as well as color.
     pub sdl_ctx: Sdl,
     pub renderer: Renderer<'static>,
+    text: RefCell<TextRenderer>,    // Holds fonts, so goes before the context they need.
+                                    // Knows the pixels per logical unit.
     pub ttf_ctx: Sdl2TtfContext,
     pub sdl_audio: AudioSubsystem, 
     pub ping_sound: Rc<Music>,
-    pub pong_sound: Rc<Music>
+    pub pong_sound: Rc<Music>,
+    controller_subsystem: Option<GameControllerSubsystem>,
+    controllers: Vec<GameController>,   // In the order they were connected.
+    pub quit_requested: bool            // The window was closed. Every screen backs out, all
+                                        // the way, and the game exits.
 }
 
 impl Ui {
     
-    pub fn new(sdl_ctx: Sdl, 
+    pub fn new(width: f32,
+           height: f32,
+           sdl_ctx: Sdl, 
            renderer: Renderer<'static>, 
            ttf_ctx: Sdl2TtfContext, 
            sdl_audio: AudioSubsystem, 
            ping_sound: Music, 
-           pong_sound: Music) -> Ui {
+           pong_sound: Music,
+           settings: &Settings) -> Ui {
 
-        return Ui { 
+        let mut renderer = renderer;
+        renderer.set_logical_size(width as u32, height as u32).unwrap();
+        let theme = Theme::load_or_default(&settings.theme);
+        let mut ui = Ui { 
+            width: width,
+            height: height,
+            theme: theme,
+            window_mode: settings.window_mode,
+            display: settings.display,
+            resolution: settings.resolution,
+            last_fullscreen: WindowMode::Borderless,
+            object_scale: settings.object_scale,
+            shape_cues: settings.shape_cues,
             sdl_ctx: sdl_ctx, 
             renderer: renderer,
+            text: RefCell::new(TextRenderer::new()),
             ttf_ctx: ttf_ctx,
             sdl_audio: sdl_audio,
             ping_sound: Rc::new(ping_sound),
-            pong_sound: Rc::new(pong_sound)
+            pong_sound: Rc::new(pong_sound),
+            controller_subsystem: None,
+            controllers: Vec::new(),
+            quit_requested: false
         };  
+        // Controllers already connected are announced with an event, just like ones plugged
+        // in later.
+        ui.controller_subsystem = ui.sdl_ctx.game_controller().ok();
+        if let Err(e) = ui.apply_window_mode() {
+            let _ = writeln!(&mut io::stderr(), "Unable to set up the window: {}", e);
+        }
+        ui.update_scale();
+        ui.set_volume(settings.volume);
+        return ui;
     } 
 
-    /// Poll for a single user event.
-    pub fn poll_event(&self) -> Option<Event> {
-        return self.sdl_ctx.event_pump().unwrap().poll_event();
+    /// Play sounds and music at a percent of full volume.
+    pub fn set_volume(&self, volume: u32) {
+        let volume = volume.min(100) as isize;
+        Music::set_volume(volume * sdl2_mixer::MAX_VOLUME / 100);
+    }
+
+    /// Draw everything with the named theme from now on. The theme in use is kept if the new
+    /// one cannot be read.
+    pub fn set_theme(&mut self, name: &str) -> io::Result<()> {
+        self.theme = try!(Theme::load(name));
+        return Ok(());
+    }
+
+    /// Work out the scale from the size of the window. The logical space is scaled evenly,
+    /// so the side that fits least decides.
+    fn update_scale(&mut self) {
+        if let Ok((width, height)) = self.renderer.output_size() {
+            let scale = (width as f32 / self.width).min(height as f32 / self.height);
+            if scale > 0. {
+                self.text.borrow_mut().set_scale(scale);
+            }
+        }
+    }
+
+    /// Show the window in the given mode, on the given display and at the given resolution.
+    pub fn set_window_mode(&mut self, mode: WindowMode, display: u32, resolution: Resolution)
+                           -> SdlResult<()> {
+        self.window_mode = mode;
+        self.display = display;
+        self.resolution = resolution;
+        return self.apply_window_mode();
+    }
+
+    /// Show the window in the current mode: in a window of the chosen resolution, borderless
+    /// over the whole display or fullscreen at the chosen resolution. A display that is no
+    /// longer connected falls back to the first.
+    fn apply_window_mode(&mut self) -> SdlResult<()> {
+        let video = try!(self.sdl_ctx.video());
+        let display = if (self.display as i32) < try!(video.num_video_displays()) {
+            self.display as i32
+        } else {
+            0
+        };
+        let bounds = try!(video.display_bounds(display));
+        let resolution = self.resolution;
+        if self.window_mode.is_fullscreen() {
+            self.last_fullscreen = self.window_mode;
+        }
+        {
+            let window = match self.renderer.window_mut() {
+                Some(window) => window,
+                None => return Ok(())
+            };
+
+            // Leave fullscreen first, as a fullscreen window cannot be moved between displays.
+            try!(window.set_fullscreen(FullscreenType::Off));
+            match self.window_mode {
+                WindowMode::Windowed => {
+                    window.set_size(resolution.width, resolution.height);
+                    let x = bounds.x() + (bounds.width() as i32 - resolution.width as i32) / 2;
+                    let y = bounds.y() + (bounds.height() as i32 - resolution.height as i32) / 2;
+                    window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+                },
+                WindowMode::Borderless => {
+                    window.set_position(WindowPos::Positioned(bounds.x()),
+                                        WindowPos::Positioned(bounds.y()));
+                    try!(window.set_fullscreen(FullscreenType::Desktop));
+                },
+                WindowMode::Fullscreen => {
+                    window.set_position(WindowPos::Positioned(bounds.x()),
+                                        WindowPos::Positioned(bounds.y()));
+                    // Without a mode of its own, the window switches the display to the mode
+                    // closest to its size.
+                    window.set_size(resolution.width, resolution.height);
+                    try!(window.set_display_mode(None));
+                    try!(window.set_fullscreen(FullscreenType::True));
+                }
+            }
+        }
+        self.update_scale();
+        return Ok(());
+    }
+
+    /// Switch between a window and the fullscreen mode used last. The game picks up the new
+    /// mode from `window_mode` and saves it.
+    fn toggle_fullscreen(&mut self) {
+        self.window_mode = if self.window_mode.is_fullscreen() {
+            WindowMode::Windowed
+        } else {
+            self.last_fullscreen
+        };
+        if let Err(e) = self.apply_window_mode() {
+            let _ = writeln!(&mut io::stderr(), "Unable to change the window mode: {}", e);
+        }
+    }
+
+    /// The name of each display, in the order they are numbered.
+    pub fn display_names(&self) -> SdlResult<Vec<String>> {
+        let video = try!(self.sdl_ctx.video());
+        let count = try!(video.num_video_displays());
+        return Ok((0..count).map(|display| video.display_name(display)).collect());
+    }
+
+    /// The resolutions a display supports, largest first.
+    pub fn resolutions(&self, display: u32) -> SdlResult<Vec<Resolution>> {
+        let video = try!(self.sdl_ctx.video());
+        let mut resolutions: Vec<Resolution> = Vec::new();
+        for mode_index in 0..try!(video.num_display_modes(display as i32)) {
+            let mode = try!(video.display_mode(display as i32, mode_index));
+            let resolution = Resolution::new(mode.w as u32, mode.h as u32);
+            if !resolutions.contains(&resolution) {
+                resolutions.push(resolution);
+            }
+        }
+        return Ok(resolutions);
+    }
+
+    /// Describe each display and the resolutions it supports, one display per line.
+    pub fn describe_displays(&self) -> SdlResult<Vec<String>> {
+        let mut lines = Vec::new();
+        for (display, name) in try!(self.display_names()).iter().enumerate() {
+            let resolutions: Vec<String> = try!(self.resolutions(display as u32)).iter()
+                .map(|resolution| resolution.to_string())
+                .collect();
+            lines.push(format!("{} {}: {}", display, name, resolutions.join(" ")));
+        }
+        return Ok(lines);
+    }
+
+    /// Poll for a single user event. Changes to the size of the window are picked up on the
+    /// way through, and Alt+Enter switches between a window and fullscreen wherever the game
+    /// is, without being passed on. Closing the window is remembered in `quit_requested`, so
+    /// that the screens a quit backs out to can follow it.
+    pub fn poll_event(&mut self) -> Option<Event> {
+        loop {
+            let event = self.sdl_ctx.event_pump().unwrap().poll_event();
+            match event {
+                Some(Event::Quit {..}) => self.quit_requested = true,
+                Some(Event::Window { win_event_id: WindowEventId::SizeChanged, .. }) => {
+                    self.update_scale();
+                },
+                Some(Event::ControllerDeviceAdded { which, .. }) => self.open_controller(which),
+                Some(Event::ControllerDeviceRemoved {..}) => {
+                    self.controllers.retain(|controller| controller.attached());
+                },
+                Some(Event::KeyDown { keycode: Some(Keycode::Return), keymod, repeat: false, .. })
+                    if keymod.intersects(LALTMOD | RALTMOD) => {
+                    self.toggle_fullscreen();
+                    continue;
+                },
+                _ => {}
+            }
+            return event;
+        }
+    }
+
+    /// Wait for a key press or click to dismiss the screen just drawn, and return it. Keys and
+    /// clicks already queued, such as the last moves of a match, and those made in the moment
+    /// after the screen appears are ignored, so that it is not dismissed before it is seen.
+    /// A request to quit is returned straight away.
+    pub fn wait_for_dismissal(&mut self) -> Event {
+        let shown_ms = clock_ticks::precise_time_ms();
+        loop {
+            while let Some(event) = self.poll_event() {
+                let ready = clock_ticks::precise_time_ms() - shown_ms >= DISMISS_DELAY_MS;
+                match event {
+                    Event::Quit {..} => return event,
+                    Event::KeyDown {..} | Event::MouseButtonDown {..} if ready => return event,
+                    _ => {}
+                }
+            }
+            thread::sleep_ms(50);
+        }
+    }
+
+    fn open_controller(&mut self, index: i32) {
+        if let Some(ref subsystem) = self.controller_subsystem {
+            match subsystem.open(index as u32) {
+                Ok(controller) => self.controllers.push(controller),
+                Err(e) => {
+                    let _ = writeln!(&mut io::stderr(), "Unable to open controller {}: {}", 
+                                     index, e);
+                }
+            }
+        }
+    }
+
+    /// Which way a controller's d-pad or left stick is pushed: from -1 for all the way up
+    /// to 1 for all the way down. 0 if it is left alone, or there is no such controller.
+    pub fn controller_direction(&self, index: usize) -> f32 {
+        let controller = match self.controllers.get(index) {
+            Some(controller) => controller,
+            None => return 0.
+        };
+        if controller.button(Button::DPadUp) {
+            return -1.;
+        }
+        if controller.button(Button::DPadDown) {
+            return 1.;
+        }
+        let value = controller.axis(Axis::LeftY) as f32;
+        return if value.abs() > CONTROLLER_DEAD_ZONE {(value / 32767.).max(-1.)} else {0.};
+    }
+
+    /// Show the mouse cursor in menus, and hide it while playing.
+    pub fn show_cursor(&self, show: bool) {
+        self.sdl_ctx.mouse().show_cursor(show);
+    }
+
+    /// Whether a key is currently held down.
+    pub fn is_key_pressed(&self, scancode: Scancode) -> bool {
+        return self.sdl_ctx.event_pump().unwrap().keyboard_state().is_scancode_pressed(scancode);
+    }
+
+    /// The logical width and height of text in the font supplied.
+    pub fn text_size(&self, font: &ScalableFont, text: &str) -> (f32, f32) {
+        return self.text.borrow_mut().size(font, text);
+    }
+
+    /// Draw text to the screen. The width and height are calculated from the font supplied.
+    pub fn draw_text(&mut self, font: &ScalableFont, text: &str, color: Color, x: f32, y: f32) {
+        self.draw_styled_text(font, text, &TextStyle::plain(color), x, y);
+    }
+
+    /// Draw text with an outline or a drop shadow, or both. Glyphs are rasterised at the
+    /// window's pixel size, once for each font and size, and copied to the window pixel for
+    /// pixel.
+    pub fn draw_styled_text(&mut self, font: &ScalableFont, text: &str, style: &TextStyle,
+                            x: f32, y: f32) {
+        self.text.borrow_mut().draw(&mut self.renderer, font, text, style, x, y);
     }
 
 }
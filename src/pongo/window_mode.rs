@@ -0,0 +1,129 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How the game's window is shown.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WindowMode {
+    Windowed = 0,       // A window of the chosen resolution, centered on the chosen display.
+    Borderless = 1,     // A window covering the whole display, which keeps its desktop mode.
+    Fullscreen = 2      // The display switches to the chosen resolution.
+}
+
+/// Every window mode. A mode's value indexes this array.
+pub const WINDOW_MODES: [WindowMode; 3] =
+    [WindowMode::Windowed, WindowMode::Borderless, WindowMode::Fullscreen];
+
+impl WindowMode {
+
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            WindowMode::Windowed => "windowed",
+            WindowMode::Borderless => "borderless",
+            WindowMode::Fullscreen => "fullscreen"
+        };
+    }
+
+    pub fn next(&self) -> WindowMode {
+        return WINDOW_MODES[(*self as usize + 1) % WINDOW_MODES.len()];
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        return *self != WindowMode::Windowed;
+    }
+
+}
+
+impl FromStr for WindowMode {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WindowMode, String> {
+        return match WINDOW_MODES.iter().find(|mode| mode.name() == s) {
+            Some(mode) => Ok(*mode),
+            None => Err(format!("unknown window mode '{}', expected windowed, borderless or \
+                                 fullscreen", s))
+        };
+    }
+
+}
+
+/// A size in pixels, written as width x height, such as 1280x720. Used as the size of the
+/// window when windowed and as the display's resolution in fullscreen.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32
+}
+
+impl Resolution {
+
+    pub fn new(width: u32, height: u32) -> Resolution {
+        return Resolution { width: width, height: height };
+    }
+
+}
+
+impl fmt::Display for Resolution {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}x{}", self.width, self.height);
+    }
+
+}
+
+impl FromStr for Resolution {
+
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Resolution, String> {
+        let mut parts = s.splitn(2, 'x');
+        if let (Some(width), Some(height)) = (parts.next(), parts.next()) {
+            if let (Ok(width), Ok(height)) = (width.parse::<u32>(), height.parse::<u32>()) {
+                if width > 0 && height > 0 {
+                    return Ok(Resolution::new(width, height));
+                }
+            }
+        }
+        return Err(format!("unknown resolution '{}', expected width x height such as 1280x720",
+                           s));
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Resolution, WindowMode, WINDOW_MODES};
+
+    #[test]
+    fn resolutions_survive_a_round_trip() {
+        for &(width, height) in [(800, 600), (1280, 720), (3840, 2160)].iter() {
+            let resolution = Resolution::new(width, height);
+            assert_eq!(resolution.to_string().parse::<Resolution>(), Ok(resolution));
+        }
+    }
+
+    #[test]
+    fn malformed_resolutions_are_rejected() {
+        for s in ["", "1280", "1280x", "x720", "0x720", "1280x0", "1280X720", "1280 x 720",
+                  "-1280x720", "1280x720x60", "wide"].iter() {
+            assert!(s.parse::<Resolution>().is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn window_modes_are_read_by_name() {
+        for mode in WINDOW_MODES.iter() {
+            assert_eq!(mode.name().parse::<WindowMode>(), Ok(*mode));
+        }
+        assert!("maximized".parse::<WindowMode>().is_err());
+    }
+
+    #[test]
+    fn modes_cycle_back_to_windowed() {
+        assert_eq!(WindowMode::Windowed.next(), WindowMode::Borderless);
+        assert_eq!(WindowMode::Borderless.next(), WindowMode::Fullscreen);
+        assert_eq!(WindowMode::Fullscreen.next(), WindowMode::Windowed);
+    }
+
+}
@@ -0,0 +1,442 @@
+//! Lobby and relay server for players who cannot reach each other directly, for instance
+//! because both are behind NAT. Clients register, host or list open matches and join one by
+//! its code. From then on, every game message a client sends is passed on to the other player.
+//!
+//!     pongo-relay [address]
+//!
+//! The server binds to 127.0.0.1:47900 by default, so that it can stand in for a public
+//! server on a single machine. Pass 0.0.0.0:47900 to accept players from elsewhere.
+
+extern crate clock_ticks;
+extern crate rand;
+
+#[path = "pongo/relay_protocol.rs"]
+mod relay_protocol;
+
+use rand::Rng;
+
+use relay_protocol::{CODE_LEN, DEFAULT_RELAY_ADDR, OpenMatch, PROTOCOL, RelayMessage};
+
+use std::collections::HashMap;
+use std::env;
+use std::net::{SocketAddr, UdpSocket};
+use std::str;
+use std::time::Duration;
+
+/// Clients that have sent nothing for this long are dropped. Clients in the lobby ask for the
+/// list of open matches every few seconds, so only clients that have gone away are affected.
+const CLIENT_TIMEOUT_MS: u64 = 30000;
+
+struct Client {
+    id: u32,
+    name: String,
+    last_seen_ms: u64,
+    hosting: Option<String>,        // Code of the open match this client is hosting.
+    partner: Option<SocketAddr>     // The other player, once a match has been joined.
+}
+
+struct Match {
+    host: SocketAddr,
+    rules: String,                  // The host's match rules. Only the clients make sense of them.
+    guest: Option<SocketAddr>       // Set once someone has joined. The match is no longer open.
+}
+
+pub struct RelayServer {
+    socket: UdpSocket,
+    clients: HashMap<SocketAddr, Client>,
+    matches: HashMap<String, Match>,
+    next_client_id: u32
+}
+
+impl RelayServer {
+
+    pub fn new(socket: UdpSocket) -> RelayServer {
+        return RelayServer {
+            socket: socket,
+            clients: HashMap::new(),
+            matches: HashMap::new(),
+            next_client_id: 1
+        };
+    }
+
+    /// Serve clients until the process is killed.
+    pub fn run(&mut self) {
+        let mut buf = [0; 1024];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, addr)) => {
+                    if let Ok(line) = str::from_utf8(&buf[..len]) {
+                        if let Some(message) = RelayMessage::decode(line) {
+                            self.handle(message, addr);
+                        }
+                    }
+                },
+                Err(_) => {}    // Read timed out. Fall through to expire idle clients.
+            }
+            self.expire_idle_clients();
+        }
+    }
+
+    fn send(&self, message: &RelayMessage, addr: SocketAddr) {
+        let _ = self.socket.send_to(message.encode().as_bytes(), addr);
+    }
+
+    fn handle(&mut self, message: RelayMessage, addr: SocketAddr) {
+        let now_ms = clock_ticks::precise_time_ms();
+
+        // Everyone but a registering client must be known to us.
+        if let RelayMessage::Register { name } = message {
+            self.register(name, addr, now_ms);
+            return;
+        }
+        match self.clients.get_mut(&addr) {
+            Some(client) => client.last_seen_ms = now_ms,
+            None => return
+        }
+
+        match message {
+            RelayMessage::Host { rules } => self.host(addr, rules),
+            RelayMessage::List => {
+                let open_matches = self.open_matches();
+                self.send(&RelayMessage::Matches(open_matches), addr);
+            },
+            RelayMessage::Join { code } => self.join(addr, code),
+            RelayMessage::Relay(payload) => {
+                if let Some(partner) = self.clients[&addr].partner {
+                    let _ = self.socket.send_to(payload.as_bytes(), partner);
+                }
+            },
+            RelayMessage::Bye => self.unregister(addr),
+            _ => {}
+        }
+    }
+
+    fn register(&mut self, name: String, addr: SocketAddr, now_ms: u64) {
+
+        // A client registering again starts from scratch.
+        if self.clients.contains_key(&addr) {
+            self.unregister(addr);
+        }
+        let id = self.next_client_id;
+        self.next_client_id += 1;
+        println!("{} registered as client {} ({})", name, id, addr);
+        self.clients.insert(addr, Client {
+            id: id,
+            name: name,
+            last_seen_ms: now_ms,
+            hosting: None,
+            partner: None
+        });
+        self.send(&RelayMessage::Registered { id: id }, addr);
+    }
+
+    fn host(&mut self, addr: SocketAddr, rules: String) {
+        let code = match self.clients[&addr].hosting.clone() {
+            Some(code) => code,     // Already hosting. Repeat the code in case it was lost.
+            None => {
+                let code = self.unused_code();
+                self.matches.insert(code.clone(), Match {
+                    host: addr,
+                    rules: rules,
+                    guest: None
+                });
+                let client = self.clients.get_mut(&addr).unwrap();
+                client.hosting = Some(code.clone());
+                println!("Client {} is hosting match {}", client.id, code);
+                code
+            }
+        };
+        self.send(&RelayMessage::Hosting { code: code.clone() }, addr);
+
+        // Hosts keep asking until someone joins, so let them know again if it already happened.
+        if let Some(guest) = self.matches[&code].guest {
+            let guest_name = self.clients[&guest].name.clone();
+            self.send(&RelayMessage::Paired { guest_name: guest_name }, addr);
+        }
+    }
+
+    fn join(&mut self, addr: SocketAddr, code: String) {
+        let partner = self.clients[&addr].partner;
+        let host = match self.matches.get_mut(&code) {
+            Some(m) if m.host != addr && partner.map_or(true, |p| p == m.host) &&
+                       m.guest.map_or(true, |guest| guest == addr) => {
+                m.guest = Some(addr);
+                Some(m.host)
+            },
+            _ => None
+        };
+        let host = match host {
+            Some(host) => host,
+            None => {
+                self.send(&RelayMessage::NoMatch { code: code }, addr);
+                return;
+            }
+        };
+
+        // Joins are repeated until answered. Only pair the players the first time around.
+        if partner.is_none() {
+            self.clients.get_mut(&addr).unwrap().partner = Some(host);
+            self.clients.get_mut(&host).unwrap().partner = Some(addr);
+            println!("Client {} joined match {}", self.clients[&addr].id, code);
+            let guest_name = self.clients[&addr].name.clone();
+            self.send(&RelayMessage::Paired { guest_name: guest_name }, host);
+        }
+
+        let rules = self.matches[&code].rules.clone();
+        let host_name = self.clients[&host].name.clone();
+        self.send(&RelayMessage::Joined {
+            code: code,
+            rules: rules,
+            host_name: host_name
+        }, addr);
+    }
+
+    /// Forget a client, tell its partner it has gone and close the match either of them was
+    /// hosting.
+    fn unregister(&mut self, addr: SocketAddr) {
+        if let Some(client) = self.clients.remove(&addr) {
+            println!("Client {} left", client.id);
+            if let Some(code) = client.hosting {
+                self.matches.remove(&code);
+            }
+            if let Some(partner) = client.partner {
+                let leave = format!("{} LEAVE", PROTOCOL);
+                let _ = self.socket.send_to(leave.as_bytes(), partner);
+                if let Some(partner_client) = self.clients.get_mut(&partner) {
+                    partner_client.partner = None;
+                    if let Some(code) = partner_client.hosting.take() {
+                        self.matches.remove(&code);
+                    }
+                }
+            }
+        }
+    }
+
+    fn expire_idle_clients(&mut self) {
+        let now_ms = clock_ticks::precise_time_ms();
+        let idle: Vec<SocketAddr> = self.clients.iter()
+            .filter(|&(_, client)| now_ms - client.last_seen_ms > CLIENT_TIMEOUT_MS)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in idle {
+            self.unregister(addr);
+        }
+    }
+
+    fn open_matches(&self) -> Vec<OpenMatch> {
+        let mut open_matches: Vec<OpenMatch> = self.matches.iter()
+            .filter(|&(_, m)| m.guest.is_none())
+            .map(|(code, m)| OpenMatch {
+                code: code.clone(),
+                rules: m.rules.clone(),
+                host_name: self.clients[&m.host].name.clone()
+            })
+            .collect();
+        open_matches.sort_by(|a, b| a.code.cmp(&b.code));
+        return open_matches;
+    }
+
+    /// Pick a random code of capital letters that no open match is using.
+    fn unused_code(&self) -> String {
+        let mut rng = rand::thread_rng();
+        loop {
+            let code: String = (0..CODE_LEN)
+                .map(|_| (b'A' + rng.gen_range(0, 26)) as char)
+                .collect();
+            if !self.matches.contains_key(&code) {
+                return code;
+            }
+        }
+    }
+
+}
+
+fn main() {
+    let addr = env::args().nth(1).unwrap_or(DEFAULT_RELAY_ADDR.to_string());
+    let socket = match UdpSocket::bind(&addr[..]) {
+        Ok(socket) => socket,
+        Err(e) => {
+            println!("Unable to bind to {}: {}", addr, e);
+            return;
+        }
+    };
+
+    // Wake up every second to expire idle clients, even when nobody is talking to us.
+    socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+    println!("Relay listening on {}", addr);
+    RelayServer::new(socket).run();
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use relay_protocol::{OpenMatch, PROTOCOL, RelayMessage};
+
+    use std::net::{SocketAddr, UdpSocket};
+    use std::str;
+    use std::time::Duration;
+
+    /// A player talking to the server from a socket of its own, so replies can be read back.
+    struct FakeClient {
+        socket: UdpSocket,
+        addr: SocketAddr
+    }
+
+    impl FakeClient {
+
+        fn new() -> FakeClient {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+            let addr = socket.local_addr().unwrap();
+            return FakeClient { socket: socket, addr: addr };
+        }
+
+        /// The next line the server sent, or None if nothing more came.
+        fn recv_line(&self) -> Option<String> {
+            let mut buf = [0; 1024];
+            return match self.socket.recv_from(&mut buf) {
+                Ok((len, _)) => Some(str::from_utf8(&buf[..len]).unwrap().to_string()),
+                Err(_) => None
+            };
+        }
+
+        fn recv(&self) -> Option<RelayMessage> {
+            return self.recv_line().and_then(|line| RelayMessage::decode(&line));
+        }
+
+    }
+
+    fn server() -> RelayServer {
+        return RelayServer::new(UdpSocket::bind("127.0.0.1:0").unwrap());
+    }
+
+    fn register(server: &mut RelayServer, client: &FakeClient, name: &str) {
+        server.handle(RelayMessage::Register { name: name.to_string() }, client.addr);
+        match client.recv() {
+            Some(RelayMessage::Registered { .. }) => {},
+            other => panic!("expected Registered, got {:?}", other)
+        }
+    }
+
+    fn host(server: &mut RelayServer, client: &FakeClient) -> String {
+        server.handle(RelayMessage::Host { rules: "points:11".to_string() }, client.addr);
+        return match client.recv() {
+            Some(RelayMessage::Hosting { code }) => code,
+            other => panic!("expected Hosting, got {:?}", other)
+        };
+    }
+
+    fn join(server: &mut RelayServer, client: &FakeClient, code: &str) -> Option<RelayMessage> {
+        server.handle(RelayMessage::Join { code: code.to_string() }, client.addr);
+        return client.recv();
+    }
+
+    /// Ada hosts a match and Bob joins it.
+    fn paired(server: &mut RelayServer) -> (FakeClient, FakeClient, String) {
+        let (ada, bob) = (FakeClient::new(), FakeClient::new());
+        register(server, &ada, "Ada");
+        register(server, &bob, "Bob");
+        let code = host(server, &ada);
+        assert_eq!(join(server, &bob, &code), Some(RelayMessage::Joined {
+            code: code.clone(),
+            rules: "points:11".to_string(),
+            host_name: "Ada".to_string()
+        }));
+        assert_eq!(ada.recv(), Some(RelayMessage::Paired { guest_name: "Bob".to_string() }));
+        return (ada, bob, code);
+    }
+
+    #[test]
+    fn open_matches_are_listed_until_joined() {
+        let mut server = server();
+        let (ada, bob) = (FakeClient::new(), FakeClient::new());
+        register(&mut server, &ada, "Ada");
+        register(&mut server, &bob, "Bob");
+        let code = host(&mut server, &ada);
+
+        server.handle(RelayMessage::List, bob.addr);
+        assert_eq!(bob.recv(), Some(RelayMessage::Matches(vec![OpenMatch {
+            code: code.clone(),
+            rules: "points:11".to_string(),
+            host_name: "Ada".to_string()
+        }])));
+
+        assert!(join(&mut server, &bob, &code).is_some());
+        server.handle(RelayMessage::List, bob.addr);
+        assert_eq!(bob.recv(), Some(RelayMessage::Matches(Vec::new())));
+    }
+
+    #[test]
+    fn repeated_host_and_join_pair_the_players_once() {
+        let mut server = server();
+        let (ada, bob, code) = paired(&mut server);
+
+        // The guest repeats its join until answered. The host hears of it only once.
+        assert!(join(&mut server, &bob, &code).is_some());
+        assert_eq!(ada.recv(), None);
+
+        // The host repeats its request to host until told someone joined.
+        assert_eq!(host(&mut server, &ada), code);
+        assert_eq!(ada.recv(), Some(RelayMessage::Paired { guest_name: "Bob".to_string() }));
+        assert_eq!(server.matches.len(), 1);
+    }
+
+    #[test]
+    fn matches_that_cannot_be_joined_are_refused() {
+        let mut server = server();
+        let (ada, _, code) = paired(&mut server);
+        let carol = FakeClient::new();
+        register(&mut server, &carol, "Carol");
+
+        let no_match = |code: &str| Some(RelayMessage::NoMatch { code: code.to_string() });
+        assert_eq!(join(&mut server, &carol, &code), no_match(&code));
+        assert_eq!(join(&mut server, &carol, "ZZZZ"), no_match("ZZZZ"));
+        assert_eq!(join(&mut server, &ada, &code), no_match(&code));
+
+        // Clients that never registered are ignored.
+        let stranger = FakeClient::new();
+        assert_eq!(join(&mut server, &stranger, &code), None);
+    }
+
+    #[test]
+    fn game_messages_are_passed_to_the_partner() {
+        let mut server = server();
+        let (ada, bob, _) = paired(&mut server);
+        let start = format!("{} START", PROTOCOL);
+        server.handle(RelayMessage::Relay(start.clone()), ada.addr);
+        assert_eq!(bob.recv_line(), Some(start));
+        let input = format!("{} INPUT 12 270.5", PROTOCOL);
+        server.handle(RelayMessage::Relay(input.clone()), bob.addr);
+        assert_eq!(ada.recv_line(), Some(input));
+    }
+
+    #[test]
+    fn leaving_closes_the_match_and_tells_the_partner() {
+        let mut server = server();
+        let (ada, bob, _) = paired(&mut server);
+        server.handle(RelayMessage::Bye, bob.addr);
+        assert_eq!(ada.recv_line(), Some(format!("{} LEAVE", PROTOCOL)));
+        assert!(server.matches.is_empty());
+        assert_eq!(server.clients[&ada.addr].partner, None);
+        assert_eq!(server.clients[&ada.addr].hosting, None);
+
+        // Nothing is passed on once the partner has gone.
+        server.handle(RelayMessage::Relay(format!("{} START", PROTOCOL)), ada.addr);
+        assert_eq!(bob.recv_line(), None);
+    }
+
+    #[test]
+    fn a_host_leaving_closes_its_open_match() {
+        let mut server = server();
+        let (ada, bob) = (FakeClient::new(), FakeClient::new());
+        register(&mut server, &ada, "Ada");
+        register(&mut server, &bob, "Bob");
+        let code = host(&mut server, &ada);
+        server.handle(RelayMessage::Bye, ada.addr);
+        assert_eq!(join(&mut server, &bob, &code),
+                   Some(RelayMessage::NoMatch { code: code.clone() }));
+    }
+
+}